@@ -11,7 +11,7 @@ use bellman_bignat::hash::circuit::CircuitHasher;
 use bellman_bignat::hash::hashes::{Mimc, Pedersen, Poseidon, Sha256};
 use bellman_bignat::hash::Hasher;
 use bellman_bignat::set::merkle::{MerkleSetBench, MerkleSetBenchInputs, MerkleSetBenchParams};
-use bellman_bignat::set::rsa::{SetBench, SetBenchInputs, SetBenchParams};
+use bellman_bignat::set::rsa::{ChallengeSource, SetBench, SetBenchInputs, SetBenchParams};
 use docopt::Docopt;
 use num_bigint::BigUint;
 use sapling_crypto::bellman::pairing::bls12_381::Bls12;
@@ -172,32 +172,26 @@ fn rsa_bench<E: Engine, H: Hasher<F = E::Fr> + CircuitHasher<E = E>>(
     };
 
     let n_untouched = if full { (1usize << c).saturating_sub(t) } else { 0 };
+    let params = SetBenchParams {
+        group,
+        limb_width: 32,
+        n_bits_elem: RSA_SIZE,
+        n_trailing_ones: 1,
+        n_bits_challenge: 256,
+        challenge_source: ChallengeSource::FiatShamir,
+        n_bits_base: RSA_SIZE,
+        item_size: ELEMENT_SIZE,
+        n_inserts: t,
+        n_removes: t,
+        window_bits: None,
+        unique_inserts: false,
+        optional_slots: false,
+        hasher: hash,
+        verbose: false,
+    };
     let circuit = SetBench {
-        inputs: Some(SetBenchInputs::from_counts(
-            n_untouched,
-            t,
-            t,
-            ELEMENT_SIZE,
-            hash.clone(),
-            RSA_SIZE,
-            32,
-            RsaQuotientGroup {
-                g: BigUint::from(2usize),
-                m: BigUint::from_str(RSA_2048).unwrap(),
-            },
-        )),
-        params: SetBenchParams {
-            group: group.clone(),
-            limb_width: 32,
-            n_bits_elem: RSA_SIZE,
-            n_bits_challenge: 256,
-            n_bits_base: RSA_SIZE,
-            item_size: ELEMENT_SIZE,
-            n_inserts: t,
-            n_removes: t,
-            hasher: hash,
-            verbose: false,
-        },
+        inputs: Some(SetBenchInputs::from_counts(n_untouched, t, t, &params)),
+        params,
     };
 
     match synth {
@@ -1,129 +1,250 @@
 extern crate bellman_bignat;
+extern crate docopt;
+extern crate exitcode;
+extern crate log;
 extern crate num_bigint;
 extern crate rand;
 extern crate sapling_crypto;
+extern crate serde;
+extern crate serde_json;
 
-use bellman_bignat::mp::bignat::nat_to_limbs;
 use bellman_bignat::group::RsaQuotientGroup;
 use bellman_bignat::hash::hashes::Poseidon;
+use bellman_bignat::io::groth16_json::{
+    from_proof, from_public_inputs, read_verifying_key, verify_json_proof, write_verifying_key_json,
+    ProofJson,
+};
 use bellman_bignat::set::int_set::NaiveExpSet;
-use bellman_bignat::set::GenSet;
-use bellman_bignat::set::rsa::{SetBench, SetBenchInputs, SetBenchParams};
+use bellman_bignat::set::rsa::{
+    ChallengeSource, SetBench, SetBenchInputs, SetBenchParams, SetInputsJson,
+};
+use docopt::Docopt;
+use log::{LevelFilter, Log, Metadata, Record};
 use num_bigint::BigUint;
-
+use rand::thread_rng;
+use sapling_crypto::bellman::groth16::{create_random_proof, generate_random_parameters, Parameters};
+use sapling_crypto::bellman::pairing::bn256::Bn256;
+use serde::Deserialize;
+
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::str::FromStr;
 use std::time::Instant;
 
+const USAGE: &str = "
+Set Proof
+
+Usage:
+  set_proof setup [-v] [--format=<fmt>] <transactions> <params> <vk>
+  set_proof prove [-v] <params> <inputs> <proof> <public>
+  set_proof verify <vk> <proof> <public>
+  set_proof (-h | --help)
+
+Commands:
+  setup   Generate parameters for <transactions> swaps, writing the swap count (4 bytes,
+          big-endian) and then the parameters (bellman format) to <params>, and the verifying
+          key to <vk> (JSON, or bellman format with --format bin).
+  prove   Prove the swaps in the JSON file <inputs>, which has `initial`, `to_remove`, and
+          `to_insert` lists of items, each of 5 decimal field elements. Writes the proof and
+          public inputs as JSON.
+  verify  Check a JSON proof and public inputs against a verifying key in either format.
+
+Options:
+  -h --help         Show this screen.
+  -v --verbose      Report the progress of circuit synthesis on stderr.
+  --format=<fmt>    Encoding of the verifying key: json or bin [default: json].
+";
+
 // From https://en.wikipedia.org/wiki/RSA_numbers#RSA-2048
 const RSA_2048: &str = "25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525880784406918290641249515082189298559149176184502808489120072844992687392807287776735971418347270261896375014971824691165077613379859095700097330459748808428401797429100642458691817195118746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739934236584823824281198163815010674810451660377306056201619676256133844143603833904414952634432190114657544454178424020924616515723350778707749817125772467962926386356373289912154831438167899885040445364023527381951378636564391212010397122822120720357";
 const RSA_SIZE: usize = 2048;
 const ELEMENT_SIZE: usize = 5;
 
-fn main() {
-    color_backtrace::install();
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_transactions: Option<usize>,
+    arg_params: String,
+    arg_vk: String,
+    arg_inputs: String,
+    arg_proof: String,
+    arg_public: String,
+    flag_format: String,
+    flag_verbose: bool,
+    cmd_setup: bool,
+    cmd_prove: bool,
+    cmd_verify: bool,
+}
 
-    let n_swaps = std::env::args()
-        .nth(1)
-        .and_then(|a| usize::from_str(&a).ok())
-        .expect("Provide the number of transactions as the first argument");
+/// Unwraps a result, or reports the error and exits with `code`.
+trait OrExit<T> {
+    fn or_exit(self, code: exitcode::ExitCode, what: &str) -> T;
+}
 
-    use rand::thread_rng;
+impl<T, E: Display> OrExit<T> for Result<T, E> {
+    fn or_exit(self, code: exitcode::ExitCode, what: &str) -> T {
+        self.unwrap_or_else(|e| {
+            eprintln!("{}: {}", what, e);
+            std::process::exit(code)
+        })
+    }
+}
 
-    use sapling_crypto::bellman::groth16::{
-        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
-    };
+/// Prints log messages to stderr.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        eprintln!("{}", record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static STDERR_LOGGER: StderrLogger = StderrLogger;
 
-    let group = RsaQuotientGroup {
+fn group() -> RsaQuotientGroup {
+    RsaQuotientGroup {
         g: BigUint::from(2usize),
         m: BigUint::from_str(RSA_2048).unwrap(),
-    };
-    let rng = &mut thread_rng();
-
-    let generate_params_start = Instant::now();
-
-    let params = {
-        let c = SetBench::<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>> {
-            inputs: None,
-            params: SetBenchParams {
-                group: group.clone(),
-                limb_width: 32,
-                n_bits_elem: RSA_SIZE,
-                n_bits_challenge: 128,
-                n_bits_base: RSA_SIZE,
-                item_size: ELEMENT_SIZE,
-                n_inserts: n_swaps,
-                n_removes: n_swaps,
-                hasher: Poseidon::default(),
-                verbose: true,
-            },
-        };
-        let p = generate_random_parameters(c, rng);
-        println!("Params are okay: {:#?}", p.is_ok());
-        p.unwrap()
-    };
+    }
+}
 
-    let generate_params_end = Instant::now();
-
-    println!(
-        "Done with parameters, duration {:?}",
-        generate_params_end - generate_params_start
-    );
-
-    let pvk = prepare_verifying_key(&params.vk);
-    println!("Done with key");
-
-    // Create a groth16 proof with our parameters.
-    let circuit = SetBench {
-        inputs: Some(SetBenchInputs::from_counts(
-            0,
-            n_swaps,
-            n_swaps,
-            ELEMENT_SIZE,
-            Poseidon::default(),
-            RSA_SIZE,
-            32,
-            RsaQuotientGroup {
-                g: BigUint::from(2usize),
-                m: BigUint::from_str(RSA_2048).unwrap(),
-            },
-        )),
-        params: SetBenchParams {
-            group: group.clone(),
-            limb_width: 32,
-            n_bits_elem: RSA_SIZE,
-            n_bits_challenge: 128,
-            n_bits_base: RSA_SIZE,
-            item_size: ELEMENT_SIZE,
-            n_inserts: n_swaps,
-            n_removes: n_swaps,
-            hasher: Poseidon::default(),
-            verbose: true,
-        },
-    };
+fn set_params(n_swaps: usize, verbose: bool) -> SetBenchParams<Poseidon<Bn256>> {
+    SetBenchParams {
+        group: group(),
+        limb_width: 32,
+        n_bits_elem: RSA_SIZE,
+        n_trailing_ones: 1,
+        n_bits_challenge: 128,
+        challenge_source: ChallengeSource::FiatShamir,
+        n_bits_base: RSA_SIZE,
+        item_size: ELEMENT_SIZE,
+        n_inserts: n_swaps,
+        n_removes: n_swaps,
+        window_bits: None,
+        unique_inserts: false,
+        optional_slots: false,
+        hasher: Poseidon::default(),
+        verbose,
+    }
+}
 
-    let ins = circuit.inputs.as_ref().unwrap();
-    let mut initial_set = ins.initial_state.clone();
-    let mut final_set = {
-        let mut t = initial_set.clone();
-        t.swap_all(ins.to_remove.clone(), ins.to_insert.clone());
-        t
+fn setup(args: &Args) {
+    group().validate().or_exit(exitcode::DATAERR, "Invalid RSA group");
+    let n_swaps = args.arg_transactions.unwrap();
+    if n_swaps > u32::max_value() as usize {
+        eprintln!("Too many swaps: {}", n_swaps);
+        std::process::exit(exitcode::USAGE);
+    }
+    let start = Instant::now();
+    let circuit = SetBench::<_, NaiveExpSet<RsaQuotientGroup>> {
+        inputs: None,
+        params: set_params(n_swaps, args.flag_verbose),
     };
+    let params = generate_random_parameters(circuit, &mut thread_rng())
+        .or_exit(exitcode::SOFTWARE, "Could not generate parameters");
+    println!("Done with parameters, duration {:?}", start.elapsed());
+
+    let file = File::create(&args.arg_params).or_exit(exitcode::CANTCREAT, &args.arg_params);
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(&(n_swaps as u32).to_be_bytes())
+        .or_exit(exitcode::IOERR, &args.arg_params);
+    params
+        .write(&mut writer)
+        .or_exit(exitcode::IOERR, &args.arg_params);
+    let file = File::create(&args.arg_vk).or_exit(exitcode::CANTCREAT, &args.arg_vk);
+    if args.flag_format == "bin" {
+        params
+            .vk
+            .write(BufWriter::new(file))
+            .or_exit(exitcode::IOERR, &args.arg_vk);
+    } else {
+        write_verifying_key_json(&params.vk, BufWriter::new(file))
+            .or_exit(exitcode::IOERR, &args.arg_vk);
+    }
+}
 
-    let prover_start = Instant::now();
-
-    let proof = create_random_proof(circuit, &params, rng).unwrap();
+fn prove(args: &Args) {
+    let file = File::open(&args.arg_params).or_exit(exitcode::NOINPUT, &args.arg_params);
+    let mut reader = BufReader::new(file);
+    let mut n_swaps = [0u8; 4];
+    reader
+        .read_exact(&mut n_swaps)
+        .or_exit(exitcode::DATAERR, &args.arg_params);
+    let n_swaps = u32::from_be_bytes(n_swaps) as usize;
+    let params = Parameters::<Bn256>::read(reader, true)
+        .or_exit(exitcode::DATAERR, &args.arg_params);
+    let inputs = fs::read_to_string(&args.arg_inputs).or_exit(exitcode::NOINPUT, &args.arg_inputs);
+    let inputs: SetInputsJson =
+        serde_json::from_str(&inputs).or_exit(exitcode::DATAERR, &args.arg_inputs);
+
+    // The parameters fix the number of swaps, so from_json rejects inputs with another.
+    let set_params = set_params(n_swaps, args.flag_verbose);
+    let mut circuit = SetBench::<_, NaiveExpSet<RsaQuotientGroup>> {
+        inputs: Some(
+            SetBenchInputs::from_json(&inputs, &set_params)
+                .or_exit(exitcode::DATAERR, &args.arg_inputs),
+        ),
+        params: set_params,
+    };
+    let public = circuit
+        .public_inputs()
+        .ok_or("the digests do not fit in the public inputs")
+        .or_exit(exitcode::DATAERR, &args.arg_inputs);
+    let start = Instant::now();
+    let proof = create_random_proof(circuit, &params, &mut thread_rng())
+        .or_exit(exitcode::SOFTWARE, "Could not prove the swaps");
+    println!("Done with proof, duration: {:?}", start.elapsed());
+
+    let file = File::create(&args.arg_proof).or_exit(exitcode::CANTCREAT, &args.arg_proof);
+    serde_json::to_writer_pretty(file, &from_proof(&proof))
+        .or_exit(exitcode::IOERR, &args.arg_proof);
+    let file = File::create(&args.arg_public).or_exit(exitcode::CANTCREAT, &args.arg_public);
+    serde_json::to_writer_pretty(file, &from_public_inputs(&public))
+        .or_exit(exitcode::IOERR, &args.arg_public);
+}
 
-    let prover_end = Instant::now();
-    println!("Done with proof, duration: {:?}", prover_end - prover_start);
-    use sapling_crypto::bellman::pairing::bn256::Bn256;
-    use sapling_crypto::bellman::pairing::ff::ScalarEngine;
-    let mut inputs: Vec<<Bn256 as ScalarEngine>::Fr> = nat_to_limbs(&group.g, 32, 64).unwrap();
-    inputs.extend(nat_to_limbs::<<Bn256 as ScalarEngine>::Fr>(&group.m, 32, 64).unwrap());
-    inputs.extend(
-        nat_to_limbs::<<Bn256 as ScalarEngine>::Fr>(&initial_set.digest(), 32, 64).unwrap(),
-    );
-    inputs
-        .extend(nat_to_limbs::<<Bn256 as ScalarEngine>::Fr>(&final_set.digest(), 32, 64).unwrap());
+fn verify(args: &Args) {
+    let read_json = |path: &str| fs::read_to_string(path).or_exit(exitcode::NOINPUT, path);
+    let vk_bytes = fs::read(&args.arg_vk).or_exit(exitcode::NOINPUT, &args.arg_vk);
+    let vk = read_verifying_key(&vk_bytes).or_exit(exitcode::DATAERR, &args.arg_vk);
+    let proof_json: ProofJson = serde_json::from_str(&read_json(&args.arg_proof))
+        .or_exit(exitcode::DATAERR, &args.arg_proof);
+    let public_json: Vec<String> = serde_json::from_str(&read_json(&args.arg_public))
+        .or_exit(exitcode::DATAERR, &args.arg_public);
+
+    let verified = verify_json_proof(&vk, &proof_json, &public_json)
+        .or_exit(exitcode::DATAERR, "Could not verify the proof");
+    println!("verified {:?}", verified);
+    if !verified {
+        std::process::exit(exitcode::DATAERR);
+    }
+}
 
-    println!("verified {:?}", verify_proof(&pvk, &proof, &inputs));
+fn main() {
+    color_backtrace::install();
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+    if args.flag_format != "json" && args.flag_format != "bin" {
+        eprintln!("Unknown key format {}, expected json or bin", args.flag_format);
+        std::process::exit(exitcode::USAGE);
+    }
+    if args.flag_verbose {
+        log::set_logger(&STDERR_LOGGER).expect("a logger is already set");
+        log::set_max_level(LevelFilter::Info);
+    }
+    if args.cmd_setup {
+        setup(&args);
+    } else if args.cmd_prove {
+        prove(&args);
+    } else if args.cmd_verify {
+        verify(&args);
+    }
 }
@@ -0,0 +1,26 @@
+/* C interface to bellman-bignat's small set prover. See src/ffi.rs. */
+#ifndef BELLMAN_BIGNAT_H
+#define BELLMAN_BIGNAT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define BBN_OK 0
+#define BBN_NULL_ARGUMENT -1
+#define BBN_INVALID_INPUT -2
+#define BBN_PROVING_FAILED -3
+#define BBN_BUFFER_TOO_SMALL -4
+
+typedef struct BbnParams BbnParams;
+
+/* Returns NULL on failure. Release with bbn_params_free. */
+BbnParams *bbn_params_load(const char *path);
+void bbn_params_free(BbnParams *params);
+
+/* `inputs` holds a JSON swap: {"untouched": [...], "removed": [...], "inserted": [...]}.
+ * `*out_len` is set to the proof length; BBN_BUFFER_TOO_SMALL is returned if it exceeds
+ * `out_cap`. */
+int bbn_prove(const BbnParams *params, const uint8_t *inputs, size_t inputs_len,
+              uint8_t *out, size_t out_cap, size_t *out_len);
+
+#endif
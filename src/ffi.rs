@@ -0,0 +1,223 @@
+//! A C ABI for proving swaps in the small set configuration of `io::small_set`.
+//!
+//! Parameters are loaded into an opaque handle, which the caller must release with
+//! `bbn_params_free`. Nothing else is allocated across the boundary: inputs are borrowed for the
+//! duration of a call, and proofs are written into a buffer owned by the caller.
+
+use sapling_crypto::bellman::groth16::Parameters;
+use sapling_crypto::bellman::pairing::bn256::{Bn256, G1Affine, G2Affine};
+use sapling_crypto::bellman::pairing::{CurveAffine, EncodedPoint};
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use io::small_set::{self, parse_swap};
+
+pub const BBN_OK: c_int = 0;
+pub const BBN_NULL_ARGUMENT: c_int = -1;
+pub const BBN_INVALID_INPUT: c_int = -2;
+pub const BBN_PROVING_FAILED: c_int = -3;
+pub const BBN_BUFFER_TOO_SMALL: c_int = -4;
+
+/// Groth16 parameters for the small set circuit.
+pub struct BbnParams(Parameters<Bn256>);
+
+/// Loads bellman-serialized parameters from the file at `path`.
+/// Returns null if `path` is null or the file cannot be read.
+#[no_mangle]
+pub unsafe extern "C" fn bbn_params_load(path: *const c_char) -> *mut BbnParams {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(_) => return ptr::null_mut(),
+    };
+    match File::open(path).and_then(|f| Parameters::read(BufReader::new(f), true)) {
+        Ok(params) => Box::into_raw(Box::new(BbnParams(params))),
+        Err(e) => {
+            eprintln!("Could not load parameters from {}: {}", path, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases parameters returned by `bbn_params_load`. Does nothing if `params` is null.
+#[no_mangle]
+pub unsafe extern "C" fn bbn_params_free(params: *mut BbnParams) {
+    if !params.is_null() {
+        drop(Box::from_raw(params));
+    }
+}
+
+/// The length of a bellman-serialized proof, which is the same for every proof: two compressed
+/// G1 points and a compressed G2 point.
+#[no_mangle]
+pub extern "C" fn bbn_proof_size() -> usize {
+    2 * <G1Affine as CurveAffine>::Compressed::size()
+        + <G2Affine as CurveAffine>::Compressed::size()
+}
+
+/// Proves the swap in the `inputs_len` bytes at `inputs`, which hold a JSON `SwapJson`, and writes
+/// the bellman-serialized proof to `out`.
+///
+/// `*out_len` is always set to the length of the proof, `bbn_proof_size()`. If that exceeds
+/// `out_cap`, nothing is proved or written and `BBN_BUFFER_TOO_SMALL` is returned.
+#[no_mangle]
+pub unsafe extern "C" fn bbn_prove(
+    params: *const BbnParams,
+    inputs: *const u8,
+    inputs_len: usize,
+    out: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> c_int {
+    if params.is_null() || inputs.is_null() || out_len.is_null() || (out.is_null() && out_cap > 0)
+    {
+        return BBN_NULL_ARGUMENT;
+    }
+    let inputs = match std::str::from_utf8(slice::from_raw_parts(inputs, inputs_len)) {
+        Ok(inputs) => inputs,
+        Err(_) => return BBN_INVALID_INPUT,
+    };
+    let swap = match parse_swap(inputs) {
+        Ok(swap) => swap,
+        Err(e) => {
+            eprintln!("Invalid swap: {}", e);
+            return BBN_INVALID_INPUT;
+        }
+    };
+    *out_len = bbn_proof_size();
+    if *out_len > out_cap {
+        return BBN_BUFFER_TOO_SMALL;
+    }
+    let params = &(*params).0;
+    // Unwinding across the boundary is undefined behavior, so panics become error codes.
+    let proof = match catch_unwind(AssertUnwindSafe(|| small_set::prove(params, &swap))) {
+        Ok(Ok(proof)) => proof,
+        Ok(Err(e)) => {
+            eprintln!("Proving failed: {}", e);
+            return BBN_PROVING_FAILED;
+        }
+        Err(_) => return BBN_PROVING_FAILED,
+    };
+    let mut bytes = Vec::new();
+    proof.write(&mut bytes).expect("writing to a Vec cannot fail");
+    debug_assert_eq!(bytes.len(), *out_len);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len());
+    BBN_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::thread_rng;
+    use sapling_crypto::bellman::groth16::{
+        generate_random_parameters, prepare_verifying_key, verify_proof, Proof,
+    };
+
+    use std::ffi::CString;
+
+    use io::small_set::{small_circuit, SwapJson};
+
+    fn item(last: &str) -> Vec<String> {
+        ["0", "1", "2", "3", last].iter().map(|s| s.to_string()).collect()
+    }
+
+    fn swap() -> SwapJson {
+        SwapJson {
+            untouched: vec![],
+            removed: vec![item("4")],
+            inserted: vec![item("5")],
+        }
+    }
+
+    #[test]
+    fn prove_through_ffi() {
+        let params =
+            generate_random_parameters(small_circuit(1, None), &mut thread_rng()).unwrap();
+        let path = std::env::temp_dir().join(format!("bbn-ffi-params-{}", std::process::id()));
+        params.write(File::create(&path).unwrap()).unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let inputs = serde_json::to_vec(&swap()).unwrap();
+
+        unsafe {
+            let handle = bbn_params_load(c_path.as_ptr());
+            assert!(!handle.is_null());
+
+            let mut len = 0;
+            let code = bbn_prove(
+                handle,
+                inputs.as_ptr(),
+                inputs.len(),
+                ptr::null_mut(),
+                0,
+                &mut len,
+            );
+            assert_eq!(code, BBN_BUFFER_TOO_SMALL);
+            assert_eq!(len, bbn_proof_size());
+
+            let mut out = vec![0u8; bbn_proof_size()];
+            let code = bbn_prove(
+                handle,
+                inputs.as_ptr(),
+                inputs.len(),
+                out.as_mut_ptr(),
+                out.len(),
+                &mut len,
+            );
+            assert_eq!(code, BBN_OK);
+            assert_eq!(len, out.len());
+
+            let mut unbalanced = swap();
+            unbalanced.inserted.clear();
+            let unbalanced = serde_json::to_vec(&unbalanced).unwrap();
+            let mut unused = 0;
+            let code = bbn_prove(
+                handle,
+                unbalanced.as_ptr(),
+                unbalanced.len(),
+                ptr::null_mut(),
+                0,
+                &mut unused,
+            );
+            assert_eq!(code, BBN_INVALID_INPUT);
+            bbn_params_free(handle);
+
+            let proof = Proof::<Bn256>::read(&out[..]).unwrap();
+            let public = small_circuit(1, Some(&swap())).public_inputs().unwrap();
+            let pvk = prepare_verifying_key(&params.vk);
+            assert!(verify_proof(&pvk, &proof, &public).unwrap());
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ffi_rejects_null_arguments() {
+        let missing = CString::new("/nonexistent/bbn-params").unwrap();
+        let inputs = serde_json::to_vec(&swap()).unwrap();
+        let mut len = 0;
+        unsafe {
+            assert!(bbn_params_load(ptr::null()).is_null());
+            assert!(bbn_params_load(missing.as_ptr()).is_null());
+            bbn_params_free(ptr::null_mut());
+            assert_eq!(
+                bbn_prove(
+                    ptr::null(),
+                    inputs.as_ptr(),
+                    inputs.len(),
+                    ptr::null_mut(),
+                    0,
+                    &mut len
+                ),
+                BBN_NULL_ARGUMENT
+            );
+        }
+    }
+}
@@ -1,21 +1,36 @@
-use num_bigint::BigUint;
+use num_bigint::{BigUint, ToBigInt};
+use num_integer::Integer;
 use num_traits::One;
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{ConstraintSystem, LinearCombination, SynthesisError};
+use serde::{Deserialize, Serialize};
 
 use std::cmp::{min, Eq, PartialEq};
 use std::fmt::{self, Debug, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
 
+use hash::miller_rabin_prime::helper::miller_rabin;
 use mp::bignat::{BigNat, BigNatParams};
 use mp::exp::optimal_k;
 use util::bit::{Bit, Bitvector};
+use util::convert::nat_decimal;
 use util::gadget::Gadget;
+use util::profiling::{count_modpow, count_mult_mod};
 
+/// The group an accumulator lives in. Sets (`set::int_set`) and proofs of exponentiation
+/// (`wesolowski`) only use this interface, so a group without a trusted setup, such as a class
+/// group, can replace `RsaGroup` and `RsaQuotientGroup` by implementing it (and
+/// `CircuitSemiGroup` for use in circuits).
 pub trait SemiGroup: Clone + Eq + Debug + Display {
     type Elem: Clone + Debug + Ord + Display;
+    /// The group operation, which must be associative and commutative.
     fn op(&self, a: &Self::Elem, b: &Self::Elem) -> Self::Elem;
     fn identity(&self) -> Self::Elem;
+    /// The base of the accumulator: the digest of the empty set.
     fn generator(&self) -> Self::Elem;
+    /// `b` raised to `e` under `op`. Groups with a faster exponentiation should override this.
     fn power(&self, b: &Self::Elem, e: &BigUint) -> Self::Elem {
         let mut acc = self.identity();
         let bits = e.to_str_radix(2);
@@ -32,11 +47,29 @@ pub trait SemiGroup: Clone + Eq + Debug + Display {
         }
         acc
     }
+
+    /// The inverse of `a` under `op`, if it has one. Semigroups, and groups which cannot compute
+    /// inverses, leave this as `None`.
+    fn inverse(&self, _a: &Self::Elem) -> Option<Self::Elem> {
+        None
+    }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+/// The inverse of `a` modulo `m`, if they are coprime.
+fn inverse_mod(a: &BigUint, m: &BigUint) -> Option<BigUint> {
+    let m = m.to_bigint().unwrap();
+    let egcd = a.to_bigint().unwrap().extended_gcd(&m);
+    if !egcd.gcd.is_one() {
+        return None;
+    }
+    egcd.x.mod_floor(&m).to_biguint()
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RsaGroup {
+    #[serde(with = "nat_decimal")]
     pub g: BigUint,
+    #[serde(with = "nat_decimal")]
     pub m: BigUint,
 }
 
@@ -49,12 +82,32 @@ impl Debug for RsaGroup {
     }
 }
 
+/// Writes `n` in hex, eliding all but the first and last 8 digits of longer numbers.
+fn abbreviated_hex(n: &BigUint) -> String {
+    let hex = n.to_str_radix(16);
+    if hex.len() <= 16 {
+        format!("0x{}", hex)
+    } else {
+        format!("0x{}..{}", &hex[..8], &hex[hex.len() - 8..])
+    }
+}
+
+/// A short description of a group for logs: the width of the modulus, and the modulus and
+/// generator abbreviated. `Debug` shows them in full.
+fn fmt_rsa_group(f: &mut Formatter, name: &str, g: &BigUint, m: &BigUint) -> fmt::Result {
+    write!(
+        f,
+        "{}({}-bit modulus {}, generator {})",
+        name,
+        m.bits(),
+        abbreviated_hex(m),
+        abbreviated_hex(g)
+    )
+}
+
 impl Display for RsaGroup {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        f.debug_struct("RsaGroup")
-            .field("g", &format_args!("{}", &self.g))
-            .field("m", &format_args!("{}", &self.m))
-            .finish()
+        fmt_rsa_group(f, "RsaGroup", &self.g, &self.m)
     }
 }
 
@@ -62,6 +115,7 @@ impl SemiGroup for RsaGroup {
     type Elem = BigUint;
 
     fn op(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        count_mult_mod();
         a * b % &self.m
     }
 
@@ -74,13 +128,127 @@ impl SemiGroup for RsaGroup {
     }
 
     fn power(&self, b: &Self::Elem, e: &BigUint) -> Self::Elem {
+        count_modpow();
         b.modpow(e, &self.m)
     }
+
+    fn inverse(&self, a: &BigUint) -> Option<BigUint> {
+        inverse_mod(a, &self.m)
+    }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+/// An invalid generator for an RSA group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GroupError {
+    /// The generator is not in `(1, m)`.
+    GeneratorOutOfRange,
+    /// The generator shares a factor with the modulus, so it is not a unit.
+    GeneratorNotCoprime,
+    /// The modulus is even, so it is not a product of two large primes.
+    EvenModulus,
+    /// The modulus is prime, so the order of the group is known to anyone.
+    PrimeModulus,
+}
+
+impl Display for GroupError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            GroupError::GeneratorOutOfRange => write!(f, "the generator is not in (1, m)"),
+            GroupError::GeneratorNotCoprime => {
+                write!(f, "the generator shares a factor with the modulus")
+            }
+            GroupError::EvenModulus => write!(f, "the modulus is even"),
+            GroupError::PrimeModulus => {
+                write!(f, "the modulus is prime, so the group order is known")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GroupError {}
+
+/// Checks that `g` is a unit in `(1, m)`.
+fn check_generator(g: &BigUint, m: &BigUint) -> Result<(), GroupError> {
+    if g <= &BigUint::one() || g >= m {
+        return Err(GroupError::GeneratorOutOfRange);
+    }
+    if !g.gcd(m).is_one() {
+        return Err(GroupError::GeneratorNotCoprime);
+    }
+    Ok(())
+}
+
+/// Cheap sanity checks that `m` could be an RSA modulus, and that `g` is a unit in `(1, m)`.
+/// They cannot show that the factorization of `m` is unknown: only that it is not trivially
+/// known.
+fn validate_modulus(g: &BigUint, m: &BigUint) -> Result<(), GroupError> {
+    if m.is_even() {
+        return Err(GroupError::EvenModulus);
+    }
+    check_generator(g, m)?;
+    if miller_rabin(m, 20) {
+        return Err(GroupError::PrimeModulus);
+    }
+    Ok(())
+}
+
+/// Parses a natural number written in decimal, or in hex with a `0x` prefix.
+pub fn parse_nat(s: &str) -> Option<BigUint> {
+    let s = s.trim();
+    if s.starts_with("0x") || s.starts_with("0X") {
+        BigUint::parse_bytes(s[2..].as_bytes(), 16)
+    } else {
+        BigUint::parse_bytes(s.as_bytes(), 10)
+    }
+}
+
+impl RsaGroup {
+    /// The group of units mod `m`, generated by `g`, which must be a unit in `(1, m)`.
+    pub fn new(g: BigUint, m: BigUint) -> Result<Self, GroupError> {
+        check_generator(&g, &m)?;
+        Ok(RsaGroup { g, m })
+    }
+
+    /// Checks that the modulus is odd and not prime, and that the generator is a unit in
+    /// `(1, m)`. Do this before generating parameters for a modulus from elsewhere: a modulus
+    /// with a known factorization makes the accumulator unsound.
+    pub fn validate(&self) -> Result<(), GroupError> {
+        validate_modulus(&self.g, &self.m)
+    }
+
+    /// The modulus. The generator is `SemiGroup::generator`.
+    pub fn modulus(&self) -> &BigUint {
+        &self.m
+    }
+
+    /// Reads a group from a file whose first non-empty line is the modulus and whose optional
+    /// second non-empty line is the generator (which defaults to 2). Each number may be decimal,
+    /// or hex with a `0x` prefix. The group must pass `validate`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines().map(str::trim).filter(|l| !l.is_empty());
+        let m_str = lines
+            .next()
+            .ok_or_else(|| invalid("missing modulus".to_owned()))?;
+        let m = parse_nat(m_str).ok_or_else(|| invalid(format!("bad modulus: {}", m_str)))?;
+        let g = match lines.next() {
+            Some(g_str) => {
+                parse_nat(g_str).ok_or_else(|| invalid(format!("bad generator: {}", g_str)))?
+            }
+            None => BigUint::from(2usize),
+        };
+        let group = RsaGroup { g, m };
+        group.validate().map_err(|e| invalid(format!("{}", e)))?;
+        Ok(group)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RsaQuotientGroup {
+    #[serde(with = "nat_decimal")]
     pub g: BigUint,
+    #[serde(with = "nat_decimal")]
     pub m: BigUint,
 }
 
@@ -95,10 +263,27 @@ impl Debug for RsaQuotientGroup {
 
 impl Display for RsaQuotientGroup {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        f.debug_struct("RsaQuotientGroup")
-            .field("g", &format_args!("{}", &self.g))
-            .field("m", &format_args!("{}", &self.m))
-            .finish()
+        fmt_rsa_group(f, "RsaQuotientGroup", &self.g, &self.m)
+    }
+}
+
+impl RsaQuotientGroup {
+    /// The units mod `m`, modulo `{1, -1}`, generated by `g`, which must be a unit in `(1, m)`.
+    pub fn new(g: BigUint, m: BigUint) -> Result<Self, GroupError> {
+        check_generator(&g, &m)?;
+        Ok(RsaQuotientGroup { g, m })
+    }
+
+    /// Checks that the modulus is odd and not prime, and that the generator is a unit in
+    /// `(1, m)`. Do this before generating parameters for a modulus from elsewhere: a modulus
+    /// with a known factorization makes the accumulator unsound.
+    pub fn validate(&self) -> Result<(), GroupError> {
+        validate_modulus(&self.g, &self.m)
+    }
+
+    /// The modulus. The generator is `SemiGroup::generator`.
+    pub fn modulus(&self) -> &BigUint {
+        &self.m
     }
 }
 
@@ -106,6 +291,7 @@ impl SemiGroup for RsaQuotientGroup {
     type Elem = BigUint;
 
     fn op(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        count_mult_mod();
         let x = a * b % &self.m;
         let y = &self.m - &x;
         min(x, y)
@@ -120,10 +306,17 @@ impl SemiGroup for RsaQuotientGroup {
     }
 
     fn power(&self, b: &Self::Elem, e: &BigUint) -> Self::Elem {
+        count_modpow();
         let x = b.modpow(e, &self.m);
         let y = &self.m - &x;
         min(x, y)
     }
+
+    fn inverse(&self, a: &BigUint) -> Option<BigUint> {
+        let x = inverse_mod(a, &self.m)?;
+        let y = &self.m - &x;
+        Some(min(x, y))
+    }
 }
 
 pub trait CircuitSemiGroup: Gadget<Access = ()> + Eq {
@@ -145,6 +338,34 @@ pub trait CircuitSemiGroup: Gadget<Access = ()> + Eq {
     fn identity(&self) -> Self::Elem;
     fn group(&self) -> Option<&Self::Group>;
     fn elem_params(p: &<Self as Gadget>::Params) -> <Self::Elem as Gadget>::Params;
+    /// Constrains `elem` to be a canonical representation of a group element.
+    fn enforce_canonical<CS: ConstraintSystem<Self::E>>(
+        &self,
+        cs: CS,
+        elem: &Self::Elem,
+    ) -> Result<(), SynthesisError>;
+    /// Allocates a group element, constraining it to be canonical.
+    fn alloc_elem<CS: ConstraintSystem<Self::E>>(
+        &self,
+        mut cs: CS,
+        value: Option<&<Self::Group as SemiGroup>::Elem>,
+    ) -> Result<Self::Elem, SynthesisError>
+    where
+        Self::Elem: Gadget<Value = <Self::Group as SemiGroup>::Elem, Access = ()>,
+    {
+        let elem = <Self::Elem as Gadget>::alloc(
+            cs.namespace(|| "alloc"),
+            value,
+            (),
+            &Self::elem_params(self.params()),
+        )?;
+        self.enforce_canonical(cs.namespace(|| "canonical"), &elem)?;
+        Ok(elem)
+    }
+    /// The window size, in bits, for exponentiation by an `n_exp_bits`-bit exponent.
+    fn window_bits(&self, n_exp_bits: usize) -> usize {
+        optimal_k(n_exp_bits)
+    }
     fn bauer_power_bin_rev_helper<'a, CS: ConstraintSystem<Self::E>>(
         &self,
         mut cs: CS,
@@ -191,7 +412,11 @@ pub trait CircuitSemiGroup: Gadget<Access = ()> + Eq {
         exp: Bitvector<Self::E>,
     ) -> Result<Self::Elem, SynthesisError> {
         // https://en.wikipedia.org/wiki/Exponentiation_by_squaring#2k-ary_method
-        let k = optimal_k(exp.bits.len());
+        let k = self.window_bits(exp.bits.len());
+        if k == 0 {
+            eprintln!("The exponentiation window must be at least one bit");
+            return Err(SynthesisError::Unsatisfiable);
+        }
         let base_powers = {
             let mut base_powers = vec![self.identity(), base.clone()];
             for i in 2..(1 << k) {
@@ -225,6 +450,63 @@ pub trait CircuitSemiGroup: Gadget<Access = ()> + Eq {
 pub struct CircuitRsaGroupParams {
     pub limb_width: usize,
     pub n_limbs: usize,
+    /// The window size for exponentiation. If `None`, the size which minimizes the number of
+    /// group operations is used.
+    pub window_bits: Option<usize>,
+}
+
+/// An error choosing the limbs for an RSA group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RsaGroupParamsError {
+    ZeroLimbWidth,
+    /// The modulus width is not a multiple of the limb width, so the limbs would not hold it.
+    PartialLimb { n_bits_base: usize, limb_width: usize },
+}
+
+impl Display for RsaGroupParamsError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            RsaGroupParamsError::ZeroLimbWidth => write!(f, "limbs must be at least one bit wide"),
+            RsaGroupParamsError::PartialLimb {
+                n_bits_base,
+                limb_width,
+            } => write!(
+                f,
+                "a {}-bit modulus does not fill a whole number of {}-bit limbs",
+                n_bits_base, limb_width
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RsaGroupParamsError {}
+
+impl From<RsaGroupParamsError> for SynthesisError {
+    fn from(e: RsaGroupParamsError) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
+impl CircuitRsaGroupParams {
+    /// Parameters for an `n_bits_base`-bit modulus in `limb_width`-bit limbs, with the default
+    /// window size. `n_bits_base` must be a multiple of `limb_width`.
+    pub fn new(limb_width: usize, n_bits_base: usize) -> Result<Self, RsaGroupParamsError> {
+        if limb_width == 0 {
+            return Err(RsaGroupParamsError::ZeroLimbWidth);
+        }
+        if n_bits_base % limb_width != 0 {
+            return Err(RsaGroupParamsError::PartialLimb {
+                n_bits_base,
+                limb_width,
+            });
+        }
+        Ok(Self {
+            limb_width,
+            n_limbs: n_bits_base / limb_width,
+            window_bits: None,
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -236,6 +518,27 @@ pub struct CircuitRsaGroup<E: Engine> {
     pub params: CircuitRsaGroupParams,
 }
 
+/// Constrains `g` to be a unit in `(1, m)`.
+fn enforce_valid_generator<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    g: &BigNat<E>,
+    m: &BigNat<E>,
+) -> Result<(), SynthesisError> {
+    BigNat::identity::<CS>(g.params.limb_width).enforce_lt(cs.namespace(|| "1 < g"), g)?;
+    g.enforce_lt(cs.namespace(|| "g < m"), m)?;
+    g.enforce_coprime(cs.namespace(|| "gcd(g, m) = 1"), m)
+}
+
+impl<E: Engine> CircuitRsaGroup<E> {
+    /// Constrains the generator to be a unit in `(1, m)`, as `RsaGroup::new` checks.
+    pub fn enforce_valid_generator<CS: ConstraintSystem<E>>(
+        &self,
+        cs: CS,
+    ) -> Result<(), SynthesisError> {
+        enforce_valid_generator(cs, &self.g, &self.m)
+    }
+}
+
 impl<E: Engine> PartialEq for CircuitRsaGroup<E> {
     fn eq(&self, other: &Self) -> bool {
         self.g == other.g
@@ -323,9 +626,26 @@ impl<E: Engine> CircuitSemiGroup for CircuitRsaGroup<E> {
     ) -> Result<BigNat<E>, SynthesisError> {
         a.mult_mod(cs, b, &self.m).map(|(_, r)| r)
     }
+    fn power<CS: ConstraintSystem<Self::E>>(
+        &self,
+        cs: CS,
+        b: &Self::Elem,
+        e: &BigNat<Self::E>,
+    ) -> Result<Self::Elem, SynthesisError> {
+        let window_bits = self.window_bits(e.limbs.len() * e.params.limb_width);
+        b.pow_mod_windowed(cs, e, &self.m, window_bits)
+    }
     fn elem_params(p: &<Self as Gadget>::Params) -> <Self::Elem as Gadget>::Params {
         BigNatParams::new(p.limb_width, p.n_limbs)
     }
+    fn enforce_canonical<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        elem: &BigNat<E>,
+    ) -> Result<(), SynthesisError> {
+        elem.decompose(cs.namespace(|| "decomp"))?;
+        elem.enforce_lt(cs.namespace(|| "< m"), &self.m)
+    }
     fn group(&self) -> Option<&Self::Group> {
         self.value.as_ref()
     }
@@ -335,6 +655,11 @@ impl<E: Engine> CircuitSemiGroup for CircuitRsaGroup<E> {
     fn identity(&self) -> Self::Elem {
         self.id.clone()
     }
+    fn window_bits(&self, n_exp_bits: usize) -> usize {
+        self.params
+            .window_bits
+            .unwrap_or_else(|| optimal_k(n_exp_bits))
+    }
 }
 
 #[derive(Clone)]
@@ -356,7 +681,26 @@ impl<E: Engine> PartialEq for CircuitRsaQuotientGroup<E> {
     }
 }
 
-impl<E: Engine> CircuitRsaQuotientGroup<E> {}
+impl<E: Engine> CircuitRsaQuotientGroup<E> {
+    /// The canonical representative of the class `{x, m - x}`: the lesser of the two.
+    /// `x` must be at most `m`.
+    pub fn canonicalize<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        x: &BigNat<E>,
+    ) -> Result<BigNat<E>, SynthesisError> {
+        let y = self.m.sub(cs.namespace(|| "sub"), x)?;
+        x.min(cs.namespace(|| "min"), &y)
+    }
+
+    /// Constrains the generator to be a unit in `(1, m)`, as `RsaQuotientGroup::new` checks.
+    pub fn enforce_valid_generator<CS: ConstraintSystem<E>>(
+        &self,
+        cs: CS,
+    ) -> Result<(), SynthesisError> {
+        enforce_valid_generator(cs, &self.g, &self.m)
+    }
+}
 
 impl<E: Engine> Eq for CircuitRsaQuotientGroup<E> {}
 
@@ -427,9 +771,7 @@ impl<E: Engine> CircuitSemiGroup for CircuitRsaQuotientGroup<E> {
         b: &BigNat<E>,
     ) -> Result<Self::Elem, SynthesisError> {
         let x = self.partial_op(cs.namespace(|| "mult"), a, b)?;
-        let y = self.m.sub(cs.namespace(|| "sub"), &x)?;
-        y.decompose(cs.namespace(|| "y decomp check"))?;
-        x.min(cs.namespace(|| "min"), &y)
+        self.canonicalize(&mut cs, &x)
     }
     fn partial_op<CS: ConstraintSystem<E>>(
         &self,
@@ -445,15 +787,21 @@ impl<E: Engine> CircuitSemiGroup for CircuitRsaQuotientGroup<E> {
         b: &Self::Elem,
         e: &BigNat<Self::E>,
     ) -> Result<Self::Elem, SynthesisError> {
-        let exp_bin_rev = e.decompose(cs.namespace(|| "exp decomp"))?.reversed();
-        let x = self.bauer_power_bin_rev(cs.namespace(|| "binary exp"), &b, exp_bin_rev)?;
-        let y = self.m.sub(cs.namespace(|| "sub"), &x)?;
-        y.decompose(cs.namespace(|| "y decomp check"))?;
-        x.min(cs.namespace(|| "min"), &y)
+        let window_bits = self.window_bits(e.limbs.len() * e.params.limb_width);
+        let x = b.pow_mod_windowed(cs.namespace(|| "pow"), e, &self.m, window_bits)?;
+        self.canonicalize(&mut cs, &x)
     }
     fn elem_params(p: &<Self as Gadget>::Params) -> <Self::Elem as Gadget>::Params {
         BigNatParams::new(p.limb_width, p.n_limbs)
     }
+    fn enforce_canonical<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        elem: &BigNat<E>,
+    ) -> Result<(), SynthesisError> {
+        elem.decompose(cs.namespace(|| "decomp"))?;
+        elem.enforce_lt(cs.namespace(|| "< m"), &self.m)
+    }
     fn group(&self) -> Option<&Self::Group> {
         self.value.as_ref()
     }
@@ -463,6 +811,11 @@ impl<E: Engine> CircuitSemiGroup for CircuitRsaQuotientGroup<E> {
     fn identity(&self) -> Self::Elem {
         self.id.clone()
     }
+    fn window_bits(&self, n_exp_bits: usize) -> usize {
+        self.params
+            .window_bits
+            .unwrap_or_else(|| optimal_k(n_exp_bits))
+    }
 }
 
 #[cfg(test)]
@@ -505,6 +858,7 @@ mod test {
                 &CircuitRsaGroupParams {
                     limb_width: self.params.limb_width,
                     n_limbs: self.params.n_limbs_b,
+                    window_bits: None,
                 },
             )?;
             let b = BigNat::alloc_from_nat(
@@ -633,6 +987,7 @@ mod test {
                 &CircuitRsaGroupParams {
                     limb_width: self.params.limb_width,
                     n_limbs: self.params.n_limbs,
+                    window_bits: None,
                 },
             )?;
             let b = BigNat::alloc_from_nat(
@@ -717,6 +1072,7 @@ mod test {
         pub limb_width: usize,
         pub n_limbs_b: usize,
         pub n_limbs_e: usize,
+        pub window_bits: Option<usize>,
     }
 
     pub struct QuotientPower<'a> {
@@ -737,6 +1093,7 @@ mod test {
                 &CircuitRsaGroupParams {
                     limb_width: self.params.limb_width,
                     n_limbs: self.params.n_limbs_b,
+                    window_bits: self.params.window_bits,
                 },
             )?;
             let b = BigNat::alloc_from_nat(
@@ -777,6 +1134,7 @@ mod test {
                     limb_width: 4,
                     n_limbs_b: 2,
                     n_limbs_e: 12,
+                    window_bits: None,
                 }
             },
             true,
@@ -794,6 +1152,7 @@ mod test {
                     limb_width: 4,
                     n_limbs_b: 2,
                     n_limbs_e: 12,
+                    window_bits: None,
                 }
             },
             true,
@@ -812,6 +1171,7 @@ mod test {
                     limb_width: 4,
                     n_limbs_b: 2,
                     n_limbs_e: 12,
+                    window_bits: None,
                 }
             },
             true,
@@ -829,9 +1189,321 @@ mod test {
                     limb_width: 32,
                     n_limbs_b: 16,
                     n_limbs_e: 4,
+                    window_bits: None,
                 }
             },
             true,
         ),
     }
+
+    #[test]
+    fn digest_and_negation_canonicalize_alike() {
+        let m = BigUint::from_str(RSA_512).unwrap();
+        let x = BigUint::from_str("1234567891011121314151617181920").unwrap();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let group = CircuitRsaQuotientGroup::alloc(
+            cs.namespace(|| "group"),
+            Some(&RsaQuotientGroup {
+                g: BigUint::from(2usize),
+                m: m.clone(),
+            }),
+            (),
+            &CircuitRsaGroupParams::new(32, 512).unwrap(),
+        )
+        .unwrap();
+        let mut canonical = |name: &str, n: BigUint| {
+            let mut cs = cs.namespace(|| name);
+            let nat = BigNat::alloc_from_nat(cs.namespace(|| "n"), || Ok(n), 32, 16).unwrap();
+            group.canonicalize(cs.namespace(|| "canonical"), &nat).unwrap()
+        };
+        let from_x = canonical("x", x.clone());
+        let from_neg_x = canonical("-x", &m - &x);
+        assert_eq!(from_x.value, Some(x));
+        assert_eq!(from_neg_x.value, from_x.value);
+        from_x.equal(cs.namespace(|| "equal"), &from_neg_x).unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn rsa_group_generators() {
+        let m = BigUint::from(143usize);
+        let group = |g: usize| RsaGroup::new(BigUint::from(g), m.clone());
+        assert!(group(2).is_ok());
+        assert!(group(142).is_ok());
+        assert_eq!(group(1), Err(GroupError::GeneratorOutOfRange));
+        assert_eq!(group(143), Err(GroupError::GeneratorOutOfRange));
+        assert_eq!(group(11), Err(GroupError::GeneratorNotCoprime));
+        assert_eq!(
+            RsaQuotientGroup::new(BigUint::from(26usize), m.clone()),
+            Err(GroupError::GeneratorNotCoprime)
+        );
+    }
+
+    /// Whether the constraints of `enforce_valid_generator` hold for `g` mod 143.
+    fn generator_is_valid_in_circuit(g: usize) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let group = CircuitRsaQuotientGroup::alloc(
+            cs.namespace(|| "group"),
+            Some(&RsaQuotientGroup {
+                g: BigUint::from(g),
+                m: BigUint::from(143usize),
+            }),
+            (),
+            &CircuitRsaGroupParams {
+                limb_width: 4,
+                n_limbs: 2,
+                window_bits: None,
+            },
+        )
+        .unwrap();
+        group
+            .enforce_valid_generator(cs.namespace(|| "check"))
+            .is_ok()
+            && cs.is_satisfied()
+    }
+
+    #[test]
+    fn generator_checks_in_circuit() {
+        assert!(generator_is_valid_in_circuit(2));
+        assert!(generator_is_valid_in_circuit(3));
+        assert!(generator_is_valid_in_circuit(142));
+        assert!(!generator_is_valid_in_circuit(1));
+        assert!(!generator_is_valid_in_circuit(13));
+    }
+
+    #[test]
+    fn rsa_group_params_fill_whole_limbs() {
+        let params = CircuitRsaGroupParams::new(32, 512).unwrap();
+        assert_eq!(params.n_limbs, 16);
+        assert_eq!(params.window_bits, None);
+        assert_eq!(
+            CircuitRsaGroupParams::new(32, 500),
+            Err(RsaGroupParamsError::PartialLimb {
+                n_bits_base: 500,
+                limb_width: 32,
+            })
+        );
+        assert_eq!(
+            CircuitRsaGroupParams::new(0, 512),
+            Err(RsaGroupParamsError::ZeroLimbWidth)
+        );
+    }
+
+    #[test]
+    fn quotient_power_512b_128b_window_sizes() {
+        let group = RsaQuotientGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let b = BigUint::from_str("12345678901234567890").unwrap();
+        let e = (BigUint::one() << 127) + 12345usize;
+        let res = group.power(&b, &e);
+        let (m, b, e, res) = (group.m.to_string(), b.to_string(), e.to_string(), res.to_string());
+        for window_bits in 1..=5 {
+            let circuit = QuotientPower {
+                inputs: Some(QuotientPowerInputs {
+                    g: "2",
+                    m: &m,
+                    b: &b,
+                    e: &e,
+                    res: &res,
+                }),
+                params: QuotientPowerParams {
+                    limb_width: 32,
+                    n_limbs_b: 16,
+                    n_limbs_e: 4,
+                    window_bits: Some(window_bits),
+                },
+            };
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            circuit.synthesize(&mut cs).expect("synthesis failed");
+            println!(
+                "Constraints with a {}-bit window: {}",
+                window_bits,
+                cs.num_constraints()
+            );
+            assert!(cs.is_satisfied());
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct AllocElemInputs<'a> {
+        pub m: &'a str,
+        pub elem: &'a str,
+    }
+
+    pub struct AllocElem<'a> {
+        inputs: Option<AllocElemInputs<'a>>,
+    }
+
+    impl<'a, E: Engine> Circuit<E> for AllocElem<'a> {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let group = self.inputs.as_ref().map(|is| RsaGroup {
+                g: BigUint::from(2usize),
+                m: BigUint::from_str(is.m).unwrap(),
+            });
+            let elem = self
+                .inputs
+                .as_ref()
+                .map(|is| BigUint::from_str(is.elem).unwrap());
+            let g = CircuitRsaGroup::alloc(
+                cs.namespace(|| "g"),
+                group.as_ref(),
+                (),
+                &CircuitRsaGroupParams {
+                    limb_width: 4,
+                    n_limbs: 2,
+                    window_bits: None,
+                },
+            )?;
+            g.alloc_elem(cs.namespace(|| "elem"), elem.as_ref())?;
+            Ok(())
+        }
+    }
+
+    circuit_tests! {
+        alloc_elem_below_m: (
+            AllocElem {
+                inputs: Some(AllocElemInputs {
+                    m: "143",
+                    elem: "142",
+                }),
+            },
+            true,
+        ),
+        alloc_elem_zero: (
+            AllocElem {
+                inputs: Some(AllocElemInputs {
+                    m: "143",
+                    elem: "0",
+                }),
+            },
+            true,
+        ),
+        alloc_elem_equal_m: (
+            AllocElem {
+                inputs: Some(AllocElemInputs {
+                    m: "143",
+                    elem: "143",
+                }),
+            },
+            false,
+        ),
+        alloc_elem_above_m: (
+            AllocElem {
+                inputs: Some(AllocElemInputs {
+                    m: "143",
+                    elem: "200",
+                }),
+            },
+            false,
+        ),
+    }
+
+    const RSA_512: &str = "11834783464130424096695514462778870280264989938857328737807205623069291535525952722847913694296392927890261736769191982212777933726583565708193466779811767";
+
+    fn write_temp(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn display_is_abbreviated() {
+        let m = BigUint::from_str(RSA_512).unwrap();
+        let group = RsaGroup::new(BigUint::from(2usize), m.clone()).unwrap();
+        assert_eq!(group.modulus(), &m);
+        assert_eq!(group.generator(), BigUint::from(2usize));
+        let hex = m.to_str_radix(16);
+        assert_eq!(
+            format!("{}", group),
+            format!(
+                "RsaGroup(512-bit modulus 0x{}..{}, generator 0x2)",
+                &hex[..8],
+                &hex[hex.len() - 8..]
+            )
+        );
+        let quotient = RsaQuotientGroup::new(BigUint::from(3usize), m).unwrap();
+        assert!(format!("{}", quotient).starts_with("RsaQuotientGroup(512-bit modulus 0x"));
+        assert!(format!("{:?}", quotient).contains(RSA_512));
+    }
+
+    #[test]
+    fn validation_rejects_weak_moduli() {
+        let two = BigUint::from(2usize);
+        let rsa_512 = BigUint::from_str(RSA_512).unwrap();
+        assert_eq!(RsaGroup::new(two.clone(), rsa_512.clone()).unwrap().validate(), Ok(()));
+        let quotient = RsaQuotientGroup::new(two.clone(), rsa_512.clone()).unwrap();
+        assert_eq!(quotient.validate(), Ok(()));
+
+        // 2^127 - 1
+        let prime = (BigUint::one() << 127) - 1usize;
+        let group = RsaGroup::new(two.clone(), prime).unwrap();
+        assert_eq!(group.validate(), Err(GroupError::PrimeModulus));
+        let group = RsaGroup {
+            g: BigUint::from(3usize),
+            m: &rsa_512 + 1usize,
+        };
+        assert_eq!(group.validate(), Err(GroupError::EvenModulus));
+        let group = RsaQuotientGroup {
+            g: &rsa_512 + 2usize,
+            m: rsa_512,
+        };
+        assert_eq!(group.validate(), Err(GroupError::GeneratorOutOfRange));
+    }
+
+    #[test]
+    fn modulus_file_decimal() {
+        let path = write_temp("modulus_dec", &format!("{}\n", RSA_512));
+        let group = RsaGroup::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(group.m, BigUint::from_str(RSA_512).unwrap());
+        assert_eq!(group.g, BigUint::from(2usize));
+    }
+
+    #[test]
+    fn modulus_file_hex_with_generator() {
+        let m = BigUint::from_str(RSA_512).unwrap();
+        let path = write_temp("modulus_hex", &format!("0x{}\n0x3\n", m.to_str_radix(16)));
+        let group = RsaGroup::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(group.m, m);
+        assert_eq!(group.g, BigUint::from(3usize));
+    }
+
+    #[test]
+    fn rsa_power_agrees_with_op() {
+        let m = BigUint::from_str(RSA_512).unwrap();
+        let rsa = RsaGroup {
+            g: BigUint::from(2usize),
+            m: m.clone(),
+        };
+        let quotient = RsaQuotientGroup {
+            g: BigUint::from(2usize),
+            m,
+        };
+        let b = BigUint::from(12345usize);
+        let mut rsa_acc = rsa.identity();
+        let mut quotient_acc = quotient.identity();
+        for e in 0..40usize {
+            assert_eq!(rsa.power(&b, &BigUint::from(e)), rsa_acc);
+            assert_eq!(quotient.power(&b, &BigUint::from(e)), quotient_acc);
+            rsa_acc = rsa.op(&rsa_acc, &b);
+            quotient_acc = quotient.op(&quotient_acc, &b);
+        }
+    }
+
+    #[test]
+    fn modulus_file_invalid() {
+        for (name, contents) in &[
+            ("modulus_bad", "143\n143\n"),
+            ("modulus_even", "144\n"),
+            ("modulus_prime", "251\n"),
+        ] {
+            let path = write_temp(name, contents);
+            let res = RsaGroup::from_file(&path);
+            fs::remove_file(&path).unwrap();
+            assert!(res.is_err(), "{} was accepted", name);
+        }
+    }
 }
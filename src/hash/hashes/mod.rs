@@ -14,6 +14,7 @@ use sapling_crypto::poseidon::{
 use std::default::Default;
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use super::circuit::CircuitHasher;
 use super::Hasher;
@@ -29,24 +30,61 @@ pub struct Poseidon<E>
 where
     E: PoseidonEngine<SBox = QuinticSBox<E>>,
 {
-    pub params: Rc<E::Params>,
+    pub params: Arc<E::Params>,
 }
 
 impl<E: PoseidonEngine<SBox = QuinticSBox<E>>> Poseidon<E> {
-    fn from_params(p: E::Params) -> Self {
-        Self { params: Rc::new(p) }
+    /// A Poseidon hasher with the given parameters, for engines without a `Default` hasher.
+    pub fn from_params(p: E::Params) -> Self {
+        Self { params: Arc::new(p) }
+    }
+
+    /// Hashes each of `inputs`, returning one hash per item, each equal to the `allocate_hash` of
+    /// that item.
+    ///
+    /// The round constants and MDS matrix enter the constraints as coefficients, not variables, so
+    /// the sponges share nothing but `params`, and this costs as many constraints as hashing the
+    /// items one by one.
+    pub fn hash_many<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        inputs: &[Vec<AllocatedNum<E>>],
+    ) -> CResult<Vec<AllocatedNum<E>>> {
+        use sapling_crypto::circuit::poseidon_hash::poseidon_hash;
+        assert_eq!(self.params.output_len(), 1);
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let cs = cs.namespace(|| format!("item {}", i));
+                Ok(poseidon_hash::<E, _>(cs, item, &self.params)?.pop().unwrap())
+            })
+            .collect()
     }
 }
 
+lazy_static! {
+    // Deriving the round constants and MDS matrix is slow, so the default parameters are computed
+    // once per process and shared by every default hasher, on every thread.
+    static ref BN256_POSEIDON_PARAMS: Arc<Bn256PoseidonParams> =
+        Arc::new(Bn256PoseidonParams::new::<Keccak256Hasher>());
+    static ref BLS12_POSEIDON_PARAMS: Arc<Bls12PoseidonParams> =
+        Arc::new(Bls12PoseidonParams::new::<Keccak256Hasher>());
+}
+
 impl Default for Poseidon<Bn256> {
     fn default() -> Self {
-        Poseidon::from_params(Bn256PoseidonParams::new::<Keccak256Hasher>())
+        Self {
+            params: Arc::clone(&BN256_POSEIDON_PARAMS),
+        }
     }
 }
 
 impl Default for Poseidon<Bls12> {
     fn default() -> Self {
-        Poseidon::from_params(Bls12PoseidonParams::new::<Keccak256Hasher>())
+        Self {
+            params: Arc::clone(&BLS12_POSEIDON_PARAMS),
+        }
     }
 }
 
@@ -99,7 +137,8 @@ impl<E: JubjubEngine> Hasher for Pedersen<E> {
     }
 }
 impl<E: JubjubEngine>  Pedersen<E> {
-    fn from_params(p: E::Params) -> Self {
+    /// A Pedersen hasher with the given parameters, for engines without a `Default` hasher.
+    pub fn from_params(p: E::Params) -> Self {
         Self {
             params: Rc::new(p),
         }
@@ -273,3 +312,68 @@ where
         sha::circuit::sha256(cs, inputs)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use util::convert::usize_to_f;
+    use util::test_helpers::*;
+
+    #[test]
+    fn default_poseidon_params_are_shared() {
+        let a = Poseidon::<Bn256>::default();
+        let b = std::thread::spawn(Poseidon::<Bn256>::default).join().unwrap();
+        assert!(Arc::ptr_eq(&a.params, &b.params));
+        let c = Poseidon::<Bls12>::default();
+        let d = std::thread::spawn(Poseidon::<Bls12>::default).join().unwrap();
+        assert!(Arc::ptr_eq(&c.params, &d.params));
+        let e = Poseidon::<Bn256>::from_params(Bn256PoseidonParams::new::<Keccak256Hasher>());
+        assert!(!Arc::ptr_eq(&a.params, &e.params));
+    }
+
+    #[test]
+    fn set_hashers_can_be_shared_across_threads() {
+        fn shareable<T: Send + Sync>(_: &T) {}
+        shareable(&Poseidon::<Bn256>::default());
+        shareable(&Poseidon::<Bls12>::default());
+        shareable(&Mimc::<Bn256>::default());
+        shareable(&Sha256::<Bn256>::default());
+    }
+
+    #[test]
+    fn batched_poseidon_matches_individual_hashes() {
+        let hasher = Poseidon::<Bn256>::default();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let items = (0..5)
+            .map(|i| {
+                (0..5)
+                    .map(|j| {
+                        AllocatedNum::alloc(cs.namespace(|| format!("input {} {}", i, j)), || {
+                            Ok(usize_to_f(5 * i + j))
+                        })
+                    })
+                    .collect::<CResult<Vec<_>>>()
+            })
+            .collect::<CResult<Vec<_>>>()
+            .unwrap();
+
+        let before = cs.num_constraints();
+        let batched = hasher.hash_many(cs.namespace(|| "batched"), &items).unwrap();
+        let batched_cost = cs.num_constraints() - before;
+        let mut individual_cost = 0;
+        for (i, (item, hash)) in items.iter().zip(&batched).enumerate() {
+            let before = cs.num_constraints();
+            let individual = hasher
+                .allocate_hash(cs.namespace(|| format!("individual {}", i)), item)
+                .unwrap();
+            individual_cost += cs.num_constraints() - before;
+            assert_eq!(hash.get_value(), individual.get_value());
+            let values: Vec<_> = item.iter().map(|n| n.get_value().unwrap()).collect();
+            assert_eq!(hash.get_value(), Some(hasher.hash(&values)));
+        }
+        assert_eq!(batched.len(), 5);
+        assert_eq!(batched_cost, individual_cost);
+        assert!(cs.is_satisfied());
+    }
+}
@@ -1,21 +1,47 @@
+use num_bigint::BigUint;
+use sapling_crypto::bellman::pairing::ff::PrimeField;
 use sapling_crypto::bellman::pairing::Engine;
-use sapling_crypto::bellman::ConstraintSystem;
-use sapling_crypto::circuit::num::AllocatedNum;
+use sha2::{Digest, Sha256};
 
-use util::bench::WitnessTimer;
+use util::convert::{f_to_nat, nat_to_f};
 
-/// Use the sha256 hash algorithm to digest these items
+/// Use the sha256 hash algorithm to digest these items.
+///
+/// This mirrors `circuit::sha256` bit-for-bit: each input contributes its `NUM_BITS` bits,
+/// least-significant first, the bit string is zero-padded to a whole number of bytes and hashed,
+/// and the first `CAPACITY` bits of the digest are read back, least-significant first.
 pub fn sha256<E: Engine>(inputs: &[E::Fr]) -> E::Fr {
-    let mut cs = WitnessTimer::new();
-    let nums: Vec<AllocatedNum<E>> = inputs
-        .into_iter()
-        .enumerate()
-        .map(|(i, input)| {
-            AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*input)).unwrap()
+    let num_bits = <E::Fr as PrimeField>::NUM_BITS as usize;
+    let mut bits: Vec<bool> = Vec::new();
+    for input in inputs {
+        let le = f_to_nat(input).to_bytes_le();
+        bits.extend(
+            (0..num_bits).map(|i| le.get(i / 8).map_or(false, |b| (b >> (i % 8)) & 1 == 1)),
+        );
+    }
+    // The circuit reads the bits of each byte most-significant first.
+    let bytes: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| {
+            byte.iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, b)| acc | ((*b as u8) << (7 - i)))
         })
         .collect();
-    let output = circuit::sha256(cs.namespace(|| "sha"), &nums).unwrap();
-    output.get_value().unwrap()
+    let mut hasher = Sha256::new();
+    hasher.input(&bytes);
+    let digest = hasher.result();
+    let digest_bits = digest
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> (7 - i)) & 1 == 1))
+        .take(<E::Fr as PrimeField>::CAPACITY as usize);
+    let mut n = BigUint::from(0usize);
+    for (i, bit) in digest_bits.enumerate() {
+        if bit {
+            n |= BigUint::from(1usize) << i;
+        }
+    }
+    nat_to_f(&n).expect("the digest is truncated to fit in the field")
 }
 
 pub mod circuit {
@@ -90,3 +116,47 @@ pub mod circuit {
         bools_to_num(cs.namespace(|| "to num"), &digest)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use sapling_crypto::bellman::pairing::ff::{Field, ScalarEngine};
+    use sapling_crypto::bellman::ConstraintSystem;
+    use sapling_crypto::circuit::num::AllocatedNum;
+
+    use util::test_helpers::*;
+
+    type Fr = <Bn256 as ScalarEngine>::Fr;
+
+    fn circuit_sha256(inputs: &[Fr]) -> Fr {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let nums: Vec<AllocatedNum<Bn256>> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*input)).unwrap()
+            })
+            .collect();
+        let output = circuit::sha256(cs.namespace(|| "sha"), &nums).unwrap();
+        assert!(cs.is_satisfied());
+        output.get_value().unwrap()
+    }
+
+    #[test]
+    fn helper_matches_circuit() {
+        let inputs: Vec<Fr> = ["0", "1", "2", "1782374"]
+            .iter()
+            .map(|s| Fr::from_str(s).unwrap())
+            .collect();
+        for n in 1..=inputs.len() {
+            assert_eq!(sha256::<Bn256>(&inputs[..n]), circuit_sha256(&inputs[..n]));
+        }
+        let mut minus_one = Fr::zero();
+        minus_one.sub_assign(&Fr::one());
+        assert_eq!(
+            sha256::<Bn256>(&[minus_one, minus_one]),
+            circuit_sha256(&[minus_one, minus_one])
+        );
+    }
+}
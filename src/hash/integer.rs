@@ -114,3 +114,71 @@ where
     );
     Ok(nat.group_limbs(limb_width))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use num_bigint::BigUint;
+
+    use hash::hashes::{Mimc, Pedersen, Poseidon, Sha256};
+    use hash::Hasher;
+    use util::test_helpers::*;
+
+    pub struct HashToInteger<H> {
+        inputs: Vec<&'static str>,
+        hasher: H,
+    }
+
+    impl<E, H> Circuit<E> for HashToInteger<H>
+    where
+        E: Engine,
+        H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
+    {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let domain = HashDomain {
+                n_bits: 256,
+                n_trailing_ones: 2,
+            };
+            let values: Vec<E::Fr> = self
+                .inputs
+                .iter()
+                .map(|s| E::Fr::from_str(s).unwrap())
+                .collect();
+            let inputs = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| AllocatedNum::alloc(cs.namespace(|| format!("in {}", i)), || Ok(*v)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let expected: BigUint = helper::hash_to_integer(&values, &domain, &self.hasher);
+            let hash =
+                hash_to_integer(cs.namespace(|| "hash"), &inputs, 32, &domain, &self.hasher)?;
+            let expected = BigNat::alloc_from_nat(
+                cs.namespace(|| "expected"),
+                || Ok(expected),
+                32,
+                hash.params.n_limbs,
+            )?;
+            hash.equal(cs.namespace(|| "eq"), &expected)
+        }
+    }
+
+    circuit_tests! {
+        poseidon_to_integer: (
+            HashToInteger { inputs: vec!["1", "2", "3"], hasher: Poseidon::<Bn256>::default() },
+            true
+        ),
+        pedersen_to_integer: (
+            HashToInteger { inputs: vec!["1", "2", "3"], hasher: Pedersen::<Bn256>::default() },
+            true
+        ),
+        mimc_to_integer: (
+            HashToInteger { inputs: vec!["1", "2", "3"], hasher: Mimc::<Bn256>::default() },
+            true
+        ),
+        sha_to_integer: (
+            HashToInteger { inputs: vec!["1", "2", "3"], hasher: Sha256::<Bn256>::default() },
+            true
+        ),
+    }
+}
@@ -12,29 +12,58 @@ use OptionExt;
 
 pub mod helper {
     use num_bigint::BigUint;
+    use num_traits::{One, Zero};
     use sapling_crypto::bellman::pairing::ff::Field;
 
     use super::super::integer::helper::hash_to_integer;
-    use super::super::{HashDomain, Hasher};
+    use super::super::{HashDomain, HashError, Hasher};
 
-    /// Returns whether `n` passes Miller-Rabin checks with the first `rounds` primes as bases
-    pub fn miller_rabin(n: &BigUint, rounds: usize) -> bool {
-        fn primes(n: usize) -> Vec<usize> {
-            let mut ps = vec![2];
-            let mut next = 3;
-            while ps.len() < n {
-                if !ps.iter().any(|p| next % p == 0) {
-                    ps.push(next);
-                }
-                next += 1;
+    use std::cmp::{max, min};
+
+    /// The first `n` primes.
+    fn primes(n: usize) -> Vec<usize> {
+        let mut ps = vec![2];
+        let mut next = 3;
+        while ps.len() < n {
+            if !ps.iter().any(|p| next % p == 0) {
+                ps.push(next);
             }
-            ps
+            next += 1;
         }
+        ps
+    }
+
+    /// Returns whether `n` passes Miller-Rabin checks with the first `rounds` primes as bases
+    pub fn miller_rabin(n: &BigUint, rounds: usize) -> bool {
         let ps = primes(rounds);
         !ps.into_iter()
             .any(|p| !miller_rabin_round(n, &BigUint::from(p)))
     }
 
+    /// Returns whether `n` is probably prime, by `miller_rabin` with `rounds` rounds.
+    ///
+    /// Unlike `miller_rabin`, this takes any `n`: numbers below two are not prime, and the bases
+    /// themselves are.
+    pub fn is_probably_prime(n: &BigUint, rounds: usize) -> bool {
+        if n <= &BigUint::one() {
+            return false;
+        }
+        for p in primes(max(rounds, 1)) {
+            if n == &BigUint::from(p) {
+                return true;
+            }
+            if (n % p).is_zero() {
+                return false;
+            }
+        }
+        miller_rabin(n, rounds)
+    }
+
+    /// The number of bits of `n`, so that `n` is in `[2^(b-1), 2^b)`. Zero has no bits.
+    pub fn bit_length(n: &BigUint) -> usize {
+        n.bits()
+    }
+
     /// Returns whether `n` passes a Miller-Rabin check with base `b`.
     fn miller_rabin_round(n: &BigUint, b: &BigUint) -> bool {
         let n_less_one = n - 1usize;
@@ -74,28 +103,64 @@ pub mod helper {
     ///
     /// and returns a tupe `(hash, nonce, bitwidth)`.
     ///
-    /// If, by misfortune, there is no such nonce, returns `None`.
+    /// Nonces are tried in increasing order, at most `max_attempts` of them. If none of them gives
+    /// a prime, returns `HashError::NoPrimeFound`.
     pub fn hash_to_prime<H: Hasher>(
         inputs: &[H::F],
         domain: &HashDomain,
         hasher: &H,
-    ) -> Option<(BigUint, H::F, usize)> {
+        max_attempts: usize,
+    ) -> Result<(BigUint, H::F, usize), HashError> {
         let n_bits = domain.nonce_width();
         let mut inputs: Vec<H::F> = inputs.iter().copied().collect();
         inputs.push(H::F::zero());
-        for _ in 0..(1 << n_bits) {
+        // Larger nonces would not fit in the bitwidth.
+        for _ in 0..min(max_attempts, 1 << n_bits) {
             let hash = hash_to_integer::<H>(&inputs, domain, hasher);
             if miller_rabin(&hash, 30) {
+                debug_assert_eq!(bit_length(&hash), domain.n_bits);
                 // unwrap is safe because of the push above
-                return Some((hash, inputs.pop().unwrap(), n_bits));
+                return Ok((hash, inputs.pop().unwrap(), n_bits));
             }
             // unwrap is safe because of the push above
             inputs.last_mut().unwrap().add_assign(&H::F::one());
         }
-        None
+        Err(HashError::NoPrimeFound { max_attempts })
+    }
+
+    /// Like `hash_to_prime`, but hashes with every nonce in the search range, even after a prime
+    /// is found, so that the number of hash evaluations does not depend on `inputs`. Use it when
+    /// the inputs are private, and the time taken could reveal how long the search was.
+    ///
+    /// Every hash is checked with Miller-Rabin too. The time a check takes still depends on the
+    /// hash, but not on where in the range the first prime was.
+    pub fn hash_to_prime_blinded<H: Hasher>(
+        inputs: &[H::F],
+        domain: &HashDomain,
+        hasher: &H,
+        max_attempts: usize,
+    ) -> Result<(BigUint, H::F, usize), HashError> {
+        let n_bits = domain.nonce_width();
+        let mut inputs: Vec<H::F> = inputs.iter().copied().collect();
+        inputs.push(H::F::zero());
+        let mut found = None;
+        for _ in 0..min(max_attempts, 1 << n_bits) {
+            let hash = hash_to_integer::<H>(&inputs, domain, hasher);
+            // unwrap is safe because of the push above
+            let nonce = inputs.last_mut().unwrap();
+            if miller_rabin(&hash, 30) && found.is_none() {
+                found = Some((hash, *nonce, n_bits));
+            }
+            nonce.add_assign(&H::F::one());
+        }
+        found.ok_or(HashError::NoPrimeFound { max_attempts })
     }
 }
 
+/// Hashes `input` to a prime in `domain`, checked with `rounds` rounds of Miller-Rabin.
+///
+/// The nonce which makes the hash prime is a witness, found by trying at most `max_attempts`
+/// nonces, and is constrained to the nonce bitwidth of `domain`.
 pub fn hash_to_prime<E, H, CS>(
     mut cs: CS,
     input: &[AllocatedNum<E>],
@@ -103,6 +168,7 @@ pub fn hash_to_prime<E, H, CS>(
     domain: &HashDomain,
     hasher: &H,
     rounds: usize,
+    max_attempts: usize,
 ) -> Result<BigNat<E>, SynthesisError>
 where
     E: Engine,
@@ -118,8 +184,8 @@ where
             .iter()
             .map(|i| i.get_value())
             .collect::<Option<Vec<E::Fr>>>();
-        let (_, nonce, _) = helper::hash_to_prime::<H>(&inputs.grab()?, domain, hasher)
-            .ok_or(SynthesisError::Unsatisfiable)?;
+        let (_, nonce, _) =
+            helper::hash_to_prime::<H>(&inputs.grab()?, domain, hasher, max_attempts)?;
         Ok(nonce)
     })?;
     Num::new(
@@ -139,13 +205,17 @@ mod test {
     use super::*;
 
     use num_bigint::BigUint;
-    use sapling_crypto::bellman::pairing::ff::PrimeField;
+    use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, ScalarEngine};
     use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
     use sapling_crypto::circuit::num::AllocatedNum;
 
     use hash::hashes::Poseidon;
+    use hash::HashError;
     use util::test_helpers::*;
 
+    use std::cell::Cell;
+    use std::rc::Rc;
+
     #[test]
     fn mr_11() {
         assert_eq!(helper::miller_rabin(&BigUint::from(11usize), 3), true);
@@ -166,6 +236,109 @@ mod test {
         assert_eq!(helper::miller_rabin(&BigUint::from(15usize), 3), false);
     }
 
+    #[test]
+    fn probable_primes() {
+        let is_prime = |n: usize| helper::is_probably_prime(&BigUint::from(n), 3);
+        let primes: Vec<usize> = (0..30).filter(|&n| is_prime(n)).collect();
+        assert_eq!(primes, vec![2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert!(is_prime(251));
+        assert!(!is_prime(251 * 257));
+        assert_eq!(helper::bit_length(&BigUint::from(0usize)), 0);
+        assert_eq!(helper::bit_length(&BigUint::from(1usize)), 1);
+        assert_eq!(helper::bit_length(&BigUint::from(255usize)), 8);
+        assert_eq!(helper::bit_length(&BigUint::from(256usize)), 9);
+    }
+
+    #[test]
+    fn hashed_primes_are_prime_and_in_range() {
+        let domain = HashDomain::new(128, 1).unwrap();
+        let hasher = Poseidon::<Bn256>::default();
+        let max_attempts = 1 << domain.nonce_width();
+        for i in 0..50usize {
+            let inputs = vec![<Bn256 as ScalarEngine>::Fr::from_str(&i.to_string()).unwrap()];
+            let (prime, _, _) = helper::hash_to_prime(&inputs, &domain, &hasher, max_attempts)
+                .expect("no prime in the nonce bitwidth");
+            // More bases than `hash_to_prime` tries, so this is not the same test again.
+            assert!(helper::is_probably_prime(&prime, 60));
+            assert_eq!(helper::bit_length(&prime), domain.n_bits);
+        }
+    }
+
+    #[test]
+    fn nonce_search_is_capped() {
+        let domain = HashDomain::new(32, 2).unwrap();
+        let hasher = Poseidon::<Bn256>::default();
+        // An input whose hash is not prime with the first nonce.
+        let inputs = (0..100usize)
+            .map(|i| vec![<Bn256 as ScalarEngine>::Fr::from_str(&i.to_string()).unwrap()])
+            .find(|inputs| {
+                let (_, nonce, _) = helper::hash_to_prime(inputs, &domain, &hasher, 1 << 11)
+                    .expect("no prime in the nonce bitwidth");
+                !nonce.is_zero()
+            })
+            .expect("every first nonce gives a prime");
+        assert_eq!(
+            helper::hash_to_prime(&inputs, &domain, &hasher, 1).unwrap_err(),
+            HashError::NoPrimeFound { max_attempts: 1 }
+        );
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let allocated_inputs =
+            vec![AllocatedNum::alloc(cs.namespace(|| "input"), || Ok(inputs[0])).unwrap()];
+        for &(max_attempts, found) in &[(1, false), (1 << 11, true)] {
+            let hash = hash_to_prime(
+                cs.namespace(|| format!("hash {}", max_attempts)),
+                &allocated_inputs,
+                32,
+                &domain,
+                &hasher,
+                3,
+                max_attempts,
+            );
+            assert_eq!(hash.is_ok(), found);
+        }
+        assert!(cs.is_satisfied());
+    }
+
+    /// Counts the hashes it computes.
+    #[derive(Clone)]
+    struct CountingHasher {
+        inner: Poseidon<Bn256>,
+        count: Rc<Cell<usize>>,
+    }
+
+    impl Hasher for CountingHasher {
+        type F = <Bn256 as ScalarEngine>::Fr;
+        fn hash2(&self, a: Self::F, b: Self::F) -> Self::F {
+            self.count.set(self.count.get() + 1);
+            self.inner.hash2(a, b)
+        }
+    }
+
+    #[test]
+    fn blinded_search_hashes_a_fixed_number_of_times() {
+        let domain = HashDomain::new(32, 2).unwrap();
+        let hasher = CountingHasher {
+            inner: Poseidon::default(),
+            count: Rc::new(Cell::new(0)),
+        };
+        let max_attempts = 1 << domain.nonce_width();
+        let mut nonces = Vec::new();
+        let mut blinded_counts = Vec::new();
+        for i in 0..10usize {
+            let inputs = vec![<Bn256 as ScalarEngine>::Fr::from_str(&i.to_string()).unwrap()];
+            let found = helper::hash_to_prime(&inputs, &domain, &hasher, max_attempts);
+            hasher.count.set(0);
+            let blinded = helper::hash_to_prime_blinded(&inputs, &domain, &hasher, max_attempts);
+            assert_eq!(found, blinded);
+            nonces.push(found.unwrap().1);
+            blinded_counts.push(hasher.count.replace(0));
+        }
+        // The searches took different numbers of hashes, but the blinded ones did not.
+        assert!(nonces.iter().any(|n| n != &nonces[0]));
+        assert!(blinded_counts.iter().all(|c| c == &blinded_counts[0]));
+    }
+
     #[derive(Debug)]
     pub struct PrimeHashInputs<'a> {
         pub inputs: &'a [&'a str],
@@ -196,8 +369,10 @@ mod test {
                 n_bits: self.params.desired_bits,
                 n_trailing_ones: 2,
             };
+            let max_attempts = 1 << domain.nonce_width();
             let (expected_ouput, _, _) =
-                helper::hash_to_prime(&input_values, &domain, &self.params.hasher).unwrap();
+                helper::hash_to_prime(&input_values, &domain, &self.params.hasher, max_attempts)
+                    .unwrap();
             let allocated_expected_output = BigNat::alloc_from_nat(
                 cs.namespace(|| "output"),
                 || Ok(expected_ouput),
@@ -218,6 +393,7 @@ mod test {
                 &domain,
                 &self.params.hasher,
                 self.params.n_rounds,
+                max_attempts,
             )?;
             assert_eq!(
                 hash.limbs.len() * hash.params.limb_width,
@@ -5,11 +5,13 @@ pub mod miller_rabin_prime;
 pub mod pocklington;
 
 use std::clone::Clone;
+use std::fmt::{self, Display, Formatter};
 
 use num_bigint::BigUint;
 use num_traits::One;
 use sapling_crypto::bellman::pairing::ff::Field;
 use sapling_crypto::bellman::pairing::ff::PrimeField;
+use sapling_crypto::bellman::SynthesisError;
 
 /// A representation of an integer domain to hash to
 #[derive(Clone, Debug)]
@@ -18,7 +20,87 @@ pub struct HashDomain {
     pub n_trailing_ones: usize,
 }
 
+/// A `HashDomain` which cannot be hashed to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashDomainError {
+    /// Without a trailing one, the domain contains even numbers.
+    NoTrailingOnes,
+    /// The top bit and the trailing ones are fixed, which leaves no bits for the hash.
+    NoHashBits { n_bits: usize, n_trailing_ones: usize },
+}
+
+impl Display for HashDomainError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            HashDomainError::NoTrailingOnes => {
+                write!(f, "a hash domain needs a trailing one to contain only odd numbers")
+            }
+            HashDomainError::NoHashBits {
+                n_bits,
+                n_trailing_ones,
+            } => write!(
+                f,
+                "a {}-bit hash domain with {} trailing ones has no bits left for the hash",
+                n_bits, n_trailing_ones
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HashDomainError {}
+
+impl From<HashDomainError> for SynthesisError {
+    fn from(e: HashDomainError) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
+/// A failure to hash to a number of the required form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashError {
+    /// None of the nonces tried gave a prime.
+    NoPrimeFound { max_attempts: usize },
+}
+
+impl Display for HashError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            HashError::NoPrimeFound { max_attempts } => {
+                write!(f, "no prime found in {} nonce attempts", max_attempts)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HashError {}
+
+impl From<HashError> for SynthesisError {
+    fn from(e: HashError) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
 impl HashDomain {
+    /// The domain of odd `n_bits`-bit numbers whose low `n_trailing_ones` bits are set.
+    /// At least one bit must be left for the hash, between the top bit and the trailing ones.
+    pub fn new(n_bits: usize, n_trailing_ones: usize) -> Result<Self, HashDomainError> {
+        if n_trailing_ones == 0 {
+            return Err(HashDomainError::NoTrailingOnes);
+        }
+        if n_trailing_ones + 1 >= n_bits {
+            return Err(HashDomainError::NoHashBits {
+                n_bits,
+                n_trailing_ones,
+            });
+        }
+        Ok(Self {
+            n_bits,
+            n_trailing_ones,
+        })
+    }
+
     pub fn nonce_width(&self) -> usize {
         let n_rounds = -128f64 * 2f64.ln() / (1f64 - 2f64 / self.n_bits as f64).ln();
         let n_bits = (n_rounds.log2().ceil() + 0.1) as usize;
@@ -31,6 +113,27 @@ pub fn low_k_bits(n: &BigUint, k: usize) -> BigUint {
     n & ((BigUint::one() << k) - 1usize)
 }
 
+/// The accumulator element which represents `item`: its division-intractable hash into `domain`,
+/// offset by `division_intractable::offset(domain.n_bits)`.
+///
+/// This matches `set::rsa::Set::element`, and the in-circuit `division_intractable::di_hash`, so it
+/// can be used to index a set or build membership witnesses outside of a circuit. Panics if
+/// `domain.n_bits` is not 128, 512 or 2048, or is not a multiple of `limb_width`.
+pub fn element_of<H: Hasher>(
+    item: &[H::F],
+    domain: &HashDomain,
+    limb_width: usize,
+    hasher: &H,
+) -> BigUint {
+    division_intractable::helper::di_hash(
+        item,
+        &division_intractable::offset(domain.n_bits),
+        domain,
+        limb_width,
+        hasher,
+    )
+}
+
 pub trait Hasher: Clone {
     type F: PrimeField;
     fn hash2(&self, a: Self::F, b: Self::F) -> Self::F;
@@ -189,3 +292,28 @@ pub mod circuit {
                 }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{HashDomain, HashDomainError};
+
+    #[test]
+    fn hash_domain_boundaries() {
+        assert!(HashDomain::new(128, 1).is_ok());
+        assert!(HashDomain::new(3, 1).is_ok());
+        assert_eq!(
+            HashDomain::new(128, 0).unwrap_err(),
+            HashDomainError::NoTrailingOnes
+        );
+        assert_eq!(
+            HashDomain::new(2, 1).unwrap_err(),
+            HashDomainError::NoHashBits {
+                n_bits: 2,
+                n_trailing_ones: 1
+            }
+        );
+        assert!(HashDomain::new(128, 127).is_err());
+        assert!(HashDomain::new(128, 128).is_err());
+        assert!(HashDomain::new(0, 1).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use num_bigint::BigUint;
+use num_integer::Integer;
+use num_iter::range_step;
+use num_traits::{One, Zero};
+use sapling_crypto::bellman::pairing::ff::Field;
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
+use sapling_crypto::circuit::boolean::Boolean;
+
+use hash::miller_rabin_prime::helper::{miller_rabin, miller_rabin_32b};
+use mp::bignat::BigNat;
+
+use std::cmp::max;
+
+/// Factors below this bound are stripped from `n - 1` by trial division when looking for a
+/// certificate. Every prime certified by an extension is above `2^32`, so the prime factor it
+/// extends is above `2^16`, and is never stripped.
+const TRIAL_DIVISION_BOUND: usize = 1 << 16;
+
+/// One step of a Pocklington certificate: from a prime `p`, the number `p * cofactor + 1` is
+/// prime if `cofactor < p` and the checking base `a` satisfies
+///
+///     a^(p * cofactor) = 1 (mod p * cofactor + 1)
+///     gcd(a^cofactor - 1, p * cofactor + 1) = 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PocklingtonStep {
+    pub cofactor: BigUint,
+    pub checking_base: BigUint,
+}
+
+/// A certificate that a number is prime: a base prime of at most 32 bits, which is checked by
+/// Miller-Rabin, extended by a chain of Pocklington steps.
+///
+/// Unlike `helper::PocklingtonCertificate`, which records how a hash output was extended to a
+/// prime, this certifies any prime with the right shape. See `prove_prime`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PocklingtonCertificate {
+    pub base_prime: BigUint,
+    pub steps: Vec<PocklingtonStep>,
+}
+
+impl PocklingtonCertificate {
+    /// The number this certificate claims is prime.
+    pub fn number(&self) -> BigUint {
+        self.steps
+            .iter()
+            .fold(self.base_prime.clone(), |p, step| p * &step.cofactor + 1usize)
+    }
+}
+
+/// Builds a certificate that `n` is prime.
+///
+/// Returns `None` if `n` is composite, or if no certificate is found: each step needs `n - 1` to
+/// be a prime times a smaller cofactor made of factors below `2^16`, so many primes have none.
+pub fn prove_prime(n: &BigUint) -> Option<PocklingtonCertificate> {
+    if n.bits() <= 32 {
+        return if n > &BigUint::one() && miller_rabin_32b(n) {
+            Some(PocklingtonCertificate {
+                base_prime: n.clone(),
+                steps: Vec::new(),
+            })
+        } else {
+            None
+        };
+    }
+    let mut p: BigUint = n - 1usize;
+    let mut cofactor = BigUint::one();
+    for d in 2..TRIAL_DIVISION_BOUND {
+        while (&p % d).is_zero() {
+            p /= d;
+            cofactor *= d;
+        }
+    }
+    if cofactor >= p || !miller_rabin(&p, 20) {
+        return None;
+    }
+    let mut cert = prove_prime(&p)?;
+    for base in range_step(BigUint::from(2usize), n.clone(), BigUint::one()) {
+        let part = base.modpow(&cofactor, n);
+        if !part.modpow(&p, n).is_one() {
+            // Fermat's little theorem fails, so `n` is composite.
+            return None;
+        }
+        if (&part - 1usize).gcd(n).is_one() {
+            cert.steps.push(PocklingtonStep {
+                cofactor,
+                checking_base: base,
+            });
+            return Some(cert);
+        }
+    }
+    None
+}
+
+fn n_limbs_for(n: &BigUint, limb_width: usize) -> usize {
+    max(1, (n.bits() + limb_width - 1) / limb_width)
+}
+
+/// Constrains `candidate` to be prime, using `cert`.
+///
+/// The certificate is a plain value, and its shape (the number of steps, and the widths of the
+/// cofactors and checking bases) determines the constraints. The constraints are satisfied only
+/// if `cert` proves that `candidate` is prime.
+pub fn verify_certificate<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    candidate: &BigNat<E>,
+    cert: &PocklingtonCertificate,
+) -> Result<(), SynthesisError> {
+    let limb_width = candidate.params.limb_width;
+    // A constant, since an allocated one would let the prover choose it.
+    let one = BigNat::identity::<CS>(limb_width);
+
+    // Allocate and check the base prime.
+    let mut prime = BigNat::alloc_from_nat(
+        cs.namespace(|| "base prime"),
+        || Ok(cert.base_prime.clone()),
+        limb_width,
+        (32 - 1) / limb_width + 1,
+    )?;
+    prime.assert_bit_length(cs.namespace(|| "base fits"), 32)?;
+    one.enforce_lt(cs.namespace(|| "base > 1"), &prime)?;
+    let mr_res = prime.miller_rabin_32b(cs.namespace(|| "base check"))?;
+    Boolean::enforce_equal(
+        cs.namespace(|| "MR passes"),
+        &mr_res,
+        &Boolean::constant(true),
+    )?;
+
+    // For each step...
+    for (i, step) in cert.steps.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("step {}", i));
+
+        let cofactor = BigNat::alloc_from_nat(
+            cs.namespace(|| "cofactor"),
+            || Ok(step.cofactor.clone()),
+            limb_width,
+            n_limbs_for(&step.cofactor, limb_width),
+        )?;
+        cofactor.decompose(cs.namespace(|| "cofactor decomp"))?;
+        one.enforce_lt(cs.namespace(|| "cofactor > 1"), &cofactor)?;
+        cofactor.enforce_lt(cs.namespace(|| "cofactor < p"), &prime)?;
+        let base = BigNat::alloc_from_nat(
+            cs.namespace(|| "base"),
+            || Ok(step.checking_base.clone()),
+            limb_width,
+            n_limbs_for(&step.checking_base, limb_width),
+        )?;
+        base.decompose(cs.namespace(|| "base decomp"))?;
+
+        // Compute helper values for pocklington's criterion
+        let n_less_one = cofactor.mult(cs.namespace(|| "n - 1"), &prime)?;
+        let n = n_less_one.shift::<CS>(E::Fr::one());
+        let part = base.pow_mod(cs.namespace(|| "a^r"), &cofactor, &n)?;
+        let part_less_one = part.sub(cs.namespace(|| "a^r - 1"), &one)?;
+
+        // Check coprimality
+        part_less_one.enforce_coprime(cs.namespace(|| "coprime"), &n)?;
+        let power = part.pow_mod(cs.namespace(|| "a^r^p"), &prime, &n)?;
+
+        // Check fermat's little theorem
+        power.equal_when_carried(cs.namespace(|| "a^r^p == 1"), &one)?;
+
+        prime = n;
+    }
+    candidate.equal_when_carried(cs.namespace(|| "candidate"), &prime)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use util::test_helpers::*;
+
+    fn certificate_is_satisfied(n: &BigUint, cert: &PocklingtonCertificate) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let candidate = BigNat::alloc_from_nat(
+            cs.namespace(|| "candidate"),
+            || Ok(n.clone()),
+            32,
+            n_limbs_for(n, 32),
+        )
+        .unwrap();
+        verify_certificate(cs.namespace(|| "verify"), &candidate, cert).is_ok()
+            && cs.is_satisfied()
+    }
+
+    #[test]
+    fn small_primes_need_no_steps() {
+        let cert = prove_prime(&BigUint::from(4294967291usize)).unwrap();
+        assert!(cert.steps.is_empty());
+        assert_eq!(prove_prime(&BigUint::from(4294967293usize)), None);
+        assert_eq!(prove_prime(&BigUint::from(1usize)), None);
+    }
+
+    #[test]
+    fn certificate_for_known_prime() {
+        // 2^64 - 95
+        let n = BigUint::from(18446744073709551521u64);
+        let cert = prove_prime(&n).expect("no certificate");
+        assert_eq!(cert.number(), n);
+        assert_eq!(cert.steps.len(), 3);
+        assert!(certificate_is_satisfied(&n, &cert));
+        assert!(!certificate_is_satisfied(&(&n + 2usize), &cert));
+    }
+
+    #[test]
+    fn certificate_for_composite_is_rejected() {
+        // 2^64 - 97
+        assert_eq!(prove_prime(&BigUint::from(18446744073709551519u64)), None);
+
+        let n = BigUint::from(18446744073709551521u64);
+        let mut cert = prove_prime(&n).expect("no certificate");
+        cert.steps.last_mut().unwrap().cofactor += 2usize;
+        let composite = cert.number();
+        assert!(!miller_rabin(&composite, 20));
+        assert!(!certificate_is_satisfied(&composite, &cert));
+    }
+}
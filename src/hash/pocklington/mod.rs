@@ -1,5 +1,10 @@
+mod certificate;
 mod entropy;
 
+pub use self::certificate::{
+    prove_prime, verify_certificate, PocklingtonCertificate, PocklingtonStep,
+};
+
 pub mod helper {
 
     use num_bigint::BigUint;
@@ -266,32 +271,52 @@ pub fn hash_to_pocklington_prime<
     E: Engine,
     H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
     CS: ConstraintSystem<E>,
+>(
+    cs: CS,
+    input: &[AllocatedNum<E>],
+    limb_width: usize,
+    entropy: usize,
+    base_hash: &H,
+) -> Result<BigNat<E>, SynthesisError> {
+    // Construct a certificate if we actually have values.
+    let cert: Option<helper::PocklingtonCertificate> = input
+        .iter()
+        .map(|n| n.get_value().clone())
+        .collect::<Option<Vec<E::Fr>>>()
+        .and_then(|is| helper::hash_to_pocklington_prime(&is, entropy, base_hash));
+    hash_to_certified_prime(cs, input, limb_width, entropy, base_hash, cert.as_ref())
+}
+
+/// Like `hash_to_pocklington_prime`, but the Pocklington certificate (the nonces and checking
+/// bases) is supplied by the caller, e.g. from `helper::hash_to_pocklington_prime`. The
+/// constraints are satisfied only if `cert` proves that each candidate derived from the hash of
+/// `input` is prime, so a certificate for a composite candidate is rejected.
+pub fn hash_to_certified_prime<
+    E: Engine,
+    H: CircuitHasher<E = E>,
+    CS: ConstraintSystem<E>,
 >(
     mut cs: CS,
     input: &[AllocatedNum<E>],
     limb_width: usize,
     entropy: usize,
     base_hash: &H,
+    cert: Option<&helper::PocklingtonCertificate>,
 ) -> Result<BigNat<E>, SynthesisError> {
-    use self::helper::{PocklingtonCertificate, PocklingtonPlan};
+    use self::helper::PocklingtonPlan;
 
     // Hash the inputs into an entropy pool.
     let hash = base_hash.allocate_hash(cs.namespace(|| "base hash"), &input)?;
     let mut entropy_source =
         EntropySource::alloc(cs.namespace(|| "entropy source"), Some(&()), hash, &entropy)?;
 
-    // Construct a pocklington plan, and a certificate if we actually have values.
+    // Construct a pocklington plan.
     let plan = PocklingtonPlan::new(entropy);
-    let cert: Option<PocklingtonCertificate> = input
-        .iter()
-        .map(|n| n.get_value().clone())
-        .collect::<Option<Vec<E::Fr>>>()
-        .and_then(|is| helper::hash_to_pocklington_prime(&is, entropy, base_hash));
 
     // Allocate the base nonce.
     let base_nonce = BigNat::from_num(
         Num::from(AllocatedNum::alloc(cs.namespace(|| "base nonce"), || {
-            Ok(usize_to_f(cert.as_ref().grab()?.base_nonce as usize))
+            Ok(usize_to_f(cert.grab()?.base_nonce as usize))
         })?),
         BigNatParams {
             n_limbs: 1, // TODO consider allowing larger nonces
@@ -327,7 +352,7 @@ pub fn hash_to_pocklington_prime<
         let nonce = BigNat::from_num(
             Num::from(AllocatedNum::alloc(cs.namespace(|| "nonce"), || {
                 Ok(usize_to_f(
-                    cert.as_ref().grab()?.extensions[i].nonce as usize,
+                    cert.grab()?.extensions[i].nonce as usize,
                 ))
             })?),
             BigNatParams {
@@ -351,18 +376,20 @@ pub fn hash_to_pocklington_prime<
             cs.namespace(|| "base"),
             || {
                 Ok(BigUint::from(
-                    cert.as_ref().grab()?.extensions[i].checking_base.clone(),
+                    cert.grab()?.extensions[i].checking_base.clone(),
                 ))
             },
             limb_width,
             1, // TODO consider allow larger bases
         )?;
+        base.decompose(cs.namespace(|| "base decomp"))?;
 
         // Compute helper values for pocklington's criterion
         let n_less_one = extension.mult(cs.namespace(|| "n - 1"), &prime)?;
         let n = n_less_one.shift::<CS>(E::Fr::one());
         let part = base.pow_mod(cs.namespace(|| "a^r"), &extension, &n)?;
-        let one = BigNat::one(cs.namespace(|| "one"), prime.params().limb_width)?;
+        // A constant, since an allocated one would let the prover choose its value.
+        let one = BigNat::identity::<CS>(prime.params().limb_width);
         let part_less_one = part.sub(cs.namespace(|| "a^r - 1"), &one)?;
 
         // Check coprimality
@@ -381,7 +408,8 @@ pub fn hash_to_pocklington_prime<
 
 #[cfg(test)]
 mod test {
-    use super::{hash_to_pocklington_prime, helper};
+    use super::{hash_to_certified_prime, hash_to_pocklington_prime, helper};
+    use num_integer::Integer;
     use sapling_crypto::bellman::pairing::ff::{PrimeField, ScalarEngine};
     use sapling_crypto::bellman::pairing::Engine;
     use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
@@ -498,6 +526,83 @@ mod test {
         }
     }
 
+    fn certificate_is_satisfied(
+        inputs: &[<Bn256 as ScalarEngine>::Fr],
+        entropy: usize,
+        cert: &helper::PocklingtonCertificate,
+    ) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let allocated_inputs: Vec<AllocatedNum<Bn256>> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || Ok(*value))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        hash_to_certified_prime(
+            cs.namespace(|| "hash"),
+            &allocated_inputs,
+            32,
+            entropy,
+            &Poseidon::<Bn256>::default(),
+            Some(cert),
+        )
+        .is_ok()
+            && cs.is_satisfied()
+    }
+
+    #[test]
+    fn certified_prime_rejects_composite_base() {
+        let entropy = 29;
+        let hash = Poseidon::<Bn256>::default();
+        // The helper takes the first nonce giving a prime base, so when that nonce is at least
+        // two, the nonce two before it gives an odd composite base.
+        let (inputs, cert) = (1..100usize)
+            .map(|i| vec![<Bn256 as ScalarEngine>::Fr::from_str(&i.to_string()).unwrap()])
+            .map(|inputs| {
+                let cert = helper::hash_to_pocklington_prime(&inputs, entropy, &hash).unwrap();
+                (inputs, cert)
+            })
+            .find(|(_, cert)| cert.base_nonce > 1)
+            .expect("every base nonce is below two");
+        assert!(certificate_is_satisfied(&inputs, entropy, &cert));
+
+        let mut composite = cert.clone();
+        composite.base_nonce -= 2;
+        let composite_base = &cert.base_prime - 2usize;
+        assert!(composite_base.is_odd());
+        assert!(!miller_rabin_prime::helper::miller_rabin_32b(&composite_base));
+        assert!(!certificate_is_satisfied(&inputs, entropy, &composite));
+    }
+
+    #[test]
+    fn certified_prime_rejects_bad_extension() {
+        let entropy = 29;
+        let hash = Poseidon::<Bn256>::default();
+        // The helper takes the first extension nonce for which some base proves the extension
+        // prime, and tries bases upwards from two, so base two proves nothing for earlier nonces.
+        let (inputs, cert) = (1..100usize)
+            .map(|i| vec![<Bn256 as ScalarEngine>::Fr::from_str(&i.to_string()).unwrap()])
+            .map(|inputs| {
+                let cert = helper::hash_to_pocklington_prime(&inputs, entropy, &hash).unwrap();
+                (inputs, cert)
+            })
+            .find(|(_, cert)| cert.extensions[0].nonce > 0)
+            .expect("every extension nonce is zero");
+        assert!(certificate_is_satisfied(&inputs, entropy, &cert));
+
+        let mut earlier_nonce = cert.clone();
+        earlier_nonce.extensions[0].nonce = 0;
+        earlier_nonce.extensions[0].checking_base = 2usize.into();
+        assert!(!certificate_is_satisfied(&inputs, entropy, &earlier_nonce));
+
+        // With base one, a^r - 1 is zero, which is not coprime to the candidate.
+        let mut base_one = cert.clone();
+        base_one.extensions[0].checking_base = 1usize.into();
+        assert!(!certificate_is_satisfied(&inputs, entropy, &base_one));
+    }
+
     circuit_tests! {
         pocklington_hash_29_1: (
             PockHash {
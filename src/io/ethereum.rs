@@ -0,0 +1,212 @@
+//! ABI encoding of Groth16 proofs for a Solidity verifier with the entry point
+//! `verifyProof(uint[2] a, uint[2][2] b, uint[2] c, uint[] input)`.
+//!
+//! Points are encoded as the BN256 precompiles (EIP-196/197) expect: affine coordinates, with the
+//! point at infinity as `(0, 0)`. An `Fq2` element `c0 + c1 * u` is written as `[c1, c0]`, so the
+//! G2 point `b` is `[[x.c1, x.c0], [y.c1, y.c0]]`.
+
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use sapling_crypto::bellman::groth16::Proof;
+use sapling_crypto::bellman::pairing::bn256::{Bn256, Fq, Fq2, Fr, G1Affine, G2Affine};
+use sapling_crypto::bellman::pairing::ff::PrimeField;
+use sapling_crypto::bellman::pairing::CurveAffine;
+
+use std::io;
+
+use mp::bignat::{nat_from_bytes_be, nat_to_bytes_be};
+use util::convert::{f_to_nat, nat_to_f};
+
+/// The first four bytes of `keccak256("verifyProof(uint256[2],uint256[2][2],uint256[2],uint256[])")`.
+pub const VERIFY_PROOF_SELECTOR: [u8; 4] = [0xc3, 0x2e, 0x37, 0x0e];
+
+const WORD: usize = 32;
+/// The words of `a`, `b`, and `c`, which are encoded in place.
+const PROOF_WORDS: usize = 2 + 4 + 2;
+
+fn word<F: PrimeField>(f: &F) -> Vec<u8> {
+    nat_to_bytes_be(&f_to_nat(f), 8 * WORD).expect("field elements fit in a word")
+}
+
+fn usize_word(n: usize) -> Vec<u8> {
+    nat_to_bytes_be(&BigUint::from(n), 8 * WORD).expect("a usize fits in a word")
+}
+
+fn g1_words(p: &G1Affine) -> Vec<u8> {
+    if p.is_zero() {
+        return vec![0u8; 2 * WORD];
+    }
+    let (x, y) = p.into_xy_unchecked();
+    let mut bytes = word(&x);
+    bytes.extend(word(&y));
+    bytes
+}
+
+fn fq2_words(f: &Fq2) -> Vec<u8> {
+    let mut bytes = word(&f.c1);
+    bytes.extend(word(&f.c0));
+    bytes
+}
+
+fn g2_words(p: &G2Affine) -> Vec<u8> {
+    if p.is_zero() {
+        return vec![0u8; 4 * WORD];
+    }
+    let (x, y) = p.into_xy_unchecked();
+    let mut bytes = fq2_words(&x);
+    bytes.extend(fq2_words(&y));
+    bytes
+}
+
+/// The calldata for `verifyProof(proof.a, proof.b, proof.c, inputs)`.
+/// `inputs` excludes the leading `1`.
+pub fn encode_verify_proof(proof: &Proof<Bn256>, inputs: &[Fr]) -> Vec<u8> {
+    let mut data = VERIFY_PROOF_SELECTOR.to_vec();
+    data.extend(g1_words(&proof.a));
+    data.extend(g2_words(&proof.b));
+    data.extend(g1_words(&proof.c));
+    // The dynamic array is encoded after the head, which is the proof and this offset.
+    data.extend(usize_word((PROOF_WORDS + 1) * WORD));
+    data.extend(usize_word(inputs.len()));
+    for input in inputs {
+        data.extend(word(input));
+    }
+    data
+}
+
+fn invalid<T>(msg: String) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn read_nat(data: &[u8], word_i: usize) -> io::Result<BigUint> {
+    match data.get(word_i * WORD..(word_i + 1) * WORD) {
+        Some(bytes) => Ok(nat_from_bytes_be(bytes)),
+        None => invalid(format!("calldata has no word {}", word_i)),
+    }
+}
+
+fn read_f<F: PrimeField>(data: &[u8], word_i: usize) -> io::Result<F> {
+    let n = read_nat(data, word_i)?;
+    nat_to_f(&n).map_or_else(|| invalid(format!("{} is not a field element", n)), Ok)
+}
+
+fn read_fq2(data: &[u8], word_i: usize) -> io::Result<Fq2> {
+    Ok(Fq2 {
+        c1: read_f(data, word_i)?,
+        c0: read_f(data, word_i + 1)?,
+    })
+}
+
+/// Decodes calldata produced by `encode_verify_proof`.
+pub fn decode_verify_proof(data: &[u8]) -> io::Result<(Proof<Bn256>, Vec<Fr>)> {
+    if !data.starts_with(&VERIFY_PROOF_SELECTOR) {
+        return invalid("calldata does not call verifyProof".to_string());
+    }
+    let data = &data[VERIFY_PROOF_SELECTOR.len()..];
+    let g1 = |word_i: usize| -> io::Result<G1Affine> {
+        let x: Fq = read_f(data, word_i)?;
+        let y: Fq = read_f(data, word_i + 1)?;
+        if x.is_zero() && y.is_zero() {
+            return Ok(G1Affine::zero());
+        }
+        G1Affine::from_xy_checked(x, y).or_else(|e| invalid(format!("{}", e)))
+    };
+    let b = {
+        let x = read_fq2(data, 2)?;
+        let y = read_fq2(data, 4)?;
+        if x.is_zero() && y.is_zero() {
+            G2Affine::zero()
+        } else {
+            G2Affine::from_xy_checked(x, y).or_else(|e| invalid(format!("{}", e)))?
+        }
+    };
+    let proof = Proof {
+        a: g1(0)?,
+        b,
+        c: g1(6)?,
+    };
+    let offset = read_nat(data, PROOF_WORDS)?;
+    if offset != BigUint::from((PROOF_WORDS + 1) * WORD) {
+        return invalid(format!("unexpected input offset {}", offset));
+    }
+    let n_inputs = read_nat(data, PROOF_WORDS + 1)?;
+    let expected_len = n_inputs
+        .to_usize()
+        .and_then(|n| n.checked_add(PROOF_WORDS + 2))
+        .and_then(|words| words.checked_mul(WORD));
+    let n_inputs = match n_inputs.to_usize() {
+        Some(n) if expected_len == Some(data.len()) => n,
+        _ => {
+            return invalid(format!(
+                "calldata for {} inputs has {} bytes",
+                n_inputs,
+                data.len()
+            ))
+        }
+    };
+    let inputs = (0..n_inputs)
+        .map(|i| read_f(data, PROOF_WORDS + 2 + i))
+        .collect::<io::Result<Vec<Fr>>>()?;
+    Ok((proof, inputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sapling_crypto::bellman::pairing::ff::Field;
+    use sapling_crypto::bellman::pairing::CurveProjective;
+
+    use util::convert::usize_to_f;
+
+    fn proof() -> Proof<Bn256> {
+        Proof {
+            a: G1Affine::one().mul(usize_to_f::<Fr>(3)).into_affine(),
+            b: G2Affine::one().mul(usize_to_f::<Fr>(5)).into_affine(),
+            c: G1Affine::one().mul(usize_to_f::<Fr>(7)).into_affine(),
+        }
+    }
+
+    #[test]
+    fn calldata_length() {
+        let inputs: Vec<Fr> = (0..5).map(usize_to_f).collect();
+        let data = encode_verify_proof(&proof(), &inputs);
+        assert_eq!(data.len(), 4 + 32 * (8 + 2 + 5));
+        assert_eq!(&data[..4], &VERIFY_PROOF_SELECTOR);
+        assert_eq!(data[4 + 32 * 9 - 1], 0x00);
+        assert_eq!(data[4 + 32 * 9 - 2], 0x01);
+        assert_eq!(data[4 + 32 * 10 - 1], 5);
+    }
+
+    #[test]
+    fn calldata_round_trip() {
+        let inputs: Vec<Fr> = vec![Fr::zero(), Fr::one(), usize_to_f(1 << 40)];
+        let data = encode_verify_proof(&proof(), &inputs);
+        let (decoded, decoded_inputs) = decode_verify_proof(&data).unwrap();
+        assert!(decoded == proof());
+        assert_eq!(decoded_inputs, inputs);
+        assert!(decode_verify_proof(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn g2_imaginary_part_first() {
+        // The G2 generator's x is 0x1800deef...ed + 0x198e9393...c2 * u.
+        let mut generator = proof();
+        generator.b = G2Affine::one();
+        let data = encode_verify_proof(&generator, &[]);
+        assert_eq!(&data[4 + 64..4 + 66], &[0x19, 0x8e]);
+        assert_eq!(&data[4 + 96..4 + 98], &[0x18, 0x00]);
+    }
+
+    #[test]
+    fn infinity_is_zero() {
+        let p = Proof::<Bn256> {
+            a: G1Affine::zero(),
+            b: G2Affine::zero(),
+            c: G1Affine::zero(),
+        };
+        let data = encode_verify_proof(&p, &[]);
+        assert!(data[4..4 + 32 * 8].iter().all(|b| *b == 0));
+        assert!(decode_verify_proof(&data).unwrap().0 == p);
+    }
+}
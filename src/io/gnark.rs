@@ -0,0 +1,346 @@
+//! Serialization of BN256 Groth16 keys, proofs, and public witnesses in gnark's binary format, so
+//! that gnark's Groth16 verifier can check proofs made here.
+//!
+//! Differences from bellman's own encoding:
+//!
+//!    * Base field elements are 32-byte big-endian in both, but gnark writes an `Fq2` element
+//!      `c0 + c1 * u` as `c1 || c0`.
+//!    * gnark keeps its metadata in the top two bits of the first byte: `00` for an uncompressed
+//!      point, `01` for the point at infinity, and `10` (resp. `11`) for a compressed point whose
+//!      `y` is the lexicographically smaller (resp. larger) root. bellman uses three flag bits
+//!      with different meanings.
+//!    * gnark's verifying key has `K`, bellman's `ic`, prefixed with its length as a big-endian
+//!      `u32`. The other points are in the order alpha (G1), beta (G1), beta (G2), gamma (G2),
+//!      delta (G1), delta (G2).
+//!    * gnark's proof is `Ar || Bs || Krs`, which are bellman's `a`, `b`, and `c`.
+//!    * A public witness is the number of public inputs, then the number of secret inputs (zero),
+//!      then the inputs as a length-prefixed vector of 32-byte big-endian scalars. The leading
+//!      `1` input is implicit in both libraries.
+
+use num_bigint::BigUint;
+use sapling_crypto::bellman::groth16::{Proof, VerifyingKey};
+use sapling_crypto::bellman::pairing::bn256::{Bn256, Fq, Fq2, Fr, G1Affine, G2Affine};
+use sapling_crypto::bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+use sapling_crypto::bellman::pairing::CurveAffine;
+
+use std::io::{self, Write};
+
+use util::convert::f_to_nat;
+
+const UNCOMPRESSED: u8 = 0b00 << 6;
+const INFINITY: u8 = 0b01 << 6;
+const COMPRESSED_SMALLEST: u8 = 0b10 << 6;
+const COMPRESSED_LARGEST: u8 = 0b11 << 6;
+
+pub const G1_COMPRESSED_LEN: usize = 32;
+pub const G1_UNCOMPRESSED_LEN: usize = 64;
+pub const G2_COMPRESSED_LEN: usize = 64;
+pub const G2_UNCOMPRESSED_LEN: usize = 128;
+
+fn fq_bytes(f: &Fq) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(32);
+    f.into_repr()
+        .write_be(&mut bytes)
+        .expect("writing to a Vec cannot fail");
+    bytes
+}
+
+fn fq2_bytes(f: &Fq2) -> Vec<u8> {
+    let mut bytes = fq_bytes(&f.c1);
+    bytes.extend(fq_bytes(&f.c0));
+    bytes
+}
+
+/// Whether `f` is greater than `-f`, as integers in `[0, q)`.
+fn fq_is_largest(f: &Fq) -> bool {
+    let mut q = Vec::new();
+    Fq::char()
+        .write_be(&mut q)
+        .expect("writing to a Vec cannot fail");
+    let q = BigUint::from_bytes_be(&q);
+    f_to_nat(f) > (q - 1usize) / 2usize
+}
+
+/// gnark orders `Fq2` by `c1`, breaking ties with `c0`.
+fn fq2_is_largest(f: &Fq2) -> bool {
+    if f_to_nat(&f.c1) == BigUint::from(0usize) {
+        fq_is_largest(&f.c0)
+    } else {
+        fq_is_largest(&f.c1)
+    }
+}
+
+pub fn g1_to_bytes(p: &G1Affine, compressed: bool) -> Vec<u8> {
+    let len = if compressed {
+        G1_COMPRESSED_LEN
+    } else {
+        G1_UNCOMPRESSED_LEN
+    };
+    if p.is_zero() {
+        let mut bytes = vec![0u8; len];
+        bytes[0] = INFINITY;
+        return bytes;
+    }
+    let (x, y) = p.into_xy_unchecked();
+    let mut bytes = fq_bytes(&x);
+    if compressed {
+        bytes[0] |= if fq_is_largest(&y) {
+            COMPRESSED_LARGEST
+        } else {
+            COMPRESSED_SMALLEST
+        };
+    } else {
+        bytes[0] |= UNCOMPRESSED;
+        bytes.extend(fq_bytes(&y));
+    }
+    bytes
+}
+
+pub fn g2_to_bytes(p: &G2Affine, compressed: bool) -> Vec<u8> {
+    let len = if compressed {
+        G2_COMPRESSED_LEN
+    } else {
+        G2_UNCOMPRESSED_LEN
+    };
+    if p.is_zero() {
+        let mut bytes = vec![0u8; len];
+        bytes[0] = INFINITY;
+        return bytes;
+    }
+    let (x, y) = p.into_xy_unchecked();
+    let mut bytes = fq2_bytes(&x);
+    if compressed {
+        bytes[0] |= if fq2_is_largest(&y) {
+            COMPRESSED_LARGEST
+        } else {
+            COMPRESSED_SMALLEST
+        };
+    } else {
+        bytes[0] |= UNCOMPRESSED;
+        bytes.extend(fq2_bytes(&y));
+    }
+    bytes
+}
+
+fn read_fq(bytes: &[u8]) -> io::Result<Fq> {
+    let mut repr = <Fq as PrimeField>::Repr::default();
+    repr.read_be(bytes)?;
+    Fq::from_repr(repr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn check_uncompressed(bytes: &[u8], len: usize) -> io::Result<bool> {
+    if bytes.len() != len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected {} bytes, not {}", len, bytes.len()),
+        ));
+    }
+    match bytes[0] & (0b11 << 6) {
+        UNCOMPRESSED => Ok(false),
+        INFINITY => Ok(true),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "only uncompressed points can be read",
+        )),
+    }
+}
+
+/// Reads an uncompressed G1 point.
+pub fn g1_from_bytes(bytes: &[u8]) -> io::Result<G1Affine> {
+    if check_uncompressed(bytes, G1_UNCOMPRESSED_LEN)? {
+        return Ok(G1Affine::zero());
+    }
+    G1Affine::from_xy_checked(read_fq(&bytes[..32])?, read_fq(&bytes[32..])?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads an uncompressed G2 point.
+pub fn g2_from_bytes(bytes: &[u8]) -> io::Result<G2Affine> {
+    if check_uncompressed(bytes, G2_UNCOMPRESSED_LEN)? {
+        return Ok(G2Affine::zero());
+    }
+    let fq2 = |b: &[u8]| -> io::Result<Fq2> {
+        Ok(Fq2 {
+            c1: read_fq(&b[..32])?,
+            c0: read_fq(&b[32..])?,
+        })
+    };
+    G2Affine::from_xy_checked(fq2(&bytes[..64])?, fq2(&bytes[64..])?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_u32<W: Write>(mut w: W, n: usize) -> io::Result<()> {
+    if n > u32::max_value() as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} does not fit in a u32", n),
+        ));
+    }
+    w.write_all(&(n as u32).to_be_bytes())
+}
+
+/// Writes `vk` as gnark's `VerifyingKey.WriteTo` (compressed) or `WriteRawTo` (uncompressed).
+pub fn write_verifying_key<W: Write>(
+    vk: &VerifyingKey<Bn256>,
+    mut w: W,
+    compressed: bool,
+) -> io::Result<()> {
+    w.write_all(&g1_to_bytes(&vk.alpha_g1, compressed))?;
+    w.write_all(&g1_to_bytes(&vk.beta_g1, compressed))?;
+    w.write_all(&g2_to_bytes(&vk.beta_g2, compressed))?;
+    w.write_all(&g2_to_bytes(&vk.gamma_g2, compressed))?;
+    w.write_all(&g1_to_bytes(&vk.delta_g1, compressed))?;
+    w.write_all(&g2_to_bytes(&vk.delta_g2, compressed))?;
+    write_u32(&mut w, vk.ic.len())?;
+    for p in &vk.ic {
+        w.write_all(&g1_to_bytes(p, compressed))?;
+    }
+    Ok(())
+}
+
+/// Writes `proof` as gnark's `Proof.WriteTo` (compressed) or `WriteRawTo` (uncompressed).
+pub fn write_proof<W: Write>(proof: &Proof<Bn256>, mut w: W, compressed: bool) -> io::Result<()> {
+    w.write_all(&g1_to_bytes(&proof.a, compressed))?;
+    w.write_all(&g2_to_bytes(&proof.b, compressed))?;
+    w.write_all(&g1_to_bytes(&proof.c, compressed))
+}
+
+/// Reads a proof written by `write_proof` without compression.
+pub fn read_proof(bytes: &[u8]) -> io::Result<Proof<Bn256>> {
+    let len = 2 * G1_UNCOMPRESSED_LEN + G2_UNCOMPRESSED_LEN;
+    if bytes.len() != len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("uncompressed proofs are {} bytes, not {}", len, bytes.len()),
+        ));
+    }
+    let (a, rest) = bytes.split_at(G1_UNCOMPRESSED_LEN);
+    let (b, c) = rest.split_at(G2_UNCOMPRESSED_LEN);
+    Ok(Proof {
+        a: g1_from_bytes(a)?,
+        b: g2_from_bytes(b)?,
+        c: g1_from_bytes(c)?,
+    })
+}
+
+/// Writes the public inputs (excluding the leading `1`) as a gnark public witness.
+pub fn write_public_witness<W: Write>(inputs: &[Fr], mut w: W) -> io::Result<()> {
+    write_u32(&mut w, inputs.len())?;
+    write_u32(&mut w, 0)?;
+    write_u32(&mut w, inputs.len())?;
+    for input in inputs {
+        input.into_repr().write_be(&mut w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sapling_crypto::bellman::pairing::ff::Field;
+    use sapling_crypto::bellman::pairing::CurveProjective;
+
+    use util::convert::usize_to_f;
+
+    fn g1(n: usize) -> G1Affine {
+        G1Affine::one().mul(usize_to_f::<Fr>(n)).into_affine()
+    }
+
+    fn g2(n: usize) -> G2Affine {
+        G2Affine::one().mul(usize_to_f::<Fr>(n)).into_affine()
+    }
+
+    #[test]
+    fn g1_generator_reference_bytes() {
+        // The BN254 G1 generator is (1, 2), and 2 is the smaller square root.
+        let mut uncompressed = vec![0u8; 64];
+        uncompressed[31] = 1;
+        uncompressed[63] = 2;
+        assert_eq!(g1_to_bytes(&G1Affine::one(), false), uncompressed);
+        let mut compressed = vec![0u8; 32];
+        compressed[0] = 0x80;
+        compressed[31] = 1;
+        assert_eq!(g1_to_bytes(&G1Affine::one(), true), compressed);
+        // -G has the larger root.
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        let neg = G1Affine::one().mul(minus_one).into_affine();
+        assert_eq!(g1_to_bytes(&neg, true)[0] & 0xc0, 0xc0);
+    }
+
+    #[test]
+    fn g2_generator_reference_bytes() {
+        // gnark writes x.A1 first; the generator's x.A1 starts with 0x19, and x.A0 with 0x18.
+        let bytes = g2_to_bytes(&G2Affine::one(), false);
+        assert_eq!(bytes.len(), G2_UNCOMPRESSED_LEN);
+        assert_eq!(bytes[0], 0x19);
+        assert_eq!(bytes[32], 0x18);
+        assert_eq!(&g2_to_bytes(&G2Affine::one(), true)[1..], &bytes[1..64]);
+    }
+
+    #[test]
+    fn infinity() {
+        assert_eq!(g1_to_bytes(&G1Affine::zero(), true)[0], 0x40);
+        assert_eq!(g2_to_bytes(&G2Affine::zero(), false)[0], 0x40);
+        let bytes = g1_to_bytes(&G1Affine::zero(), false);
+        assert_eq!(g1_from_bytes(&bytes).unwrap(), G1Affine::zero());
+    }
+
+    #[test]
+    fn points_round_trip() {
+        for n in 1..10 {
+            assert_eq!(g1_from_bytes(&g1_to_bytes(&g1(n), false)).unwrap(), g1(n));
+            assert_eq!(g2_from_bytes(&g2_to_bytes(&g2(n), false)).unwrap(), g2(n));
+        }
+    }
+
+    #[test]
+    fn verifying_key_layout() {
+        let vk = VerifyingKey::<Bn256> {
+            alpha_g1: g1(1),
+            beta_g1: g1(2),
+            beta_g2: g2(3),
+            gamma_g2: g2(4),
+            delta_g1: g1(5),
+            delta_g2: g2(6),
+            ic: vec![g1(7), g1(8), g1(9)],
+        };
+        let mut raw = Vec::new();
+        write_verifying_key(&vk, &mut raw, false).unwrap();
+        assert_eq!(raw.len(), 3 * 64 + 3 * 128 + 4 + 3 * 64);
+        assert_eq!(&raw[64 * 2 + 128 * 2..][..64], &g1_to_bytes(&g1(5), false)[..]);
+        assert_eq!(&raw[3 * 64 + 3 * 128..][..4], &[0, 0, 0, 3]);
+        let mut compressed = Vec::new();
+        write_verifying_key(&vk, &mut compressed, true).unwrap();
+        assert_eq!(compressed.len(), 3 * 32 + 3 * 64 + 4 + 3 * 32);
+    }
+
+    #[test]
+    fn proof_round_trip() {
+        let proof = Proof::<Bn256> {
+            a: g1(11),
+            b: g2(12),
+            c: g1(13),
+        };
+        let mut raw = Vec::new();
+        write_proof(&proof, &mut raw, false).unwrap();
+        assert_eq!(raw.len(), 256);
+        assert!(read_proof(&raw).unwrap() == proof);
+        let mut compressed = Vec::new();
+        write_proof(&proof, &mut compressed, true).unwrap();
+        assert_eq!(compressed.len(), 128);
+        assert!(read_proof(&compressed).is_err());
+    }
+
+    #[test]
+    fn public_witness_layout() {
+        let inputs = vec![usize_to_f::<Fr>(1), usize_to_f::<Fr>(0x0203)];
+        let mut bytes = Vec::new();
+        write_public_witness(&inputs, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 12 + 2 * 32);
+        assert_eq!(&bytes[..12], &[0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(bytes[12 + 31], 1);
+        assert_eq!(&bytes[12 + 62..], &[2, 3]);
+    }
+}
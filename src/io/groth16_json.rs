@@ -0,0 +1,843 @@
+//! JSON encodings of Groth16 parameters over BN256, in the layout used by snarkjs.
+//!
+//! Field elements are decimal strings. Points are in projective form, so a G1 point is
+//! `[x, y, "1"]` and a G2 point is `[[x.c0, x.c1], [y.c0, y.c1], ["1", "0"]]`. The point at
+//! infinity is `["0", "1", "0"]` (resp. `[["0", "0"], ["1", "0"], ["0", "0"]]`).
+
+use num_bigint::BigUint;
+use sapling_crypto::bellman::groth16::{
+    prepare_verifying_key, verify_proof, Parameters, Proof, VerifyingKey,
+};
+use sapling_crypto::bellman::pairing::bn256::{Bn256, Fq, Fq2, Fr, G1Affine, G2Affine};
+use sapling_crypto::bellman::pairing::ff::PrimeField;
+use sapling_crypto::bellman::pairing::CurveAffine;
+use serde::ser::{SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use mp::bignat::{nat_from_bytes_be, nat_to_bytes_be};
+use util::convert::{f_to_nat, nat_to_f};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProvingKeyJson {
+    #[serde(rename = "A")]
+    pub a: Vec<Vec<String>>,
+    #[serde(rename = "B1")]
+    pub b1: Vec<Vec<String>>,
+    #[serde(rename = "B2")]
+    pub b2: Vec<Vec<Vec<String>>>,
+    #[serde(rename = "C")]
+    pub c: Vec<Option<Vec<String>>>,
+    pub vk_alfa_1: Vec<String>,
+    pub vk_beta_1: Vec<String>,
+    pub vk_delta_1: Vec<String>,
+    pub vk_beta_2: Vec<Vec<String>>,
+    pub vk_delta_2: Vec<Vec<String>>,
+    #[serde(rename = "hExps")]
+    pub h: Vec<Vec<String>>,
+}
+
+/// A `ProvingKeyJson` in which each distinct point of `A`, `B1`, `C`, and `hExps` is written once,
+/// in `points_g1`, and each distinct point of `B2` once, in `points_g2`. Those lists hold indices
+/// into the tables instead. This is not a snarkjs format: `expand_proving_key` recovers one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CompactProvingKeyJson {
+    pub points_g1: Vec<Vec<String>>,
+    pub points_g2: Vec<Vec<Vec<String>>>,
+    #[serde(rename = "A")]
+    pub a: Vec<usize>,
+    #[serde(rename = "B1")]
+    pub b1: Vec<usize>,
+    #[serde(rename = "B2")]
+    pub b2: Vec<usize>,
+    #[serde(rename = "C")]
+    pub c: Vec<Option<usize>>,
+    pub vk_alfa_1: Vec<String>,
+    pub vk_beta_1: Vec<String>,
+    pub vk_delta_1: Vec<String>,
+    pub vk_beta_2: Vec<Vec<String>>,
+    pub vk_delta_2: Vec<Vec<String>>,
+    #[serde(rename = "hExps")]
+    pub h: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifyingKeyJson {
+    pub protocol: String,
+    #[serde(rename = "nPublic")]
+    pub n_public: usize,
+    #[serde(rename = "IC")]
+    pub ic: Vec<Vec<String>>,
+    pub vk_alfa_1: Vec<String>,
+    pub vk_beta_2: Vec<Vec<String>>,
+    pub vk_gamma_2: Vec<Vec<String>>,
+    pub vk_delta_2: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofJson {
+    pub protocol: String,
+    pub pi_a: Vec<String>,
+    pub pi_b: Vec<Vec<String>>,
+    pub pi_c: Vec<String>,
+}
+
+/// The decimal encoding of a field element.
+pub fn repr_to_big<F: PrimeField>(f: &F) -> String {
+    f_to_nat(f).to_string()
+}
+
+pub fn p1_to_vec(p: &G1Affine) -> Vec<String> {
+    if p.is_zero() {
+        return vec!["0".to_string(), "1".to_string(), "0".to_string()];
+    }
+    let (x, y) = p.into_xy_unchecked();
+    vec![repr_to_big(&x), repr_to_big(&y), "1".to_string()]
+}
+
+pub fn p2_to_vec(p: &G2Affine) -> Vec<Vec<String>> {
+    let fq2 = |c: &Fq2| vec![repr_to_big(&c.c0), repr_to_big(&c.c1)];
+    let pair = |a: &str, b: &str| vec![a.to_string(), b.to_string()];
+    if p.is_zero() {
+        return vec![pair("0", "0"), pair("1", "0"), pair("0", "0")];
+    }
+    let (x, y) = p.into_xy_unchecked();
+    vec![fq2(&x), fq2(&y), pair("1", "0")]
+}
+
+fn invalid<T>(msg: String) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn big_to_repr(s: &str) -> io::Result<Fq> {
+    BigUint::from_str(s)
+        .ok()
+        .and_then(|n| nat_to_f(&n))
+        .map_or_else(|| invalid(format!("{} is not a base field element", s)), Ok)
+}
+
+fn is_projective_zero(z: &[String]) -> bool {
+    z.iter().all(|c| c == "0")
+}
+
+pub fn vec_to_p1(v: &[String]) -> io::Result<G1Affine> {
+    if v.len() != 3 {
+        return invalid(format!("G1 points have 3 coordinates, not {}", v.len()));
+    }
+    if is_projective_zero(&v[2..]) {
+        return Ok(G1Affine::zero());
+    }
+    if v[2] != "1" {
+        return invalid(format!("G1 point is not normalized: z = {}", v[2]));
+    }
+    G1Affine::from_xy_checked(big_to_repr(&v[0])?, big_to_repr(&v[1])?)
+        .or_else(|e| invalid(format!("{}", e)))
+}
+
+pub fn vec_to_p2(v: &[Vec<String>]) -> io::Result<G2Affine> {
+    let fq2 = |c: &[String]| -> io::Result<Fq2> {
+        if c.len() != 2 {
+            return invalid(format!("Fq2 elements have 2 coefficients, not {}", c.len()));
+        }
+        Ok(Fq2 {
+            c0: big_to_repr(&c[0])?,
+            c1: big_to_repr(&c[1])?,
+        })
+    };
+    if v.len() != 3 {
+        return invalid(format!("G2 points have 3 coordinates, not {}", v.len()));
+    }
+    if is_projective_zero(&v[2]) {
+        return Ok(G2Affine::zero());
+    }
+    if v[2] != ["1", "0"] {
+        return invalid(format!("G2 point is not normalized: z = {:?}", v[2]));
+    }
+    G2Affine::from_xy_checked(fq2(&v[0])?, fq2(&v[1])?).or_else(|e| invalid(format!("{}", e)))
+}
+
+/// Encodes `params` as a proving key and a verifying key.
+pub fn from_parameters(params: &Parameters<Bn256>) -> (ProvingKeyJson, VerifyingKeyJson) {
+    let vk = &params.vk;
+    // snarkjs indexes `C` by wire, with no entry for the public inputs (including one).
+    let c = std::iter::repeat(None)
+        .take(vk.ic.len())
+        .chain(params.l.iter().map(|p| Some(p1_to_vec(p))))
+        .collect();
+    let pk_json = ProvingKeyJson {
+        a: params.a.iter().map(p1_to_vec).collect(),
+        b1: params.b_g1.iter().map(p1_to_vec).collect(),
+        b2: params.b_g2.iter().map(p2_to_vec).collect(),
+        c,
+        vk_alfa_1: p1_to_vec(&vk.alpha_g1),
+        vk_beta_1: p1_to_vec(&vk.beta_g1),
+        vk_delta_1: p1_to_vec(&vk.delta_g1),
+        vk_beta_2: p2_to_vec(&vk.beta_g2),
+        vk_delta_2: p2_to_vec(&vk.delta_g2),
+        h: params.h.iter().map(p1_to_vec).collect(),
+    };
+    (pk_json, verifying_key_json(vk))
+}
+
+/// Encodes a verifying key on its own.
+pub fn verifying_key_json(vk: &VerifyingKey<Bn256>) -> VerifyingKeyJson {
+    VerifyingKeyJson {
+        protocol: "groth".to_string(),
+        n_public: vk.ic.len() - 1,
+        ic: vk.ic.iter().map(p1_to_vec).collect(),
+        vk_alfa_1: p1_to_vec(&vk.alpha_g1),
+        vk_beta_2: p2_to_vec(&vk.beta_g2),
+        vk_gamma_2: p2_to_vec(&vk.gamma_g2),
+        vk_delta_2: p2_to_vec(&vk.delta_g2),
+    }
+}
+
+/// Distinct points, each with its index in `points`.
+struct PointTable<P> {
+    points: Vec<P>,
+    indices: HashMap<P, usize>,
+}
+
+impl<P: Clone + Eq + Hash> PointTable<P> {
+    fn new() -> Self {
+        Self {
+            points: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// The index of `p`, which is added if it is new.
+    fn index(&mut self, p: &P) -> usize {
+        if let Some(i) = self.indices.get(p) {
+            return *i;
+        }
+        self.points.push(p.clone());
+        self.indices.insert(p.clone(), self.points.len() - 1);
+        self.points.len() - 1
+    }
+}
+
+/// Writes each distinct point of `pk_json` once. See `CompactProvingKeyJson`.
+pub fn compact_proving_key(pk_json: &ProvingKeyJson) -> CompactProvingKeyJson {
+    let mut g1 = PointTable::new();
+    let mut g2 = PointTable::new();
+    let a = pk_json.a.iter().map(|p| g1.index(p)).collect();
+    let b1 = pk_json.b1.iter().map(|p| g1.index(p)).collect();
+    let b2 = pk_json.b2.iter().map(|p| g2.index(p)).collect();
+    let c = pk_json
+        .c
+        .iter()
+        .map(|p| p.as_ref().map(|p| g1.index(p)))
+        .collect();
+    let h = pk_json.h.iter().map(|p| g1.index(p)).collect();
+    CompactProvingKeyJson {
+        points_g1: g1.points,
+        points_g2: g2.points,
+        a,
+        b1,
+        b2,
+        c,
+        vk_alfa_1: pk_json.vk_alfa_1.clone(),
+        vk_beta_1: pk_json.vk_beta_1.clone(),
+        vk_delta_1: pk_json.vk_delta_1.clone(),
+        vk_beta_2: pk_json.vk_beta_2.clone(),
+        vk_delta_2: pk_json.vk_delta_2.clone(),
+        h,
+    }
+}
+
+/// Undoes `compact_proving_key`. An index past the end of its table is an error.
+pub fn expand_proving_key(compact: &CompactProvingKeyJson) -> io::Result<ProvingKeyJson> {
+    fn point<P: Clone>(points: &[P], i: usize, name: &str) -> io::Result<P> {
+        match points.get(i) {
+            Some(p) => Ok(p.clone()),
+            None => invalid(format!("{} refers to point {} of {}", name, i, points.len())),
+        }
+    }
+    let g1s = |indices: &[usize], name: &str| {
+        indices
+            .iter()
+            .map(|i| point(&compact.points_g1, *i, name))
+            .collect::<io::Result<Vec<_>>>()
+    };
+    Ok(ProvingKeyJson {
+        a: g1s(&compact.a, "A")?,
+        b1: g1s(&compact.b1, "B1")?,
+        b2: compact
+            .b2
+            .iter()
+            .map(|i| point(&compact.points_g2, *i, "B2"))
+            .collect::<io::Result<_>>()?,
+        c: compact
+            .c
+            .iter()
+            .map(|i| i.map(|i| point(&compact.points_g1, i, "C")).transpose())
+            .collect::<io::Result<_>>()?,
+        vk_alfa_1: compact.vk_alfa_1.clone(),
+        vk_beta_1: compact.vk_beta_1.clone(),
+        vk_delta_1: compact.vk_delta_1.clone(),
+        vk_beta_2: compact.vk_beta_2.clone(),
+        vk_delta_2: compact.vk_delta_2.clone(),
+        h: g1s(&compact.h, "hExps")?,
+    })
+}
+
+/// Serializes G1 points with `p1_to_vec`, one at a time.
+struct G1Seq<'a>(&'a [G1Affine]);
+
+impl<'a> Serialize for G1Seq<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(p1_to_vec))
+    }
+}
+
+/// Serializes G2 points with `p2_to_vec`, one at a time.
+struct G2Seq<'a>(&'a [G2Affine]);
+
+impl<'a> Serialize for G2Seq<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(p2_to_vec))
+    }
+}
+
+/// The `C` entries of a proving key, as in `from_parameters`.
+struct CSeq<'a>(&'a Parameters<Bn256>);
+
+impl<'a> Serialize for CSeq<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            std::iter::repeat(None)
+                .take(self.0.vk.ic.len())
+                .chain(self.0.l.iter().map(|p| Some(p1_to_vec(p)))),
+        )
+    }
+}
+
+/// Serializes like the `ProvingKeyJson` of `from_parameters`, but encodes each point as it is
+/// written, so the whole key is never held in memory as strings.
+struct ProvingKeyJsonRef<'a>(&'a Parameters<Bn256>);
+
+impl<'a> Serialize for ProvingKeyJsonRef<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let params = self.0;
+        let vk = &params.vk;
+        let mut s = serializer.serialize_struct("ProvingKeyJson", 10)?;
+        s.serialize_field("A", &G1Seq(&params.a))?;
+        s.serialize_field("B1", &G1Seq(&params.b_g1))?;
+        s.serialize_field("B2", &G2Seq(&params.b_g2))?;
+        s.serialize_field("C", &CSeq(params))?;
+        s.serialize_field("vk_alfa_1", &p1_to_vec(&vk.alpha_g1))?;
+        s.serialize_field("vk_beta_1", &p1_to_vec(&vk.beta_g1))?;
+        s.serialize_field("vk_delta_1", &p1_to_vec(&vk.delta_g1))?;
+        s.serialize_field("vk_beta_2", &p2_to_vec(&vk.beta_g2))?;
+        s.serialize_field("vk_delta_2", &p2_to_vec(&vk.delta_g2))?;
+        s.serialize_field("hExps", &G1Seq(&params.h))?;
+        s.end()
+    }
+}
+
+/// Writes the proving key of `params` as the JSON of `from_parameters(params).0`, streaming it
+/// to `writer` instead of building the encoding in memory first.
+pub fn write_proving_key_json<W: io::Write>(
+    params: &Parameters<Bn256>,
+    writer: W,
+) -> io::Result<()> {
+    serde_json::to_writer(writer, &ProvingKeyJsonRef(params)).map_err(io::Error::from)
+}
+
+/// Writes `vk` as the pretty-printed JSON of `verifying_key_json(vk)`, and flushes `writer`, so
+/// that a failed write is reported rather than lost when a buffered writer is dropped.
+pub fn write_verifying_key_json<W: io::Write>(
+    vk: &VerifyingKey<Bn256>,
+    mut writer: W,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, &verifying_key_json(vk)).map_err(io::Error::from)?;
+    writer.flush()
+}
+
+/// A failure to write keys with `export_keys`.
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// An output file already exists. It is left as it was.
+    FileExists(PathBuf),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "{}", e),
+            ExportError::Json(e) => write!(f, "{}", e),
+            ExportError::FileExists(path) => write!(f, "{} already exists", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        ExportError::Json(e)
+    }
+}
+
+/// Writes the proving key of `params` to `pk_path` and its verifying key to `vk_path`, both as
+/// JSON. Neither file may exist already: both are created before either is written, so if one
+/// exists, neither is written.
+pub fn export_keys(
+    params: &Parameters<Bn256>,
+    pk_path: &Path,
+    vk_path: &Path,
+) -> Result<(), ExportError> {
+    let create = |path: &Path| {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => ExportError::FileExists(path.to_path_buf()),
+                _ => ExportError::Io(e),
+            })
+    };
+    let pk_file = create(pk_path)?;
+    let vk_file = create(vk_path).map_err(|e| {
+        // The proving key file is new and empty.
+        let _ = fs::remove_file(pk_path);
+        e
+    })?;
+    let mut pk_writer = io::BufWriter::new(pk_file);
+    serde_json::to_writer(&mut pk_writer, &ProvingKeyJsonRef(params))?;
+    pk_writer.flush()?;
+    write_verifying_key_json(&params.vk, io::BufWriter::new(vk_file))?;
+    Ok(())
+}
+
+/// Decodes a verifying key on its own.
+/// The snarkjs verifying key has no `beta_g1` or `delta_g1`, so they are left as the identity.
+/// Verification (`prepare_verifying_key`) does not use them.
+pub fn vk_from_json(vk_json: &VerifyingKeyJson) -> io::Result<VerifyingKey<Bn256>> {
+    if vk_json.ic.len() != vk_json.n_public + 1 {
+        return invalid(format!(
+            "{} public inputs need {} IC points, not {}",
+            vk_json.n_public,
+            vk_json.n_public + 1,
+            vk_json.ic.len()
+        ));
+    }
+    Ok(VerifyingKey {
+        alpha_g1: vec_to_p1(&vk_json.vk_alfa_1)?,
+        beta_g1: G1Affine::zero(),
+        beta_g2: vec_to_p2(&vk_json.vk_beta_2)?,
+        gamma_g2: vec_to_p2(&vk_json.vk_gamma_2)?,
+        delta_g1: G1Affine::zero(),
+        delta_g2: vec_to_p2(&vk_json.vk_delta_2)?,
+        ic: vk_json
+            .ic
+            .iter()
+            .map(|p| vec_to_p1(p))
+            .collect::<io::Result<_>>()?,
+    })
+}
+
+/// Decodes the verifying key half of a parameter pair.
+pub fn to_verifying_key(
+    pk_json: &ProvingKeyJson,
+    vk_json: &VerifyingKeyJson,
+) -> io::Result<VerifyingKey<Bn256>> {
+    let mut vk = vk_from_json(vk_json)?;
+    vk.beta_g1 = vec_to_p1(&pk_json.vk_beta_1)?;
+    vk.delta_g1 = vec_to_p1(&pk_json.vk_delta_1)?;
+    Ok(vk)
+}
+
+pub fn from_proof(proof: &Proof<Bn256>) -> ProofJson {
+    ProofJson {
+        protocol: "groth".to_string(),
+        pi_a: p1_to_vec(&proof.a),
+        pi_b: p2_to_vec(&proof.b),
+        pi_c: p1_to_vec(&proof.c),
+    }
+}
+
+pub fn to_proof(proof_json: &ProofJson) -> io::Result<Proof<Bn256>> {
+    Ok(Proof {
+        a: vec_to_p1(&proof_json.pi_a)?,
+        b: vec_to_p2(&proof_json.pi_b)?,
+        c: vec_to_p1(&proof_json.pi_c)?,
+    })
+}
+
+/// Reads a verifying key that is either `VerifyingKeyJson` or bellman's binary encoding
+/// (`VerifyingKey::write`). JSON keys are recognized by their first byte, which must be `{`. A
+/// binary key may begin with a whitespace byte, so none is skipped.
+pub fn read_verifying_key(bytes: &[u8]) -> io::Result<VerifyingKey<Bn256>> {
+    match bytes.first() {
+        Some(b'{') => {
+            let vk_json: VerifyingKeyJson = serde_json::from_slice(bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            vk_from_json(&vk_json)
+        }
+        _ => VerifyingKey::read(bytes),
+    }
+}
+
+/// Checks a JSON proof and decimal public inputs against a decoded verifying key.
+/// A malformed proof or input, or the wrong number of inputs, is an error.
+pub fn verify_json_proof(
+    vk: &VerifyingKey<Bn256>,
+    proof_json: &ProofJson,
+    public_json: &[String],
+) -> io::Result<bool> {
+    let proof = to_proof(proof_json)?;
+    let public = to_public_inputs::<Fr>(public_json)?;
+    if public.len() + 1 != vk.ic.len() {
+        return invalid(format!(
+            "the key takes {} public inputs, not {}",
+            vk.ic.len() - 1,
+            public.len()
+        ));
+    }
+    verify_proof(&prepare_verifying_key(vk), &proof, &public)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))
+}
+
+/// Checks a proof against a verifying key and decimal public inputs, all in their JSON forms.
+/// A malformed key, proof, or input, or the wrong number of inputs, is an error.
+pub fn verify_json(
+    vk_json: &VerifyingKeyJson,
+    proof_json: &ProofJson,
+    public_json: &[String],
+) -> io::Result<bool> {
+    verify_json_proof(&vk_from_json(vk_json)?, proof_json, public_json)
+}
+
+/// Encodes public inputs as decimal strings.
+pub fn from_public_inputs<F: PrimeField>(inputs: &[F]) -> Vec<String> {
+    inputs.iter().map(repr_to_big).collect()
+}
+
+/// Decodes public inputs encoded as decimal strings.
+pub fn to_public_inputs<F: PrimeField>(inputs: &[String]) -> io::Result<Vec<F>> {
+    inputs
+        .iter()
+        .map(|s| {
+            BigUint::from_str(s)
+                .ok()
+                .and_then(|n| nat_to_f(&n))
+                .map_or_else(|| invalid(format!("{} is not a scalar field element", s)), Ok)
+        })
+        .collect()
+}
+
+/// Encodes public inputs as `0x`-prefixed, 32-byte big-endian hex strings.
+pub fn from_public_inputs_hex<F: PrimeField>(inputs: &[F]) -> Vec<String> {
+    inputs
+        .iter()
+        .map(|f| {
+            let bytes =
+                nat_to_bytes_be(&f_to_nat(f), 256).expect("field elements fit in 32 bytes");
+            format!("0x{}", hex::encode(bytes))
+        })
+        .collect()
+}
+
+/// Decodes public inputs encoded by `from_public_inputs_hex`.
+pub fn to_public_inputs_hex<F: PrimeField>(inputs: &[String]) -> io::Result<Vec<F>> {
+    inputs
+        .iter()
+        .map(|s| {
+            let digits = if s.starts_with("0x") { &s[2..] } else { &s[..] };
+            if digits.len() != 64 {
+                return invalid(format!("{} is not 32 bytes of hex", s));
+            }
+            hex::decode(digits)
+                .ok()
+                .and_then(|bytes| nat_to_f(&nat_from_bytes_be(&bytes)))
+                .map_or_else(|| invalid(format!("{} is not a scalar field element", s)), Ok)
+        })
+        .collect()
+}
+
+/// Decodes the parameters encoded by `from_parameters`.
+pub fn to_parameters(
+    pk_json: &ProvingKeyJson,
+    vk_json: &VerifyingKeyJson,
+) -> io::Result<Parameters<Bn256>> {
+    let vk = to_verifying_key(pk_json, vk_json)?;
+    let n_public = vk.ic.len();
+    if pk_json.c.len() < n_public || pk_json.c[..n_public].iter().any(Option::is_some) {
+        return invalid(format!(
+            "C must start with {} nulls for the public inputs",
+            n_public
+        ));
+    }
+    let l = pk_json.c[n_public..]
+        .iter()
+        .map(|p| match p {
+            Some(p) => vec_to_p1(p),
+            None => invalid("C is null at a private wire".to_string()),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let g1s = |ps: &[Vec<String>]| {
+        ps.iter()
+            .map(|p| vec_to_p1(p))
+            .collect::<io::Result<Vec<_>>>()
+    };
+    Ok(Parameters {
+        vk,
+        h: Arc::new(g1s(&pk_json.h)?),
+        l: Arc::new(l),
+        a: Arc::new(g1s(&pk_json.a)?),
+        b_g1: Arc::new(g1s(&pk_json.b1)?),
+        b_g2: Arc::new(
+            pk_json
+                .b2
+                .iter()
+                .map(|p| vec_to_p2(p))
+                .collect::<io::Result<Vec<_>>>()?,
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::thread_rng;
+    use sapling_crypto::bellman::groth16::generate_random_parameters;
+
+    use io::small_set;
+    use util::test_helpers::*;
+
+    fn params() -> Parameters<Bn256> {
+        generate_random_parameters::<Bn256, _, _>(Square, &mut thread_rng()).unwrap()
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let params = params();
+        let (pk_json, vk_json) = from_parameters(&params);
+        assert_eq!(vk_json.n_public, 1);
+        assert_eq!(pk_json.c.len(), 2 + params.l.len());
+        let decoded = to_parameters(&pk_json, &vk_json).unwrap();
+        assert!(decoded == params);
+        assert_eq!(from_parameters(&decoded), (pk_json, vk_json));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let (pk_json, vk_json) = from_parameters(&params());
+        let pk_text = serde_json::to_string(&pk_json).unwrap();
+        let vk_text = serde_json::to_string(&vk_json).unwrap();
+        assert!(vk_text.starts_with("{\"protocol\":\"groth\",\"nPublic\":1,\"IC\":[["));
+        assert!(pk_text.starts_with("{\"A\":[["));
+        assert_eq!(serde_json::from_str::<ProvingKeyJson>(&pk_text).unwrap(), pk_json);
+        assert_eq!(serde_json::from_str::<VerifyingKeyJson>(&vk_text).unwrap(), vk_json);
+    }
+
+    #[test]
+    fn points_round_trip() {
+        let vk = params().vk;
+        assert_eq!(vec_to_p1(&p1_to_vec(&vk.alpha_g1)).unwrap(), vk.alpha_g1);
+        assert_eq!(vec_to_p2(&p2_to_vec(&vk.beta_g2)).unwrap(), vk.beta_g2);
+        assert_eq!(vec_to_p1(&p1_to_vec(&G1Affine::zero())).unwrap(), G1Affine::zero());
+        assert_eq!(vec_to_p2(&p2_to_vec(&G2Affine::zero())).unwrap(), G2Affine::zero());
+    }
+
+    #[test]
+    fn verifying_key_without_proving_key() {
+        let (pk_json, vk_json) = from_parameters(&params());
+        let full = to_verifying_key(&pk_json, &vk_json).unwrap();
+        let partial = vk_from_json(&vk_json).unwrap();
+        assert_eq!(partial.alpha_g1, full.alpha_g1);
+        assert_eq!(partial.delta_g2, full.delta_g2);
+        assert_eq!(partial.ic, full.ic);
+    }
+
+    #[test]
+    fn public_inputs_round_trip() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        let inputs = vec![Fr::zero(), Fr::one(), nat_to_f(&BigUint::from(12345usize)).unwrap()];
+        let encoded = from_public_inputs(&inputs);
+        assert_eq!(encoded, vec!["0", "1", "12345"]);
+        assert_eq!(to_public_inputs::<Fr>(&encoded).unwrap(), inputs);
+    }
+
+    #[test]
+    fn public_inputs_hex_match_decimal() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        let mut set = small_set::small_circuit(1, Some(&small_set::SwapJson {
+            untouched: vec![],
+            removed: vec![["0", "1", "2", "3", "4"].iter().map(|s| s.to_string()).collect()],
+            inserted: vec![["0", "1", "2", "3", "5"].iter().map(|s| s.to_string()).collect()],
+        }));
+        let inputs = set.public_inputs().unwrap();
+        let decimal = from_public_inputs(&inputs);
+        let hex = from_public_inputs_hex(&inputs);
+        assert_eq!(hex.len(), decimal.len());
+        assert!(hex.iter().all(|h| h.len() == 66 && h.starts_with("0x")));
+        // The generator's low limb is 2.
+        assert_eq!(hex[0], format!("0x{:064x}", 2));
+        assert_eq!(
+            to_public_inputs_hex::<Fr>(&hex).unwrap(),
+            to_public_inputs::<Fr>(&decimal).unwrap()
+        );
+        assert!(to_public_inputs_hex::<Fr>(&["0x02".to_string()]).is_err());
+        assert!(to_public_inputs_hex::<Fr>(&[format!("0x{}", "f".repeat(64))]).is_err());
+    }
+
+    #[test]
+    fn prove_and_verify_json() {
+        use sapling_crypto::bellman::groth16::create_random_proof;
+
+        let params = params();
+        let vk_text = serde_json::to_string(&from_parameters(&params).1).unwrap();
+        let proof = create_random_proof(Square, &params, &mut thread_rng()).unwrap();
+        let proof_text = serde_json::to_string(&from_proof(&proof)).unwrap();
+        let vk_json: VerifyingKeyJson = serde_json::from_str(&vk_text).unwrap();
+        let proof_json: ProofJson = serde_json::from_str(&proof_text).unwrap();
+        let public = vec!["1".to_string()];
+        assert!(verify_json(&vk_json, &proof_json, &public).unwrap());
+        assert!(!verify_json(&vk_json, &proof_json, &["2".to_string()]).unwrap());
+        assert!(verify_json(&vk_json, &proof_json, &[]).is_err());
+        assert!(verify_json(&vk_json, &proof_json, &["x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn binary_keys_round_trip() {
+        use sapling_crypto::bellman::groth16::create_random_proof;
+
+        let params = params();
+        let mut params_bytes = Vec::new();
+        params.write(&mut params_bytes).unwrap();
+        let mut vk_bytes = Vec::new();
+        params.vk.write(&mut vk_bytes).unwrap();
+        let vk_text = serde_json::to_string_pretty(&from_parameters(&params).1).unwrap();
+
+        let read_params = Parameters::<Bn256>::read(&params_bytes[..], true).unwrap();
+        assert!(read_params == params);
+        let binary_vk = read_verifying_key(&vk_bytes).unwrap();
+        assert!(binary_vk == params.vk);
+        let json_vk = read_verifying_key(vk_text.as_bytes()).unwrap();
+        assert_eq!(json_vk.ic, params.vk.ic);
+        // Only the first byte decides the format, so this is decoded as a binary key.
+        assert!(read_verifying_key(format!(" {}", vk_text).as_bytes()).is_err());
+
+        let proof = create_random_proof(Square, &read_params, &mut thread_rng()).unwrap();
+        let proof_json = from_proof(&proof);
+        let public = vec!["1".to_string()];
+        assert!(verify_json_proof(&binary_vk, &proof_json, &public).unwrap());
+        assert!(verify_json_proof(&json_vk, &proof_json, &public).unwrap());
+        assert!(read_verifying_key(&vk_bytes[..vk_bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn streamed_proving_key() {
+        let params =
+            generate_random_parameters(small_set::small_circuit(1, None), &mut thread_rng())
+                .unwrap();
+        let path = std::env::temp_dir().join(format!("pk-{}.json", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        write_proving_key_json(&params, io::BufWriter::new(file)).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (pk_json, vk_json) = from_parameters(&params);
+        assert_eq!(text, serde_json::to_string(&pk_json).unwrap());
+        let streamed: ProvingKeyJson = serde_json::from_str(&text).unwrap();
+        assert!(to_parameters(&streamed, &vk_json).unwrap() == params);
+    }
+
+    #[test]
+    fn buffered_verifying_key_round_trip() {
+        let params = params();
+        let path = std::env::temp_dir().join(format!("vk-{}.json", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        write_verifying_key_json(&params.vk, io::BufWriter::new(file)).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let vk_json: VerifyingKeyJson = serde_json::from_str(&text).unwrap();
+        assert_eq!(vk_json, verifying_key_json(&params.vk));
+        let vk = vk_from_json(&vk_json).unwrap();
+        assert_eq!(vk.alpha_g1, params.vk.alpha_g1);
+        assert_eq!(vk.ic, params.vk.ic);
+    }
+
+    #[test]
+    fn exported_keys_are_not_overwritten() {
+        let params = params();
+        let dir = std::env::temp_dir();
+        let pk_path = dir.join(format!("export-pk-{}.json", std::process::id()));
+        let vk_path = dir.join(format!("export-vk-{}.json", std::process::id()));
+
+        export_keys(&params, &pk_path, &vk_path).unwrap();
+        let pk_json: ProvingKeyJson =
+            serde_json::from_str(&std::fs::read_to_string(&pk_path).unwrap()).unwrap();
+        let vk_json: VerifyingKeyJson =
+            serde_json::from_str(&std::fs::read_to_string(&vk_path).unwrap()).unwrap();
+        assert!(to_parameters(&pk_json, &vk_json).unwrap() == params);
+
+        match export_keys(&params, &pk_path, &vk_path) {
+            Err(ExportError::FileExists(path)) => assert_eq!(path, pk_path),
+            r => panic!("expected FileExists, got {:?}", r),
+        }
+        // An existing verifying key stops the proving key from being written too.
+        std::fs::remove_file(&pk_path).unwrap();
+        match export_keys(&params, &pk_path, &vk_path) {
+            Err(ExportError::FileExists(path)) => assert_eq!(path, vk_path),
+            r => panic!("expected FileExists, got {:?}", r),
+        }
+        assert!(!pk_path.exists());
+        std::fs::remove_file(&vk_path).unwrap();
+    }
+
+    #[test]
+    fn compact_proving_key_round_trip() {
+        let params = params();
+        let (pk_json, vk_json) = from_parameters(&params);
+        let text = serde_json::to_string(&compact_proving_key(&pk_json)).unwrap();
+        let compact: CompactProvingKeyJson = serde_json::from_str(&text).unwrap();
+        assert_eq!(expand_proving_key(&compact).unwrap(), pk_json);
+        let decoded = to_parameters(&expand_proving_key(&compact).unwrap(), &vk_json).unwrap();
+        let mut bytes = Vec::new();
+        params.write(&mut bytes).unwrap();
+        let mut decoded_bytes = Vec::new();
+        decoded.write(&mut decoded_bytes).unwrap();
+        assert_eq!(decoded_bytes, bytes);
+
+        // Repeated points are written once.
+        let mut repeated = pk_json.clone();
+        repeated.b2 = vec![p2_to_vec(&G2Affine::zero()); 10];
+        repeated.h.extend(repeated.a.clone());
+        let compact = compact_proving_key(&repeated);
+        assert_eq!(compact.points_g2.len(), 1);
+        assert_eq!(compact.b2, vec![0; 10]);
+        assert_eq!(&compact.h[pk_json.h.len()..], &compact.a[..]);
+        assert_eq!(expand_proving_key(&compact).unwrap(), repeated);
+
+        let mut dangling = compact.clone();
+        dangling.b2.push(1);
+        assert!(expand_proving_key(&dangling).is_err());
+    }
+
+    #[test]
+    fn off_curve_point() {
+        let mut v = p1_to_vec(&params().vk.alpha_g1);
+        v[1] = "1".to_string();
+        assert!(vec_to_p1(&v).is_err());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod ethereum;
+pub mod gnark;
+pub mod groth16_json;
+pub mod small_set;
@@ -0,0 +1,124 @@
+//! The small set-swap configuration used by the WASM and C bindings: an RSA-512 accumulator with
+//! 32-bit limbs, 128-bit elements and challenges, and items of `ITEM_SIZE` field elements, hashed
+//! with Poseidon over BN256.
+
+use num_bigint::BigUint;
+use rand::thread_rng;
+use sapling_crypto::bellman::groth16::{create_random_proof, Parameters, Proof};
+use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+use sapling_crypto::bellman::pairing::ff::PrimeField;
+use serde::{Deserialize, Serialize};
+
+use std::fmt::Display;
+use std::iter;
+use std::str::FromStr;
+
+use group::RsaQuotientGroup;
+use hash::division_intractable as di;
+use hash::hashes::Poseidon;
+use set::int_set::NaiveExpSet;
+use set::rsa::{check_distinct, ChallengeSource, Set, SetBench, SetBenchInputs, SetBenchParams};
+
+// From my machine (openssl)
+pub const RSA_512: &str = "11834783464130424096695514462778870280264989938857328737807205623069291535525952722847913694296392927890261736769191982212777933726583565708193466779811767";
+pub const ITEM_SIZE: usize = 5;
+
+/// The items of a swap, as decimal field elements.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwapJson {
+    pub untouched: Vec<Vec<String>>,
+    pub removed: Vec<Vec<String>>,
+    pub inserted: Vec<Vec<String>>,
+}
+
+pub type SmallSet = Set<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>>;
+pub type SmallSetBench = SetBench<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>>;
+
+/// The RSA-512 group of the small configuration, generated by 2.
+pub fn small_group() -> RsaQuotientGroup {
+    RsaQuotientGroup {
+        g: BigUint::from(2usize),
+        m: BigUint::from_str(RSA_512).unwrap(),
+    }
+}
+
+/// An empty set in `group`, which hashes items as the small circuit does.
+pub fn empty_set(group: RsaQuotientGroup) -> SmallSet {
+    Set::new_with(group, di::offset(128), Poseidon::default(), 128, 1, 32, iter::empty())
+}
+
+/// The small circuit for `n_swaps` swaps. Parameters must be generated for the same `n_swaps`
+/// that proofs are later made for.
+///
+/// Panics if `swap` does not pass `parse_swap`.
+pub fn small_circuit(n_swaps: usize, swap: Option<&SwapJson>) -> SmallSetBench {
+    let params = SetBenchParams {
+        group: small_group(),
+        limb_width: 32,
+        n_bits_elem: 128,
+        n_trailing_ones: 1,
+        n_bits_challenge: 128,
+        challenge_source: ChallengeSource::FiatShamir,
+        n_bits_base: 512,
+        item_size: ITEM_SIZE,
+        n_inserts: n_swaps,
+        n_removes: n_swaps,
+        window_bits: None,
+        unique_inserts: false,
+        optional_slots: false,
+        hasher: Poseidon::default(),
+        verbose: false,
+    };
+    SetBench {
+        inputs: swap.map(|s| {
+            SetBenchInputs::new(
+                s.untouched.clone(),
+                s.removed.clone(),
+                s.inserted.clone(),
+                &params,
+            )
+            .expect("a swap checked by `parse_swap`")
+        }),
+        params,
+    }
+}
+
+pub fn err<E: Display>(e: E) -> String {
+    format!("{}", e)
+}
+
+pub fn parse_swap(inputs_json: &str) -> Result<SwapJson, String> {
+    let swap: SwapJson = serde_json::from_str(inputs_json).map_err(err)?;
+    if swap.removed.len() != swap.inserted.len() {
+        return Err(format!(
+            "{} removals but {} insertions",
+            swap.removed.len(),
+            swap.inserted.len()
+        ));
+    }
+    let parse = |items: &[Vec<String>]| -> Result<Vec<Vec<Fr>>, String> {
+        items.iter().map(|item| parse_item(item)).collect()
+    };
+    parse(&swap.untouched)?;
+    // `small_circuit` rejects an item removed or inserted twice, as `SetBenchInputs::new` does.
+    check_distinct(&parse(&swap.removed)?, &swap.removed).map_err(err)?;
+    check_distinct(&parse(&swap.inserted)?, &swap.inserted).map_err(err)?;
+    Ok(swap)
+}
+
+/// Parses an item of `ITEM_SIZE` decimal field elements.
+pub fn parse_item(item: &[String]) -> Result<Vec<Fr>, String> {
+    if item.len() != ITEM_SIZE {
+        return Err(format!("items have {} elements, not {}", item.len(), ITEM_SIZE));
+    }
+    item.iter()
+        .map(|e| Fr::from_str(e))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("{:?} is not a list of field elements", item))
+}
+
+/// Proves `swap` with `params`, which must have been generated for `swap.removed.len()` swaps.
+pub fn prove(params: &Parameters<Bn256>, swap: &SwapJson) -> Result<Proof<Bn256>, String> {
+    let circuit = small_circuit(swap.removed.len(), Some(swap));
+    create_random_proof(circuit, params, &mut thread_rng()).map_err(err)
+}
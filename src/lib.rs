@@ -1,15 +1,28 @@
 #![feature(hash_raw_entry)]
 
 extern crate fnv;
+extern crate hex;
 extern crate num_iter;
 extern crate num_bigint;
 extern crate num_integer;
 extern crate num_traits;
 extern crate rand;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 extern crate sapling_crypto;
+extern crate serde;
+extern crate serde_json;
 #[macro_use]
 extern crate derivative;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
 extern crate sha2;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(all(test, feature = "wasm", target_arch = "wasm32"))]
+extern crate wasm_bindgen_test;
 
 #[cfg(test)]
 extern crate quickcheck;
@@ -20,11 +33,15 @@ extern crate quickcheck_macros;
 
 #[macro_use]
 pub mod util;
+pub mod ffi;
 pub mod mp;
 pub mod group;
 pub mod hash;
+pub mod io;
 pub mod rollup;
 pub mod set;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod wesolowski;
 
 use sapling_crypto::bellman::SynthesisError;
@@ -1,12 +1,15 @@
 use num_bigint::{BigUint, ToBigInt};
 use num_integer::Integer;
-use num_traits::{One, Pow, ToPrimitive};
+use num_traits::{One, Pow, ToPrimitive, Zero};
 use sapling_crypto::bellman::pairing::ff::{Field, PrimeField};
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{ConstraintSystem, LinearCombination, SynthesisError};
 use sapling_crypto::circuit::boolean::Boolean;
+use sapling_crypto::circuit::num::AllocatedNum;
 
 use std::borrow::Borrow;
+#[cfg(test)]
+use std::cell::Cell;
 use std::cmp::{max, min, Ordering};
 use std::convert::From;
 use std::fmt::{self, Debug, Display, Formatter};
@@ -15,9 +18,10 @@ use std::rc::Rc;
 use super::exp::optimal_k;
 use super::poly::Polynomial;
 use util::bit::{Bit, Bitvector};
-use util::convert::{f_to_nat, nat_to_f};
+use util::convert::{f_to_nat, nat_to_f, usize_to_f};
 use util::gadget::Gadget;
 use util::num::Num;
+use util::profiling::{count_modpow, count_mult_mod};
 use util::lazy::LazyCell;
 use OptionExt;
 
@@ -33,27 +37,119 @@ pub fn limbs_to_nat<F: PrimeField, B: Borrow<F>, I: Iterator<Item = B>>(
         .sum()
 }
 
+/// Compute the natural number represented by an array of limbs, checking that each limb is
+/// below `2^limb_width`. This is the inverse of `nat_to_limbs`.
+pub fn limbs_to_nat_checked<F: PrimeField>(
+    limbs: &[F],
+    limb_width: usize,
+) -> Result<BigUint, LimbError> {
+    for (index, limb) in limbs.iter().enumerate() {
+        let have = f_to_nat(limb).bits();
+        if have > limb_width {
+            return Err(LimbError::LimbTooWide {
+                index,
+                have,
+                max: limb_width,
+            });
+        }
+    }
+    Ok(limbs_to_nat::<F, _, _>(limbs.iter(), limb_width))
+}
+
+/// An error encoding or decoding a natural number as limbs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimbError {
+    /// The number has `have` bits, but the limbs hold at most `max`.
+    TooManyBits { have: usize, max: usize },
+    /// The limb at `index` has `have` bits, but limbs hold at most `max`.
+    LimbTooWide { index: usize, have: usize, max: usize },
+}
+
+impl Display for LimbError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LimbError::TooManyBits { have, max } => {
+                write!(f, "nat has {} bits, but the limbs hold at most {}", have, max)
+            }
+            LimbError::LimbTooWide { index, have, max } => write!(
+                f,
+                "limb {} has {} bits, but limbs hold at most {}",
+                index, have, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimbError {}
+
+impl From<LimbError> for SynthesisError {
+    fn from(e: LimbError) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
 /// Compute the limbs encoding a natural number.
 /// The limbs are assumed to be based the `limb_width` power of 2.
 pub fn nat_to_limbs<'a, F: PrimeField>(
     nat: &BigUint,
     limb_width: usize,
     n_limbs: usize,
-) -> Result<Vec<F>, SynthesisError> {
+) -> Result<Vec<F>, LimbError> {
     let mask = (BigUint::from(1usize) << limb_width) - 1usize;
     if nat.bits() <= n_limbs * limb_width {
         Ok((0..n_limbs)
             .map(|limb_i| nat_to_f(&(&mask & (nat >> (limb_i * limb_width)))).unwrap())
             .collect())
     } else {
-        eprintln!(
-            "nat {} does not fit in {} limbs of width {}",
-            nat, n_limbs, limb_width
-        );
+        Err(LimbError::TooManyBits {
+            have: nat.bits(),
+            max: n_limbs * limb_width,
+        })
+    }
+}
+
+/// Compute the big-endian, fixed-width byte encoding of a natural number.
+/// The width is `n_bits / 8` bytes, and the encoding is left-padded with zeros.
+pub fn nat_to_bytes_be(nat: &BigUint, n_bits: usize) -> Result<Vec<u8>, SynthesisError> {
+    let n_bytes = n_bits / 8;
+    if nat.bits() <= n_bytes * 8 {
+        let raw = nat.to_bytes_be();
+        let mut bytes = vec![0u8; n_bytes];
+        // `to_bytes_be` returns `[0]` for zero, which may be longer than a zero-width encoding.
+        let raw = if raw.len() > n_bytes { &raw[raw.len() - n_bytes..] } else { &raw[..] };
+        bytes[n_bytes - raw.len()..].copy_from_slice(raw);
+        Ok(bytes)
+    } else {
+        eprintln!("nat {} does not fit in {} bytes", nat, n_bytes);
         Err(SynthesisError::Unsatisfiable)
     }
 }
 
+/// Compute the natural number encoded by big-endian bytes.
+pub fn nat_from_bytes_be(bytes: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(bytes)
+}
+
+/// Compute the big-endian byte encoding of an array of limbs.
+/// The encoding is `ceil(n_limbs * limb_width / 8)` bytes wide, so a partial top byte is kept.
+pub fn limbs_to_bytes<F: PrimeField>(
+    limbs: &[F],
+    limb_width: usize,
+) -> Result<Vec<u8>, SynthesisError> {
+    let n_bits = (limbs.len() * limb_width + 7) / 8 * 8;
+    nat_to_bytes_be(&limbs_to_nat::<F, _, _>(limbs.iter(), limb_width), n_bits)
+}
+
+/// Compute the `n_limbs` limbs of width `limb_width` encoding big-endian bytes.
+pub fn bytes_to_limbs<F: PrimeField>(
+    bytes: &[u8],
+    limb_width: usize,
+    n_limbs: usize,
+) -> Result<Vec<F>, LimbError> {
+    nat_to_limbs(&nat_from_bytes_be(bytes), limb_width, n_limbs)
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct BigNatParams {
     pub min_bits: usize,
@@ -102,6 +198,21 @@ impl<E: Engine> From<BigNat<E>> for Polynomial<E> {
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    static ALLOCATE_CARRIES: Cell<bool> = Cell::new(false);
+}
+
+/// Runs `f` with `BigNat::equal_when_carried` allocating each carry, as it did before carries were
+/// derived, so that tests can measure what deriving them saves.
+#[cfg(test)]
+pub fn with_allocated_carries<T, F: FnOnce() -> T>(f: F) -> T {
+    ALLOCATE_CARRIES.with(|a| a.set(true));
+    let t = f();
+    ALLOCATE_CARRIES.with(|a| a.set(false));
+    t
+}
+
 impl<E: Engine> BigNat<E> {
     /// Allocates a `BigNat` in the circuit with `n_limbs` limbs of width `limb_width` each.
     /// If `max_word` is missing, then it is assumed to be `(2 << limb_width) - 1`.
@@ -188,6 +299,13 @@ impl<E: Engine> BigNat<E> {
         }
     }
 
+    /// Creates a `BigNat` from limbs that are already allocated, such as hash outputs or the
+    /// limbs of a `BigNat` from another gadget. No constraints are added, so the limbs are not
+    /// range-checked: the caller must ensure that each fits in `limb_width` bits.
+    pub fn from_allocated_limbs(limbs: &[AllocatedNum<E>], limb_width: usize) -> Self {
+        Self::from_limbs(limbs.iter().cloned().map(Num::from).collect(), limb_width)
+    }
+
     /// Allocates a `BigNat` in the circuit with `n_limbs` limbs of width `limb_width` each.
     /// The `max_word` is gauranteed to be `(2 << limb_width) - 1`.
     /// The value is provided by a closure returning a natural number.
@@ -300,13 +418,14 @@ impl<E: Engine> BigNat<E> {
         Ok(())
     }
 
+    /// A bit which is set iff every limb of `self` equals the corresponding limb of `other`.
+    /// Each limb costs a zero test of the limbs' difference, and one more zero test checks that
+    /// all of them passed.
     pub fn is_equal<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
         other: &Self,
     ) -> Result<Boolean, SynthesisError> {
-        use sapling_crypto::circuit::num::{AllocatedNum, Num};
-        let mut rolling = Boolean::Constant(true);
         if self.limbs.len() != other.limbs.len() {
             eprintln!(
                 "Self has {} limbs, other {} (BigNat::is_equal)",
@@ -317,33 +436,47 @@ impl<E: Engine> BigNat<E> {
         }
         self.enforce_limb_width_agreement(other, "is_equal")?;
         let n = self.limbs.len();
+        // `n_unequal` counts the equal limbs, less `n`.
+        let mut minus_n = usize_to_f::<E::Fr>(n);
+        minus_n.negate();
+        let mut n_unequal = Num::new(
+            Some(minus_n),
+            LinearCombination::zero() + (minus_n, CS::one()),
+        );
         for i in 0..n {
-            let self_limb = AllocatedNum::alloc(cs.namespace(|| format!("self {}", i)), || {
-                Ok(self.limb_values.as_ref().grab()?[i])
-            })?;
-            cs.enforce(
-                || format!("equal self {}", i),
-                |lc| lc,
-                |lc| lc,
-                |lc| lc - &Num::from(self_limb.clone()).lc(E::Fr::one()) + &self.limbs[i],
-            );
-            let other_limb = AllocatedNum::alloc(cs.namespace(|| format!("other {}", i)), || {
-                Ok(other.limb_values.as_ref().grab()?[i])
-            })?;
-            cs.enforce(
-                || format!("equal other {}", i),
-                |lc| lc,
-                |lc| lc,
-                |lc| lc - &Num::from(other_limb.clone()).lc(E::Fr::one()) + &other.limbs[i],
+            let diff = Num::new(
+                self.limb_values.as_ref().and_then(|s| {
+                    other.limb_values.as_ref().map(|o| {
+                        let mut d = s[i];
+                        d.sub_assign(&o[i]);
+                        d
+                    })
+                }),
+                LinearCombination::zero() + &self.limbs[i] - &other.limbs[i],
             );
-            let b = AllocatedNum::equals(
-                cs.namespace(|| format!("eq {}", i)),
-                &self_limb,
-                &other_limb,
-            )?;
-            rolling = Boolean::and(cs.namespace(|| format!("and {}", i)), &b, &rolling)?;
+            let eq = diff.is_zero(cs.namespace(|| format!("limb {} equal", i)))?;
+            n_unequal.num = n_unequal.num + &eq.lc(CS::one(), E::Fr::one());
+            n_unequal.value = n_unequal.value.and_then(|mut v| {
+                eq.get_value().map(|b| {
+                    if b {
+                        v.add_assign(&E::Fr::one());
+                    }
+                    v
+                })
+            });
         }
-        Ok(rolling)
+        n_unequal.is_zero(cs.namespace(|| "all equal"))
+    }
+
+    /// Returns `a` if `cond` is true, otherwise `b`. Like `Gadget::mux`, but with the operands
+    /// in the order of sapling's `AllocatedNum::conditionally_select`.
+    pub fn conditionally_select<CS: ConstraintSystem<E>>(
+        cs: CS,
+        a: &Self,
+        b: &Self,
+        cond: &Boolean,
+    ) -> Result<Self, SynthesisError> {
+        Self::mux(cs, &Bit::from_sapling::<CS>(cond.clone()), b, a)
     }
 
     /// Break `self` up into a bit-vector.
@@ -412,6 +545,34 @@ impl<E: Engine> BigNat<E> {
         Ok(())
     }
 
+    /// Constrains `self` to be less than `2^n_bits`, whatever its number of limbs. The limbs
+    /// below bit `n_bits` are range-checked (the one holding bit `n_bits - 1` to the bits below
+    /// `n_bits`), and the limbs above it must be zero.
+    pub fn assert_bit_length<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        n_bits: usize,
+    ) -> Result<(), SynthesisError> {
+        let limb_width = self.params.limb_width;
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let low_bit = i * limb_width;
+            let value = self.limb_values.as_ref().map(|vs| vs[i]);
+            if low_bit >= n_bits {
+                cs.enforce(
+                    || format!("limb {} is zero", i),
+                    |lc| lc,
+                    |lc| lc,
+                    |lc| lc + limb,
+                );
+            } else {
+                let width = min(limb_width, n_bits - low_bit);
+                Num::new(value, limb.clone())
+                    .fits_in_bits(cs.namespace(|| format!("limb {} fits", i)), width)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn enforce_min_bits<CS: ConstraintSystem<E>>(
         &mut self,
         mut cs: CS,
@@ -553,49 +714,75 @@ impl<E: Engine> BigNat<E> {
         let carry_bits =
             (((max_word.to_f64().unwrap() * 2.0).log2() - self.params.limb_width as f64).ceil()
                 + 0.1) as usize;
+        let target_base_f = nat_to_f::<E::Fr>(&target_base).unwrap();
+        let target_base_inv = target_base_f.inverse().unwrap();
+        let max_word_f = nat_to_f::<E::Fr>(max_word).unwrap();
         let mut carry_in = Num::new(Some(E::Fr::zero()), LinearCombination::zero());
 
+        // Each carry is a linear function of the previous carry and the limbs, so it is never
+        // allocated: only the bits of its range check are.
         for i in 0..n {
-            let carry = Num::alloc(cs.namespace(|| format!("carry value {}", i)), || {
-                Ok(nat_to_f(
-                    &((f_to_nat(&self.limb_values.grab()?[i])
-                        + f_to_nat(&carry_in.value.unwrap())
-                        + max_word
-                        - f_to_nat(&other.limb_values.grab()?[i]))
-                        / &target_base),
-                )
-                .unwrap())
-            })?;
             accumulated_extra += max_word;
-
-            cs.enforce(
-                || format!("carry {}", i),
-                |lc| lc,
-                |lc| lc,
-                |lc| {
-                    lc + &carry_in.num + &self.limbs[i] - &other.limbs[i]
-                        + (nat_to_f(&max_word).unwrap(), CS::one())
-                        - (nat_to_f(&target_base).unwrap(), &carry.num)
-                        - (
-                            nat_to_f(&(&accumulated_extra % &target_base)).unwrap(),
-                            CS::one(),
-                        )
-                },
-            );
+            let extra_f = nat_to_f::<E::Fr>(&(&accumulated_extra % &target_base)).unwrap();
+            let shifted = LinearCombination::zero() + &carry_in.num + &self.limbs[i]
+                - &other.limbs[i]
+                + (max_word_f, CS::one())
+                - (extra_f, CS::one());
+            let shifted_value = carry_in.value.and_then(|c| {
+                self.limb_values.as_ref().and_then(|svs| {
+                    other.limb_values.as_ref().map(|ovs| {
+                        let mut v = c;
+                        v.add_assign(&svs[i]);
+                        v.sub_assign(&ovs[i]);
+                        v.add_assign(&max_word_f);
+                        v.sub_assign(&extra_f);
+                        v
+                    })
+                })
+            });
 
             accumulated_extra /= &target_base;
 
+            let carry = Num::new(
+                shifted_value.map(|mut v| {
+                    v.mul_assign(&target_base_inv);
+                    v
+                }),
+                LinearCombination::zero() + (target_base_inv, &shifted),
+            );
+            #[cfg(test)]
+            let carry = if ALLOCATE_CARRIES.with(Cell::get) {
+                let allocated = Num::alloc(cs.namespace(|| format!("carry value {}", i)), || {
+                    Ok(*carry.value.grab()?)
+                })?;
+                cs.enforce(
+                    || format!("carry {}", i),
+                    |lc| lc,
+                    |lc| lc,
+                    |lc| lc + &allocated.num - &carry.num,
+                );
+                allocated
+            } else {
+                carry
+            };
+
             if i < n - 1 {
                 carry.fits_in_bits(cs.namespace(|| format!("carry {} decomp", i)), carry_bits)?;
+                carry_in = carry;
             } else {
                 cs.enforce(
                     || format!("carry {} is out", i),
                     |lc| lc,
                     |lc| lc,
-                    |lc| lc + &carry.num - (nat_to_f(&accumulated_extra).unwrap(), CS::one()),
+                    |lc| {
+                        lc + &shifted
+                            - (
+                                nat_to_f::<E::Fr>(&(&accumulated_extra * &target_base)).unwrap(),
+                                CS::one(),
+                            )
+                    },
                 );
             }
-            carry_in = Num::from(carry);
         }
 
         for (i, zero_limb) in self.limbs.iter().enumerate().skip(n) {
@@ -617,20 +804,49 @@ impl<E: Engine> BigNat<E> {
         Ok(())
     }
 
+    /// The largest number of limbs that `equal_when_carried_regroup` can combine into one field
+    /// element when comparing `self` and `other`, leaving room for the carry.
+    pub fn max_limbs_per_group(&self, other: &Self) -> usize {
+        let max_word = std::cmp::max(&self.params.max_word, &other.params.max_word);
+        let carry_bits =
+            (((max_word.to_f64().unwrap() * 2.0).log2() - self.params.limb_width as f64).ceil()
+                + 0.1) as usize;
+        (E::Fr::CAPACITY as usize - carry_bits) / self.params.limb_width
+    }
+
     /// Constrain `self` to be equal to `other`, after carrying both.
     /// Uses regrouping internally to take full advantage of the field size and reduce the amount
     /// of carrying.
     pub fn equal_when_carried_regroup<CS: ConstraintSystem<E>>(
+        &self,
+        cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.equal_when_carried_grouped(cs, other, self.max_limbs_per_group(other))
+    }
+
+    /// Constrain `self` to be equal to `other`, after carrying both, with `limbs_per_group` limbs
+    /// combined into each word before carrying.
+    ///
+    /// Each carry between words is range-checked, at a cost of about one constraint per bit of
+    /// carry, and the carries are about as wide as a limb. So larger groups mean fewer carries and
+    /// fewer constraints. A group size of 1 is `equal_when_carried`, and the largest usable size
+    /// is `max_limbs_per_group`.
+    pub fn equal_when_carried_grouped<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
         other: &Self,
+        limbs_per_group: usize,
     ) -> Result<(), SynthesisError> {
-        self.enforce_limb_width_agreement(other, "equal_when_carried_regroup")?;
-        let max_word = std::cmp::max(&self.params.max_word, &other.params.max_word);
-        let carry_bits =
-            (((max_word.to_f64().unwrap() * 2.0).log2() - self.params.limb_width as f64).ceil()
-                + 0.1) as usize;
-        let limbs_per_group = (E::Fr::CAPACITY as usize - carry_bits) / self.params.limb_width;
+        self.enforce_limb_width_agreement(other, "equal_when_carried_grouped")?;
+        let max_limbs_per_group = self.max_limbs_per_group(other);
+        if limbs_per_group == 0 || limbs_per_group > max_limbs_per_group {
+            eprintln!(
+                "Cannot group {} limbs of width {}: between 1 and {} fit in a field element",
+                limbs_per_group, self.params.limb_width, max_limbs_per_group
+            );
+            return Err(SynthesisError::Unsatisfiable);
+        }
         let self_grouped = self.group_limbs(limbs_per_group);
         let other_grouped = other.group_limbs(limbs_per_group);
         self_grouped.equal_when_carried(cs.namespace(|| "grouped"), &other_grouped)
@@ -698,25 +914,132 @@ impl<E: Engine> BigNat<E> {
         new
     }
 
+    /// Computes `self - other`, constraining the difference `d` to satisfy `d + other == self`.
+    /// The limbs of `d` are range checked, so the constraints are unsatisfiable if `self < other`.
+    /// In that case, witness generation fails with an error.
     pub fn sub<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
         other: &Self,
     ) -> Result<BigNat<E>, SynthesisError> {
+        self.enforce_limb_width_agreement(other, "sub")?;
         let diff = BigNat::alloc_from_nat(
             cs.namespace(|| "diff"),
             || {
                 let s = self.value.grab()?;
                 let o = other.value.grab()?;
+                if s < o {
+                    eprintln!("sub underflow: {} - {}", s, o);
+                    return Err(SynthesisError::Unsatisfiable);
+                }
                 Ok(s - o)
             },
             self.params.limb_width,
             self.params.n_limbs,
         )?;
+        diff.decompose(cs.namespace(|| "diff decomp"))?;
         let sum = other.add::<CS>(&diff)?;
         self.equal_when_carried_regroup(cs.namespace(|| "eq"), &sum)?;
         Ok(diff)
     }
+
+    /// Computes `self - modulus` if `cond` is true, and `self` otherwise. The limbs of the result
+    /// are range checked.
+    /// The caller is responsible for choosing `cond` so that the result is non-negative, e.g.
+    /// `cond` should be `self >= modulus` when `self` is known to be in `[0, 2 * modulus)`.
+    pub fn conditional_reduce<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        modulus: &Self,
+        cond: &Boolean,
+    ) -> Result<BigNat<E>, SynthesisError> {
+        self.enforce_limb_width_agreement(modulus, "conditional_reduce")?;
+        let subtrahend_values: Option<Vec<E::Fr>> = cond.get_value().and_then(|c| {
+            modulus.limb_values.as_ref().map(|vs| {
+                vs.iter()
+                    .map(|v| if c { v.clone() } else { E::Fr::zero() })
+                    .collect()
+            })
+        });
+        let mut subtrahend_limbs = Vec::new();
+        for (i, m_limb) in modulus.limbs.iter().enumerate() {
+            let s = cs.alloc(
+                || format!("subtrahend {}", i),
+                || Ok(subtrahend_values.grab()?[i]),
+            )?;
+            cs.enforce(
+                || format!("subtrahend {} is cond * modulus", i),
+                |_| cond.lc(CS::one(), E::Fr::one()),
+                |lc| lc + m_limb,
+                |lc| lc + s,
+            );
+            subtrahend_limbs.push(LinearCombination::zero() + s);
+        }
+        let subtrahend = BigNat {
+            limbs: subtrahend_limbs,
+            value: cond.get_value().and_then(|c| {
+                if c {
+                    modulus.value.clone()
+                } else {
+                    Some(BigUint::from(0usize))
+                }
+            }),
+            limb_values: subtrahend_values,
+            params: BigNatParams {
+                min_bits: 0,
+                ..modulus.params.clone()
+            },
+        };
+        let diff = BigNat::alloc_from_nat(
+            cs.namespace(|| "diff"),
+            || {
+                let s = self.value.grab()?;
+                let o = subtrahend.value.grab()?;
+                if s < o {
+                    eprintln!("conditional_reduce underflow: {} - {}", s, o);
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+                Ok(s - o)
+            },
+            self.params.limb_width,
+            self.params.n_limbs,
+        )?;
+        diff.decompose(cs.namespace(|| "diff decomp"))?;
+        let sum = subtrahend.add::<CS>(&diff)?;
+        self.equal_when_carried_regroup(cs.namespace(|| "eq"), &sum)?;
+        Ok(diff)
+    }
+
+    /// Constrains `self < other` by witnessing `other - self - 1` and range-checking its limbs.
+    /// Assumes that the limbs of `self` are already known to be in range.
+    pub fn enforce_lt<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<(), SynthesisError> {
+        self.enforce_limb_width_agreement(other, "enforce_lt")?;
+        let diff = BigNat::alloc_from_nat(
+            cs.namespace(|| "diff"),
+            || {
+                let s = self.value.grab()?;
+                let o = other.value.grab()?;
+                if s < o {
+                    Ok(o - s - 1usize)
+                } else {
+                    // The constraints below will not be satisfied.
+                    Ok(BigUint::from(0usize))
+                }
+            },
+            other.params.limb_width,
+            other.params.n_limbs,
+        )?;
+        diff.decompose(cs.namespace(|| "diff decomp"))?;
+        let sum = self
+            .add::<CS>(&diff)?
+            .add::<CS>(&BigNat::identity::<CS>(self.params.limb_width))?;
+        other.equal_when_carried_regroup(cs.namespace(|| "eq"), &sum)
+    }
+
     pub fn one<CS: ConstraintSystem<E>>(
         cs: CS,
         limb_width: usize,
@@ -809,7 +1132,7 @@ impl<E: Engine> BigNat<E> {
                 .and_then(|s| other.value.as_ref().map(|o| o < s)),
         )?;
         let (lesser, greater) = Gadget::switch(cs.namespace(|| "switch"), &select, self, other)?;
-        let _diff = greater.sub(cs.namespace(|| "difference"), other)?;
+        let _diff = greater.sub(cs.namespace(|| "difference"), &lesser)?;
         Ok(lesser)
     }
 
@@ -821,25 +1144,18 @@ impl<E: Engine> BigNat<E> {
     ) -> Result<(), SynthesisError> {
         self.enforce_limb_width_agreement(other, "verify_mult, other")?;
         self.enforce_limb_width_agreement(prod, "verify_mult, prod")?;
-        // Verify that factor is in bounds
-        let max_word = BigUint::from(min(self.params.n_limbs, other.params.n_limbs))
-            * &self.params.max_word
-            * &other.params.max_word;
-        let poly_prod = Polynomial::from(self.clone()).alloc_product(
-            cs.namespace(|| "poly product"),
-            &Polynomial::from(other.clone()),
-        )?;
-        BigNat::from_poly(poly_prod, other.params.limb_width, max_word)
+        self.poly_mult(cs.namespace(|| "product"), other)?
             .equal_when_carried_regroup(cs.namespace(|| "equal"), prod)?;
         Ok(())
     }
 
     pub fn enforce_coprime<CS: ConstraintSystem<E>>(
         &self,
-        mut cs: CS,
+        cs: CS,
         other: &Self,
     ) -> Result<(), SynthesisError> {
-        let one = Self::one(cs.namespace(|| "one"), other.params.limb_width)?;
+        // A constant, since an allocated one would let the prover choose the gcd.
+        let one = Self::identity::<CS>(other.params.limb_width);
         self.enforce_gcd(cs, other, &one)
     }
 
@@ -911,6 +1227,37 @@ impl<E: Engine> BigNat<E> {
         Ok(gcd)
     }
 
+    /// Compute a `BigNat` constrained to be the inverse of `self` modulo `modulus`.
+    /// Witness generation fails if `self` and `modulus` are not coprime.
+    pub fn inverse_mod<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        modulus: &Self,
+    ) -> Result<BigNat<E>, SynthesisError> {
+        self.enforce_limb_width_agreement(modulus, "inverse_mod")?;
+        let inverse = BigNat::alloc_from_nat(
+            cs.namespace(|| "inverse"),
+            || {
+                let a = self.value.grab()?.to_bigint().unwrap();
+                let m = modulus.value.grab()?.to_bigint().unwrap();
+                let egcd = a.extended_gcd(&m);
+                if !egcd.gcd.is_one() {
+                    eprintln!("{} has no inverse modulo {}", a, m);
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+                // The Bezout coefficient may be negative.
+                Ok(egcd.x.mod_floor(&m).to_biguint().unwrap())
+            },
+            modulus.params.limb_width,
+            modulus.params.n_limbs,
+        )?;
+        inverse.decompose(cs.namespace(|| "inverse rangecheck"))?;
+        inverse.enforce_lt(cs.namespace(|| "inverse < modulus"), modulus)?;
+        let one = BigNat::identity::<CS>(self.params.limb_width);
+        self.assert_product_mod(cs.namespace(|| "product is one"), &inverse, modulus, &one)?;
+        Ok(inverse)
+    }
+
     pub fn assert_product_mod<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
@@ -921,7 +1268,6 @@ impl<E: Engine> BigNat<E> {
         self.enforce_limb_width_agreement(other, "assert_product_mod, other")?;
         self.enforce_limb_width_agreement(modulus, "assert_product_mod, modulus")?;
         self.enforce_limb_width_agreement(remainder, "assert_product_mod, remainder")?;
-        let limb_width = self.params.limb_width;
         let quotient_limbs = self.limbs.len() + other.limbs.len();
         let quotient = BigNat::alloc_from_nat(
             cs.namespace(|| "quotient"),
@@ -930,40 +1276,77 @@ impl<E: Engine> BigNat<E> {
             quotient_limbs,
         )?;
         quotient.decompose(cs.namespace(|| "quotient rangecheck"))?;
-        let a_poly = Polynomial::from(self.clone());
-        let b_poly = Polynomial::from(other.clone());
-        let mod_poly = Polynomial::from(modulus.clone());
-        let q_poly = Polynomial::from(BigNat::from(quotient.clone()));
-        let r_poly = Polynomial::from(BigNat::from(remainder.clone()));
-
-        // a * b
-        let left = a_poly.alloc_product(cs.namespace(|| "left"), &b_poly)?;
-        let right_product = q_poly.alloc_product(cs.namespace(|| "right_product"), &mod_poly)?;
-        // q * m + r
-        let right = Polynomial::from(right_product).sum(&r_poly);
-
-        let left_max_word = BigUint::from(min(self.limbs.len(), other.limbs.len()))
+        let left_int = self.poly_mult(cs.namespace(|| "left"), other)?;
+        BigNat::assert_product_sum(cs, &left_int, &quotient, modulus, remainder)?;
+        Ok(quotient)
+    }
+
+    /// Constrains `a == b * c + d`.
+    /// The operands are not range checked: callers which need `b`, `c`, or `d` to be well-formed
+    /// must check that themselves (e.g. with `decompose`).
+    pub fn assert_product_sum<CS: ConstraintSystem<E>>(
+        cs: CS,
+        a: &Self,
+        b: &Self,
+        c: &Self,
+        d: &Self,
+    ) -> Result<(), SynthesisError> {
+        Self::assert_product_sum_grouped(cs, a, b, c, d, None)
+    }
+
+    /// `assert_product_sum`, carrying with `limbs_per_group` limbs per word if given, and with
+    /// the largest grouping otherwise.
+    fn assert_product_sum_grouped<CS: ConstraintSystem<E>>(
+        mut cs: CS,
+        a: &Self,
+        b: &Self,
+        c: &Self,
+        d: &Self,
+        limbs_per_group: Option<usize>,
+    ) -> Result<(), SynthesisError> {
+        a.enforce_limb_width_agreement(b, "assert_product_sum, b")?;
+        a.enforce_limb_width_agreement(c, "assert_product_sum, c")?;
+        a.enforce_limb_width_agreement(d, "assert_product_sum, d")?;
+        let product = b.poly_mult(cs.namespace(|| "product"), c)?;
+        let right = product.add::<CS>(d)?;
+        let limbs_per_group = limbs_per_group.unwrap_or_else(|| a.max_limbs_per_group(&right));
+        a.equal_when_carried_grouped(cs.namespace(|| "carry"), &right, limbs_per_group)
+    }
+
+    /// The limb-wise (uncarried) product of `self` and `other`.
+    fn poly_mult<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        other: &Self,
+    ) -> Result<BigNat<E>, SynthesisError> {
+        let max_word = BigUint::from(min(self.limbs.len(), other.limbs.len()))
             * &self.params.max_word
             * &other.params.max_word;
-        let right_max_word =
-            BigUint::from(std::cmp::min(quotient.limbs.len(), modulus.limbs.len()))
-                * &quotient.params.max_word
-                * &modulus.params.max_word
-                + &remainder.params.max_word;
-
-        let left_int = BigNat::from_poly(Polynomial::from(left), limb_width, left_max_word);
-        let right_int = BigNat::from_poly(Polynomial::from(right), limb_width, right_max_word);
-        left_int.equal_when_carried_regroup(cs.namespace(|| "carry"), &right_int)?;
-        Ok(quotient)
+        let product = Polynomial::from(self.clone())
+            .alloc_product(cs.namespace(|| "poly product"), &Polynomial::from(other.clone()))?;
+        Ok(BigNat::from_poly(product, self.params.limb_width, max_word))
     }
 
     /// Compute a `BigNat` contrained to be equal to `self * other % modulus`.
     pub fn mult_mod<CS: ConstraintSystem<E>>(
+        &self,
+        cs: CS,
+        other: &Self,
+        modulus: &Self,
+    ) -> Result<(BigNat<E>, BigNat<E>), SynthesisError> {
+        self.mult_mod_grouped(cs, other, modulus, None)
+    }
+
+    /// `mult_mod`, checking the product with `limbs_per_group` limbs per word (see
+    /// `equal_when_carried_grouped`). `None` uses the largest grouping, as `mult_mod` does.
+    pub fn mult_mod_grouped<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
         other: &Self,
         modulus: &Self,
+        limbs_per_group: Option<usize>,
     ) -> Result<(BigNat<E>, BigNat<E>), SynthesisError> {
+        count_mult_mod();
         self.enforce_limb_width_agreement(other, "mult_mod")?;
         let limb_width = self.params.limb_width;
         let quotient_bits =
@@ -983,40 +1366,51 @@ impl<E: Engine> BigNat<E> {
             modulus.limbs.len(),
         )?;
         remainder.decompose(cs.namespace(|| "remainder rangecheck"))?;
-        let a_poly = Polynomial::from(self.clone());
-        let b_poly = Polynomial::from(other.clone());
-        let mod_poly = Polynomial::from(modulus.clone());
-        let q_poly = Polynomial::from(BigNat::from(quotient.clone()));
-        let r_poly = Polynomial::from(BigNat::from(remainder.clone()));
-
-        // a * b
-        let left = a_poly.alloc_product(cs.namespace(|| "left"), &b_poly)?;
-        let right_product = q_poly.alloc_product(cs.namespace(|| "right_product"), &mod_poly)?;
-        // q * m + r
-        let right = Polynomial::from(right_product).sum(&r_poly);
-
-        let left_max_word = BigUint::from(std::cmp::min(self.limbs.len(), other.limbs.len()))
-            * &self.params.max_word
-            * &other.params.max_word;
-        let right_max_word =
-            BigUint::from(std::cmp::min(quotient.limbs.len(), modulus.limbs.len()))
-                * &quotient.params.max_word
-                * &modulus.params.max_word
-                + &remainder.params.max_word;
-
-        let left_int = BigNat::from_poly(Polynomial::from(left), limb_width, left_max_word);
-        let right_int = BigNat::from_poly(Polynomial::from(right), limb_width, right_max_word);
-        left_int.equal_when_carried_regroup(cs.namespace(|| "carry"), &right_int)?;
+        let left_int = self.poly_mult(cs.namespace(|| "left"), other)?;
+        BigNat::assert_product_sum_grouped(
+            cs,
+            &left_int,
+            &quotient,
+            modulus,
+            &remainder,
+            limbs_per_group,
+        )?;
         Ok((quotient, remainder))
     }
 
     /// Compute a `BigNat` contrained to be equal to `self * other % modulus`.
     pub fn red_mod<CS: ConstraintSystem<E>>(
         &self,
-        mut cs: CS,
+        cs: CS,
         modulus: &Self,
     ) -> Result<BigNat<E>, SynthesisError> {
         self.enforce_limb_width_agreement(modulus, "red_mod")?;
+        Ok(self.divide(cs, modulus)?.1)
+    }
+
+    /// Computes `(q, r)`, constrained so that `self = q * divisor + r` and `r < divisor`.
+    pub fn divmod<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        divisor: &Self,
+    ) -> Result<(BigNat<E>, BigNat<E>), SynthesisError> {
+        self.enforce_limb_width_agreement(divisor, "divmod")?;
+        if divisor.value.as_ref().map_or(false, Zero::is_zero) {
+            eprintln!("Division by zero (BigNat::divmod)");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let (quotient, remainder) = self.divide(cs.namespace(|| "divide"), divisor)?;
+        remainder.enforce_lt(cs.namespace(|| "remainder < divisor"), divisor)?;
+        Ok((quotient, remainder))
+    }
+
+    /// Allocates `q` and `r` with `self = q * modulus + r`, range-checking their limbs but not
+    /// checking that `r < modulus`.
+    fn divide<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        modulus: &Self,
+    ) -> Result<(BigNat<E>, BigNat<E>), SynthesisError> {
         let limb_width = self.params.limb_width;
         let quotient_bits = self.n_bits().saturating_sub(modulus.params.min_bits);
         let quotient_limbs = quotient_bits.saturating_sub(1) / limb_width + 1;
@@ -1034,23 +1428,8 @@ impl<E: Engine> BigNat<E> {
             modulus.limbs.len(),
         )?;
         remainder.decompose(cs.namespace(|| "remainder rangecheck"))?;
-        let mod_poly = Polynomial::from(modulus.clone());
-        let q_poly = Polynomial::from(BigNat::from(quotient.clone()));
-        let r_poly = Polynomial::from(BigNat::from(remainder.clone()));
-
-        // q * m + r
-        let right_product = q_poly.alloc_product(cs.namespace(|| "right_product"), &mod_poly)?;
-        let right = Polynomial::from(right_product).sum(&r_poly);
-
-        let right_max_word =
-            BigUint::from(std::cmp::min(quotient.limbs.len(), modulus.limbs.len()))
-                * &quotient.params.max_word
-                * &modulus.params.max_word
-                + &remainder.params.max_word;
-
-        let right_int = BigNat::from_poly(Polynomial::from(right), limb_width, right_max_word);
-        self.equal_when_carried_regroup(cs.namespace(|| "carry"), &right_int)?;
-        Ok(remainder)
+        BigNat::assert_product_sum(cs, self, &quotient, modulus, &remainder)?;
+        Ok((quotient, remainder))
     }
 
     /// Combines limbs into groups.
@@ -1099,11 +1478,13 @@ impl<E: Engine> BigNat<E> {
     }
 
     // NB: `exp` should have its bits *in reverse*. That is, the bit at index 0 is high order.
+    // `k` is the window size, in bits.
     fn pow_mod_bin_rev<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
         exp: Bitvector<E>,
         modulus: &Self,
+        k: usize,
     ) -> Result<BigNat<E>, SynthesisError> {
         fn bauer_power_bin_rev_helper<'a, E: Engine, CS: ConstraintSystem<E>>(
             mut cs: CS,
@@ -1149,7 +1530,11 @@ impl<E: Engine> BigNat<E> {
                 Ok(BigNat::identity::<CS>(modulus.params.limb_width))
             }
         }
-        let k = optimal_k(exp.bits.len());
+        if k == 0 {
+            eprintln!("The exponentiation window must be at least one bit");
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        count_modpow();
         let base_powers = {
             let mut base_powers = vec![
                 BigNat::identity::<CS>(modulus.params.limb_width),
@@ -1182,21 +1567,42 @@ impl<E: Engine> BigNat<E> {
         exp: &Self,
         modulus: &Self,
     ) -> Result<BigNat<E>, SynthesisError> {
-        let exp_bin_rev = if exp.params.max_word >= BigUint::one() << exp.params.limb_width {
-            let exp_carried = BigNat::alloc_from_nat(
+        let exp_bin_rev = exp.exp_bits_rev(cs.namespace(|| "exp"))?;
+        let k = optimal_k(exp_bin_rev.bits.len());
+        self.pow_mod_bin_rev(cs.namespace(|| "binary exp"), exp_bin_rev, modulus, k)
+    }
+
+    /// Computes a `BigNat` constrained to be equal to `self ** exp % modulus`, processing
+    /// `window_bits` bits of `exp` per multiplication.
+    /// Larger windows trade more precomputed powers of `self` for fewer multiplications.
+    pub fn pow_mod_windowed<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        exp: &Self,
+        modulus: &Self,
+        window_bits: usize,
+    ) -> Result<BigNat<E>, SynthesisError> {
+        let exp_bin_rev = exp.exp_bits_rev(cs.namespace(|| "exp"))?;
+        self.pow_mod_bin_rev(cs.namespace(|| "binary exp"), exp_bin_rev, modulus, window_bits)
+    }
+
+    /// The bits of `self`, high order first, carrying it first if necessary.
+    fn exp_bits_rev<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+    ) -> Result<Bitvector<E>, SynthesisError> {
+        if self.params.max_word >= BigUint::one() << self.params.limb_width {
+            let carried = BigNat::alloc_from_nat(
                 cs.namespace(|| "exp carried"),
-                || Ok(exp.value.grab()?.clone()),
-                exp.params.limb_width,
-                exp.params.n_limbs,
+                || Ok(self.value.grab()?.clone()),
+                self.params.limb_width,
+                self.params.n_limbs,
             )?;
-            exp_carried.equal_when_carried_regroup(cs.namespace(|| "carry check"), &exp)?;
-            exp_carried
-                .decompose(cs.namespace(|| "exp decomp"))?
-                .reversed()
+            carried.equal_when_carried_regroup(cs.namespace(|| "carry check"), self)?;
+            Ok(carried.decompose(cs.namespace(|| "exp decomp"))?.reversed())
         } else {
-            exp.decompose(cs.namespace(|| "exp decomp"))?.reversed()
-        };
-        self.pow_mod_bin_rev(cs.namespace(|| "binary exp"), exp_bin_rev, modulus)
+            Ok(self.decompose(cs.namespace(|| "exp decomp"))?.reversed())
+        }
     }
 
     /// Assuming that the input is equivalent to 3 modulo 4, does a round of Miller-Rabin to check
@@ -1325,6 +1731,22 @@ impl<E: Engine> BigNat<E> {
         assert!(self.params.n_limbs > 0);
         self.params.limb_width * (self.params.n_limbs - 1) + self.params.max_word.bits()
     }
+
+    /// The number of limbs.
+    pub fn n_limbs(&self) -> usize {
+        self.params.n_limbs
+    }
+
+    /// The width of each limb, in bits.
+    pub fn limb_width(&self) -> usize {
+        self.params.limb_width
+    }
+
+    /// The number of bits which fit in the limbs once carried.
+    /// Unlike `n_bits`, this ignores any slack in `max_word`.
+    pub fn max_bits(&self) -> usize {
+        self.params.n_limbs * self.params.limb_width
+    }
 }
 
 impl<E: Engine> Gadget for BigNat<E> {
@@ -1963,6 +2385,74 @@ mod tests {
         }
     }
 
+    /// Computes `b^e % 251` with 4-bit limbs, by `pow_mod_windowed` with `window_bits` if it is
+    /// given, and by `pow_mod` otherwise.
+    fn pow_with_window(
+        b: usize,
+        e: usize,
+        e_limbs: usize,
+        window_bits: Option<usize>,
+    ) -> (BigUint, TestConstraintSystem<Bn256>) {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let alloc = |cs: &mut TestConstraintSystem<Bn256>, name: &str, v: usize, n_limbs: usize| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(BigUint::from(v)), 4, n_limbs)
+                .unwrap()
+        };
+        let b = alloc(&mut cs, "b", b, 2);
+        let e = alloc(&mut cs, "e", e, e_limbs);
+        let m = alloc(&mut cs, "m", 251, 2);
+        let pow = match window_bits {
+            Some(k) => b.pow_mod_windowed(cs.namespace(|| "pow"), &e, &m, k),
+            None => b.pow_mod(cs.namespace(|| "pow"), &e, &m),
+        }
+        .unwrap();
+        (pow.value.unwrap(), cs)
+    }
+
+    #[test]
+    fn pow_mod_windowed_matches_pow_mod() {
+        // The one-limb exponent has 4 bits, fewer than the widest window.
+        for &(b, e, e_limbs) in &[(7usize, 0xd3busize, 3usize), (11, 0b1011, 1)] {
+            let (expected, cs) = pow_with_window(b, e, e_limbs, None);
+            assert!(cs.is_satisfied());
+            assert_eq!(
+                expected,
+                BigUint::from(b).modpow(&BigUint::from(e), &BigUint::from(251usize))
+            );
+            for window_bits in 1..6 {
+                let (actual, cs) = pow_with_window(b, e, e_limbs, Some(window_bits));
+                assert!(cs.is_satisfied(), "window of {} bits", window_bits);
+                assert_eq!(actual, expected, "window of {} bits", window_bits);
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn pow_mod_matches_modpow(b: u64, e: u16, m: u64) -> TestResult {
+        if m < 2 {
+            return TestResult::discard();
+        }
+        let (b, e, m) = (BigUint::from(b), BigUint::from(e), BigUint::from(m));
+        let (b_str, e_str, m_str) = (b.to_string(), e.to_string(), m.to_string());
+        let res_str = b.modpow(&e, &m).to_string();
+        let circuit = PowMod {
+            inputs: Some(PowModInputs {
+                b: &b_str,
+                e: &e_str,
+                m: &m_str,
+                res: &res_str,
+            }),
+            params: PowModParams {
+                limb_width: 32,
+                n_limbs_b: 2,
+                n_limbs_e: 1,
+            },
+        };
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        TestResult::from_bool(cs.is_satisfied())
+    }
+
     circuit_tests! {
         pow_mod_1_to_0: (
                             PowMod {
@@ -2465,6 +2955,684 @@ mod tests {
                           },
                           true),
     }
+
+    #[derive(Debug)]
+    pub struct ConditionalReduceInputs<'a> {
+        pub a: &'a str,
+        pub m: &'a str,
+        pub cond: bool,
+        pub res: &'a str,
+    }
+
+    pub struct ConditionalReduce<'a> {
+        inputs: Option<ConditionalReduceInputs<'a>>,
+    }
+
+    impl<'a, E: Engine> Circuit<E> for ConditionalReduce<'a> {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            use sapling_crypto::circuit::boolean::AllocatedBit;
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.a).unwrap()),
+                4,
+                3,
+            )?;
+            let m = BigNat::alloc_from_nat(
+                cs.namespace(|| "m"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.m).unwrap()),
+                4,
+                3,
+            )?;
+            let res = BigNat::alloc_from_nat(
+                cs.namespace(|| "res"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.res).unwrap()),
+                4,
+                3,
+            )?;
+            let cond = Boolean::Is(AllocatedBit::alloc(
+                cs.namespace(|| "cond"),
+                self.inputs.as_ref().map(|i| i.cond),
+            )?);
+            let actual = a.conditional_reduce(cs.namespace(|| "reduce"), &m, &cond)?;
+            actual.equal(cs.namespace(|| "eq"), &res)?;
+            Ok(())
+        }
+    }
+
+    circuit_tests! {
+        conditional_reduce_above: (
+            ConditionalReduce {
+                inputs: Some(ConditionalReduceInputs {
+                    a: "300",
+                    m: "251",
+                    cond: true,
+                    res: "49",
+                }),
+            },
+            true),
+        conditional_reduce_equal: (
+            ConditionalReduce {
+                inputs: Some(ConditionalReduceInputs {
+                    a: "251",
+                    m: "251",
+                    cond: true,
+                    res: "0",
+                }),
+            },
+            true),
+        conditional_reduce_below: (
+            ConditionalReduce {
+                inputs: Some(ConditionalReduceInputs {
+                    a: "200",
+                    m: "251",
+                    cond: false,
+                    res: "200",
+                }),
+            },
+            true),
+        conditional_reduce_wrong: (
+            ConditionalReduce {
+                inputs: Some(ConditionalReduceInputs {
+                    a: "300",
+                    m: "251",
+                    cond: false,
+                    res: "49",
+                }),
+            },
+            false),
+    }
+
+    #[test]
+    fn conditional_reduce_rejects_out_of_range_limbs() {
+        use sapling_crypto::circuit::boolean::AllocatedBit;
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let alloc = |cs: &mut TestConstraintSystem<Bn256>, name: &str, n: usize| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(BigUint::from(n)), 4, 3).unwrap()
+        };
+        let a = alloc(&mut cs, "a", 300);
+        let m = alloc(&mut cs, "m", 251);
+        let cond = Boolean::Is(AllocatedBit::alloc(cs.namespace(|| "cond"), Some(true)).unwrap());
+        a.conditional_reduce(cs.namespace(|| "reduce"), &m, &cond).unwrap();
+        assert!(cs.is_satisfied());
+
+        // 300 - 251 = 49 has limbs [1, 3, 0]. The limbs [1, 19, -1] also sum to 49 in the field,
+        // but they are not a valid 4-bit representation.
+        let mut minus_one = usize_to_f::<<Bn256 as Engine>::Fr>(1);
+        minus_one.negate();
+        cs.set("reduce/diff/limb 1", usize_to_f(19));
+        cs.set("reduce/diff/limb 2", minus_one);
+        assert!(!cs.is_satisfied());
+    }
+
+    #[derive(Debug)]
+    pub struct ProductSumInputs<'a> {
+        pub a: &'a str,
+        pub b: &'a str,
+        pub c: &'a str,
+        pub d: &'a str,
+    }
+
+    pub struct ProductSum<'a> {
+        inputs: Option<ProductSumInputs<'a>>,
+    }
+
+    impl<'a, E: Engine> Circuit<E> for ProductSum<'a> {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.a).unwrap()),
+                4,
+                6,
+            )?;
+            let b = BigNat::alloc_from_nat(
+                cs.namespace(|| "b"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.b).unwrap()),
+                4,
+                3,
+            )?;
+            let c = BigNat::alloc_from_nat(
+                cs.namespace(|| "c"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.c).unwrap()),
+                4,
+                3,
+            )?;
+            let d = BigNat::alloc_from_nat(
+                cs.namespace(|| "d"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.d).unwrap()),
+                4,
+                3,
+            )?;
+            BigNat::assert_product_sum(cs.namespace(|| "product sum"), &a, &b, &c, &d)
+        }
+    }
+
+    circuit_tests! {
+        product_sum_valid: (
+            ProductSum {
+                inputs: Some(ProductSumInputs {
+                    a: "2000077",
+                    b: "1000",
+                    c: "2000",
+                    d: "77",
+                }),
+            },
+            true),
+        product_sum_zero_addend: (
+            ProductSum {
+                inputs: Some(ProductSumInputs {
+                    a: "2000000",
+                    b: "1000",
+                    c: "2000",
+                    d: "0",
+                }),
+            },
+            true),
+        product_sum_one_over: (
+            ProductSum {
+                inputs: Some(ProductSumInputs {
+                    a: "2000078",
+                    b: "1000",
+                    c: "2000",
+                    d: "77",
+                }),
+            },
+            false),
+        product_sum_one_under: (
+            ProductSum {
+                inputs: Some(ProductSumInputs {
+                    a: "2000076",
+                    b: "1000",
+                    c: "2000",
+                    d: "77",
+                }),
+            },
+            false),
+    }
+
+    pub struct SubInputs {
+        pub a: BigUint,
+        pub b: BigUint,
+        pub diff: BigUint,
+    }
+
+    pub struct Sub {
+        inputs: Option<SubInputs>,
+    }
+
+    impl<E: Engine> Circuit<E> for Sub {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(self.inputs.grab()?.a.clone()),
+                32,
+                64,
+            )?;
+            let b = BigNat::alloc_from_nat(
+                cs.namespace(|| "b"),
+                || Ok(self.inputs.grab()?.b.clone()),
+                32,
+                64,
+            )?;
+            let expected = BigNat::alloc_from_nat(
+                cs.namespace(|| "expected"),
+                || Ok(self.inputs.grab()?.diff.clone()),
+                32,
+                64,
+            )?;
+            let diff = a.sub(cs.namespace(|| "sub"), &b)?;
+            diff.equal(cs.namespace(|| "check"), &expected)
+        }
+    }
+
+    fn sub_inputs(diff_offset: usize) -> SubInputs {
+        let a = BigUint::from_str(RSA_2048).unwrap();
+        let b = (&a >> 1) + 12345usize;
+        SubInputs {
+            diff: &a - &b + diff_offset,
+            a,
+            b,
+        }
+    }
+
+    circuit_tests! {
+        sub_2048: (Sub { inputs: Some(sub_inputs(0)) }, true),
+        sub_2048_wrong_diff: (Sub { inputs: Some(sub_inputs(1)) }, false),
+        sub_2048_equal: (
+            Sub {
+                inputs: Some(SubInputs {
+                    a: BigUint::from_str(RSA_2048).unwrap(),
+                    b: BigUint::from_str(RSA_2048).unwrap(),
+                    diff: BigUint::from(0usize),
+                }),
+            },
+            true),
+    }
+
+    #[test]
+    fn sub_2048_underflow() {
+        let SubInputs { a, b, .. } = sub_inputs(0);
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = Sub {
+            inputs: Some(SubInputs {
+                a: b,
+                b: a,
+                diff: BigUint::from(0usize),
+            }),
+        };
+        assert!(circuit.synthesize(&mut cs).is_err());
+    }
+
+    pub struct InverseModInputs<'a> {
+        pub a: &'a str,
+        pub m: &'a str,
+    }
+
+    pub struct InverseMod<'a> {
+        inputs: Option<InverseModInputs<'a>>,
+    }
+
+    impl<'a, E: Engine> Circuit<E> for InverseMod<'a> {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = BigNat::alloc_from_nat(
+                cs.namespace(|| "a"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.a).unwrap()),
+                32,
+                16,
+            )?;
+            let m = BigNat::alloc_from_nat(
+                cs.namespace(|| "m"),
+                || Ok(BigUint::from_str(self.inputs.grab()?.m).unwrap()),
+                32,
+                16,
+            )?;
+            let inverse = a.inverse_mod(cs.namespace(|| "inverse"), &m)?;
+            let (_, product) = a.mult_mod(cs.namespace(|| "product"), &inverse, &m)?;
+            product.equal(cs.namespace(|| "product is one"), &BigNat::identity::<CS>(32))
+        }
+    }
+
+    circuit_tests! {
+        inverse_mod_rsa_512: (
+            InverseMod {
+                inputs: Some(InverseModInputs {
+                    a: "123456789012345678901234567890123456789",
+                    m: RSA_512,
+                }),
+            },
+            true),
+        inverse_mod_rsa_512_of_one: (
+            InverseMod {
+                inputs: Some(InverseModInputs {
+                    a: "1",
+                    m: RSA_512,
+                }),
+            },
+            true),
+    }
+
+    #[test]
+    fn inverse_mod_value() {
+        let a = BigUint::from_str("123456789012345678901234567890123456789").unwrap();
+        let m = BigUint::from_str(RSA_512).unwrap();
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let a_nat =
+            BigNat::<Bn256>::alloc_from_nat(cs.namespace(|| "a"), || Ok(a.clone()), 32, 16)
+                .unwrap();
+        let m_nat =
+            BigNat::alloc_from_nat(cs.namespace(|| "m"), || Ok(m.clone()), 32, 16).unwrap();
+        let inverse = a_nat
+            .inverse_mod(cs.namespace(|| "inverse"), &m_nat)
+            .unwrap()
+            .value
+            .unwrap();
+        assert!(inverse < m);
+        assert_eq!(a * inverse % m, BigUint::one());
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn inverse_mod_not_coprime() {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let circuit = InverseMod {
+            inputs: Some(InverseModInputs {
+                a: "6",
+                m: "9",
+            }),
+        };
+        assert!(circuit.synthesize(&mut cs).is_err());
+    }
+
+    /// Whether `is_equal` finds `a` and `b` equal, as 64 32-bit limbs. Checks the circuit is
+    /// satisfied.
+    fn is_equal_2048(a: &BigUint, b: &BigUint) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let a = BigNat::alloc_from_nat(cs.namespace(|| "a"), || Ok(a.clone()), 32, 64).unwrap();
+        let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(b.clone()), 32, 64).unwrap();
+        let eq = a.is_equal(cs.namespace(|| "eq"), &b).unwrap();
+        assert!(cs.is_satisfied());
+        eq.get_value().unwrap()
+    }
+
+    #[test]
+    fn is_equal_2048_bit() {
+        let m = BigUint::from_str(RSA_2048).unwrap();
+        assert!(is_equal_2048(&m, &m));
+        assert!(!is_equal_2048(&m, &(&m - 1usize)));
+        assert!(!is_equal_2048(&m, &(&m - (BigUint::one() << 2047))));
+        assert!(!is_equal_2048(&m, &(&m + (BigUint::one() << 1024))));
+    }
+
+    #[test]
+    fn conditionally_select_512_bit() {
+        use sapling_crypto::circuit::boolean::AllocatedBit;
+
+        let a = BigUint::from_str(RSA_512).unwrap();
+        let b = &a - (BigUint::one() << 300);
+        for cond in &[true, false] {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let a_nat =
+                BigNat::<Bn256>::alloc_from_nat(cs.namespace(|| "a"), || Ok(a.clone()), 32, 16)
+                    .unwrap();
+            let b_nat =
+                BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(b.clone()), 32, 16).unwrap();
+            let bit = AllocatedBit::alloc(cs.namespace(|| "cond"), Some(*cond)).unwrap();
+            let selected = BigNat::conditionally_select(
+                cs.namespace(|| "select"),
+                &a_nat,
+                &b_nat,
+                &Boolean::from(bit),
+            )
+            .unwrap();
+            let expected = if *cond { &a_nat } else { &b_nat };
+            assert_eq!(selected.value, expected.value);
+            assert_eq!(selected.params.n_limbs, 16);
+            selected
+                .equal(cs.namespace(|| "selected"), expected)
+                .unwrap();
+            assert!(cs.is_satisfied());
+        }
+    }
+
+    /// Whether `n`, as 5 32-bit limbs, satisfies `assert_bit_length(n_bits)`.
+    fn has_bit_length(n: BigUint, n_bits: usize) -> bool {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let nat = BigNat::alloc_from_nat(cs.namespace(|| "n"), || Ok(n), 32, 5).unwrap();
+        nat.assert_bit_length(cs.namespace(|| "bits"), n_bits)
+            .unwrap();
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn assert_bit_length_bounds() {
+        let two_to = |k: usize| BigUint::one() << k;
+        assert!(has_bit_length(two_to(129), 130));
+        assert!(has_bit_length(two_to(130) - 1usize, 130));
+        assert!(!has_bit_length(two_to(129), 128));
+        assert!(!has_bit_length(two_to(130) - 1usize, 128));
+        assert!(has_bit_length(two_to(128) - 1usize, 128));
+        assert!(!has_bit_length(two_to(128), 128));
+        assert!(has_bit_length(two_to(99), 100));
+        assert!(!has_bit_length(two_to(100), 100));
+        assert!(has_bit_length(BigUint::from(0usize), 1));
+        assert!(!has_bit_length(BigUint::from(1usize), 0));
+    }
+
+    /// Checks `divmod` against `div_rem`, with `a` in 4 32-bit limbs and `d` in 3.
+    fn check_divmod(a: BigUint, d: BigUint) {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let a_nat =
+            BigNat::<Bn256>::alloc_from_nat(cs.namespace(|| "a"), || Ok(a.clone()), 32, 4)
+                .unwrap();
+        let d_nat = BigNat::alloc_from_nat(cs.namespace(|| "d"), || Ok(d.clone()), 32, 3).unwrap();
+        let (q, r) = a_nat.divmod(cs.namespace(|| "divmod"), &d_nat).unwrap();
+        let (expected_q, expected_r) = a.div_rem(&d);
+        assert_eq!(q.value, Some(expected_q));
+        assert_eq!(r.value, Some(expected_r));
+        assert!(cs.is_satisfied());
+    }
+
+    #[quickcheck]
+    fn qc_divmod(a: Vec<u32>, d: Vec<u32>) -> TestResult {
+        let a = BigUint::new(a.into_iter().take(4).collect());
+        let d = BigUint::new(d.into_iter().take(3).collect());
+        if d.is_zero() {
+            return TestResult::discard();
+        }
+        check_divmod(a, d);
+        TestResult::passed()
+    }
+
+    #[test]
+    fn divmod_by_larger_divisor() {
+        check_divmod(BigUint::from(12345usize), BigUint::from(1usize) << 80);
+        check_divmod(BigUint::from(0usize), BigUint::from(7usize));
+    }
+
+    #[test]
+    fn divmod_by_zero() {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let nat = |cs: &mut TestConstraintSystem<Bn256>, name: &str, n: usize| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(BigUint::from(n)), 32, 1).unwrap()
+        };
+        let a = nat(&mut cs, "a", 5);
+        let d = nat(&mut cs, "d", 0);
+        assert!(a.divmod(cs.namespace(|| "divmod"), &d).is_err());
+    }
+
+    #[test]
+    fn from_allocated_limbs_64_bit() {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let words: [u64; 4] = [
+            0x0123_4567_89ab_cdef,
+            0xffff_ffff_ffff_ffff,
+            0,
+            0x8000_0000_0000_0001,
+        ];
+        let limbs = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                AllocatedNum::alloc(cs.namespace(|| format!("limb {}", i)), || {
+                    Ok(nat_to_f(&BigUint::from(*w)).unwrap())
+                })
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let nat = BigNat::from_allocated_limbs(&limbs, 64);
+        let expected_value = words
+            .iter()
+            .rev()
+            .fold(BigUint::zero(), |acc, w| (acc << 64) + *w);
+        assert_eq!(nat.value, Some(expected_value.clone()));
+        assert_eq!(nat.params.n_limbs, 4);
+        let expected =
+            BigNat::alloc_from_nat(cs.namespace(|| "expected"), || Ok(expected_value), 64, 4)
+                .unwrap();
+        nat.equal(cs.namespace(|| "equal"), &expected).unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    /// Constraints in `a * b % m` for 512-bit operands in `limb_width`-bit limbs, with the product
+    /// carried `limbs_per_group` limbs at a time.
+    fn mult_mod_constraints(
+        limb_width: usize,
+        limbs_per_group: Option<usize>,
+    ) -> Result<usize, SynthesisError> {
+        let m = BigUint::from_str(RSA_512).unwrap();
+        let a = &m - 12345usize;
+        let b = &m - (BigUint::one() << 300);
+        let expected = &a * &b % &m;
+        let n_limbs = 512 / limb_width;
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let mut nat = |name: &str, n: BigUint| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(n), limb_width, n_limbs).unwrap()
+        };
+        let (a, b, m) = (nat("a", a), nat("b", b), nat("m", m));
+        let (_, r) = a.mult_mod_grouped(cs.namespace(|| "mult_mod"), &b, &m, limbs_per_group)?;
+        assert_eq!(r.value, Some(expected));
+        assert!(cs.is_satisfied());
+        let n = cs.num_constraints();
+        record_constraint_count(
+            &format!("mult_mod_{}_bit_limbs_grouped_{:?}", limb_width, limbs_per_group),
+            n,
+        );
+        Ok(n)
+    }
+
+    #[test]
+    fn mult_mod_grouping_constraint_counts() {
+        for limb_width in &[32, 16] {
+            let one = mult_mod_constraints(*limb_width, Some(1)).unwrap();
+            let two = mult_mod_constraints(*limb_width, Some(2)).unwrap();
+            let largest = mult_mod_constraints(*limb_width, None).unwrap();
+            println!(
+                "mult_mod with {}-bit limbs: {} constraints with 1 limb per group, {} with 2, {} \
+                 with the largest groups",
+                limb_width, one, two, largest
+            );
+            assert!(largest < two && two < one);
+            assert!(mult_mod_constraints(*limb_width, Some(0)).is_err());
+            assert!(mult_mod_constraints(*limb_width, Some(256 / limb_width)).is_err());
+        }
+    }
+
+    #[test]
+    fn limb_metadata_getters() {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let n = BigNat::alloc_from_nat(
+            cs.namespace(|| "n"),
+            || Ok(BigUint::from(12345usize)),
+            32,
+            4,
+        )
+        .unwrap();
+        assert_eq!(n.n_limbs(), 4);
+        assert_eq!(n.limb_width(), 32);
+        assert_eq!(n.max_bits(), 128);
+        let sum = n.add::<TestConstraintSystem<Bn256>>(&n).unwrap();
+        assert_eq!(sum.max_bits(), 128);
+        assert_eq!(sum.n_bits(), 129);
+    }
+
+    fn carried_sum_cs(a: usize, b: usize, sum: usize) -> TestConstraintSystem<Bn256> {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let alloc = |cs: &mut TestConstraintSystem<Bn256>, name: &str, v: usize, n_limbs: usize| {
+            BigNat::alloc_from_nat(cs.namespace(|| name), || Ok(BigUint::from(v)), 4, n_limbs)
+                .unwrap()
+        };
+        let a = alloc(&mut cs, "a", a, 3);
+        let b = alloc(&mut cs, "b", b, 3);
+        let sum = alloc(&mut cs, "sum", sum, 4);
+        a.add::<TestConstraintSystem<Bn256>>(&b)
+            .unwrap()
+            .equal_when_carried(cs.namespace(|| "eq"), &sum)
+            .unwrap();
+        cs
+    }
+
+    #[test]
+    fn equal_when_carried_only_allocates_carry_bits() {
+        let cs = carried_sum_cs(0xabc, 0x777, 0x1233);
+        assert!(cs.is_satisfied());
+        // Two 2-bit carry range checks (2 constraints each), the final carry, and one zero limb.
+        // Allocating the three carries would add a variable and a constraint for each.
+        assert_eq!(cs.num_constraints(), 6);
+    }
+
+    #[test]
+    fn equal_when_carried_wrong_sum() {
+        assert!(!carried_sum_cs(0xabc, 0x777, 0x1234).is_satisfied());
+        assert!(!carried_sum_cs(0xabc, 0x777, 0x0233).is_satisfied());
+    }
+
+    // From my machine (openssl)
+    const RSA_512: &str = "11834783464130424096695514462778870280264989938857328737807205623069291535525952722847913694296392927890261736769191982212777933726583565708193466779811767";
+    // From https://en.wikipedia.org/wiki/RSA_numbers#RSA-2048
+    const RSA_2048: &str = "25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525880784406918290641249515082189298559149176184502808489120072844992687392807287776735971418347270261896375014971824691165077613379859095700097330459748808428401797429100642458691817195118746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739934236584823824281198163815010674810451660377306056201619676256133844143603833904414952634432190114657544454178424020924616515723350778707749817125772467962926386356373289912154831438167899885040445364023527381951378636564391212010397122822120720357";
+
+    #[test]
+    fn bytes_round_trip_2048() {
+        let m = BigUint::from_str(RSA_2048).unwrap();
+        let digest = BigUint::from(2usize).modpow(&BigUint::from(65537usize), &m);
+        let bytes = nat_to_bytes_be(&digest, 2048).unwrap();
+        assert_eq!(bytes.len(), 256);
+        assert_eq!(nat_from_bytes_be(&bytes), digest);
+    }
+
+    #[test]
+    fn bytes_preserve_leading_zeros() {
+        let n = BigUint::from(0x0102usize);
+        let bytes = nat_to_bytes_be(&n, 32).unwrap();
+        assert_eq!(bytes, vec![0, 0, 1, 2]);
+        assert_eq!(nat_from_bytes_be(&bytes), n);
+        assert_eq!(nat_to_bytes_be(&BigUint::from(0usize), 16).unwrap(), vec![0, 0]);
+    }
+
+    #[test]
+    fn bytes_too_big() {
+        assert!(nat_to_bytes_be(&BigUint::from(0x10000usize), 16).is_err());
+    }
+
+    #[test]
+    fn limbs_round_trip_random_2048() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let bytes: Vec<u8> = (0..256).map(|_| rng.gen()).collect();
+            let n = BigUint::from_bytes_be(&bytes);
+            let limbs: Vec<Fr> = nat_to_limbs(&n, 32, 64).unwrap();
+            assert_eq!(limbs_to_nat_checked(&limbs, 32), Ok(n));
+        }
+    }
+
+    #[test]
+    fn limbs_too_wide() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        let limbs: Vec<Fr> = vec![usize_to_f(3), usize_to_f(1 << 32), usize_to_f(1)];
+        assert_eq!(
+            limbs_to_nat_checked(&limbs, 32),
+            Err(LimbError::LimbTooWide {
+                index: 1,
+                have: 33,
+                max: 32
+            })
+        );
+    }
+
+    #[test]
+    fn limbs_too_many_bits() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        let m = BigUint::from_str(RSA_2048).unwrap();
+        assert_eq!(
+            nat_to_limbs::<Fr>(&m, 32, 32),
+            Err(LimbError::TooManyBits {
+                have: 2048,
+                max: 1024
+            })
+        );
+    }
+
+    #[test]
+    fn limbs_bytes_round_trip_2048() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        let m = BigUint::from_str(RSA_2048).unwrap();
+        let limbs: Vec<Fr> = nat_to_limbs(&m, 32, 64).unwrap();
+        let bytes = limbs_to_bytes(&limbs, 32).unwrap();
+        assert_eq!(bytes.len(), 256);
+        assert_eq!(bytes_to_limbs::<Fr>(&bytes, 32, 64).unwrap(), limbs);
+    }
+
+    #[test]
+    fn limbs_bytes_partial_top_limb() {
+        use sapling_crypto::bellman::pairing::bn256::Fr;
+        // 3 limbs of width 5 is 15 bits, which needs 2 bytes.
+        let n = BigUint::from(0x7abcusize);
+        let limbs: Vec<Fr> = nat_to_limbs(&n, 5, 3).unwrap();
+        let bytes = limbs_to_bytes(&limbs, 5).unwrap();
+        assert_eq!(bytes, vec![0x7a, 0xbc]);
+        assert_eq!(bytes_to_limbs::<Fr>(&bytes, 5, 3).unwrap(), limbs);
+    }
 }
 
 impl<E: Engine> Display for BigNat<E> {
@@ -15,7 +15,7 @@ use rollup::sig::allocate_point;
 use rollup::tx::circuit::{CircuitAccount, CircuitSignedTx};
 use rollup::tx::{Account, Action, SignedTx, Tx, TxAccountChanges};
 use set::merkle::{MerkleCircuitSet, MerkleSet};
-use set::{CircuitGenSet, GenSet};
+use set::{CircuitGenSet, GenSet, MissingItem};
 use util::convert::usize_to_f;
 use util::gadget::Gadget;
 use CResult;
@@ -48,10 +48,10 @@ where
         }
     }
 
-    pub fn swap(&mut self, a: Account<E>, b: Account<E>) {
+    pub fn swap(&mut self, a: Account<E>, b: Account<E>) -> Result<(), MissingItem> {
         let mut key = Vec::new();
         a.id.write(&mut key).unwrap();
-        self.set.swap(&a.as_elems(), b.as_elems());
+        self.set.swap(&a.as_elems(), b.as_elems())
     }
 
     pub fn get(&self, k: &PublicKey<E>) -> Option<&Account<E>> {
@@ -80,8 +80,8 @@ where
             dst.amt = dst.amt.checked_add(t.action.amt)?;
             dst
         };
-        self.swap(src_init.clone(), src_final.clone());
-        self.swap(dst_init.clone(), dst_final.clone());
+        self.swap(src_init.clone(), src_final.clone()).ok()?;
+        self.swap(dst_init.clone(), dst_final.clone()).ok()?;
         Some(TxAccountChanges {
             src_init,
             src_final,
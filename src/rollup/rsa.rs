@@ -2,6 +2,7 @@ use rand::Rng;
 
 use num_bigint::BigUint;
 
+use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{Circuit, ConstraintSystem, SynthesisError};
 use sapling_crypto::circuit::ecc::EdwardsPoint;
 use sapling_crypto::circuit::num::AllocatedNum;
@@ -10,16 +11,17 @@ use sapling_crypto::jubjub::edwards::Point;
 use sapling_crypto::jubjub::{FixedGenerators, JubjubEngine, JubjubParams, PrimeOrder};
 
 use group::{CircuitRsaGroupParams, CircuitRsaQuotientGroup, RsaQuotientGroup};
-use hash::circuit::CircuitHasher;
+use hash::circuit::{CircuitHasher, MaybeHashed};
 use hash::hashes::Pedersen;
-use hash::{self, division_intractable as di, Hasher};
+use hash::{division_intractable as di, Hasher};
 use mp::bignat::BigNat;
 use rollup::sig::allocate_point;
 use rollup::tx::circuit::{CircuitAccount, CircuitSignedTx};
 use rollup::tx::{Account, Action, SignedTx, Tx, TxAccountChanges};
 use set::int_set::NaiveExpSet;
-use set::rsa::{CircuitSet, CircuitSetParams, Set};
+use set::rsa::{CircuitSet, CircuitSetParams, ParamError, Set};
 use set::{CircuitGenSet, GenSet};
+use util::bench::ConstraintCounter;
 use util::convert::usize_to_f;
 use util::gadget::Gadget;
 use CResult;
@@ -29,6 +31,8 @@ use std::collections::HashMap;
 use std::rc::Rc;
 use std::str::FromStr;
 
+pub use set::rsa::{shared_challenge, shared_challenge_value};
+
 const RSA_2048: &str = "25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525880784406918290641249515082189298559149176184502808489120072844992687392807287776735971418347270261896375014971824691165077613379859095700097330459748808428401797429100642458691817195118746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739934236584823824281198163815010674810451660377306056201619676256133844143603833904414952634432190114657544454178424020924616515723350778707749817125772467962926386356373289912154831438167899885040445364023527381951378636564391212010397122822120720357";
 
 #[derive(Derivative)]
@@ -56,6 +60,7 @@ where
                 di::offset(s.n_bits_elem),
                 s.hasher.clone(),
                 s.n_bits_elem,
+                s.n_trailing_ones,
                 s.limb_width,
                 vec![],
             ),
@@ -244,15 +249,41 @@ where
     }
 }
 
+#[derive(Clone)]
 pub struct RsaParams<H> {
     pub group: RsaQuotientGroup,
     pub limb_width: usize,
     pub n_bits_base: usize,
     pub n_bits_elem: usize,
+    /// The number of low bits of each account element which are fixed to one.
+    pub n_trailing_ones: usize,
     pub n_bits_challenge: usize,
+    /// The number of independent challenges. The swap is checked once for each, so a cheating
+    /// prover must fool every check.
+    pub n_challenges: usize,
     pub hasher: H,
 }
 
+impl<H> RsaParams<H> {
+    /// Checks that the base fills a whole number of limbs, and holds the elements, and that there
+    /// is a challenge.
+    pub fn validate(&self) -> Result<(), ParamError> {
+        CircuitRsaGroupParams::new(self.limb_width, self.n_bits_base)?;
+        if self.n_bits_elem > self.n_bits_base {
+            return Err(ParamError::ElemTooWide {
+                n_bits_elem: self.n_bits_elem,
+                n_bits_base: self.n_bits_base,
+            });
+        }
+        if self.n_challenges == 0 {
+            return Err(ParamError::NoChallenges);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""))]
 pub struct RollupBenchParams<E, H>
 where
     E: JubjubEngine,
@@ -279,21 +310,19 @@ where
     E: JubjubEngine,
     H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
 {
+    /// A rollup of `t` transactions among `c` accounts, accumulated in an RSA-2048 group with
+    /// 32-bit limbs.
     pub fn from_counts(
         c: usize,
         t: usize,
         jj_params: <E as JubjubEngine>::Params,
         set_hash: H,
     ) -> Self {
-        let jj_params = Rc::new(jj_params);
-        let params = RollupBenchParams {
-            jj_params: jj_params.clone(),
-            sig_hasher: Pedersen {
-                params: jj_params.clone(),
-            },
-            gen: FixedGenerators::SpendingKeyGenerator,
-            n_tx: t,
-            set_params: RsaParams {
+        Self::from_counts_with_set_params(
+            c,
+            t,
+            jj_params,
+            RsaParams {
                 group: RsaQuotientGroup {
                     g: BigUint::from(2usize),
                     m: BigUint::from_str(RSA_2048).unwrap(),
@@ -301,23 +330,82 @@ where
                 limb_width: 32,
                 n_bits_base: 2048,
                 n_bits_challenge: 256,
+                n_challenges: 1,
                 n_bits_elem: 2048,
+                n_trailing_ones: 1,
                 hasher: set_hash,
             },
+        )
+        .expect("RSA-2048 with 32-bit limbs is valid")
+    }
+
+    /// A rollup of `t` transactions among `c` accounts, with the given accumulator parameters.
+    /// `set_params.n_bits_base` must be a multiple of `set_params.limb_width`, and at least
+    /// `set_params.n_bits_elem`. Elements may be much narrower than the base, which makes hashing
+    /// to them cheaper. There must be at least one challenge.
+    pub fn from_counts_with_set_params(
+        c: usize,
+        t: usize,
+        jj_params: <E as JubjubEngine>::Params,
+        set_params: RsaParams<H>,
+    ) -> Result<Self, ParamError> {
+        set_params.validate()?;
+        let jj_params = Rc::new(jj_params);
+        let params = RollupBenchParams {
+            jj_params: jj_params.clone(),
+            sig_hasher: Pedersen {
+                params: jj_params.clone(),
+            },
+            gen: FixedGenerators::SpendingKeyGenerator,
+            n_tx: t,
+            set_params,
         };
-        Self {
+        Ok(Self {
             input: Some(RollupBenchInputs::from_counts(c, t, &params)),
             params,
+        })
+    }
+
+    /// The number of constraints in a rollup with these parameters.
+    ///
+    /// The circuit is synthesized without a witness, so this is exact, but skips both witness
+    /// computation and parameter generation.
+    pub fn estimate_constraints(params: &RollupBenchParams<E, H>) -> CResult<usize> {
+        params.set_params.validate()?;
+        let mut cs = ConstraintCounter::new();
+        Self {
+            input: None,
+            params: params.clone(),
         }
+        .synthesize(&mut cs)?;
+        Ok(cs.num_constraints())
     }
 }
 
+/// An item holding the constant `i`, which makes the `i`th challenge independent of the others.
+fn challenge_index<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    i: usize,
+) -> CResult<MaybeHashed<E>> {
+    let value: E::Fr = usize_to_f(i);
+    let index = AllocatedNum::alloc(cs.namespace(|| "alloc"), || Ok(value))?;
+    cs.enforce(
+        || "constant",
+        |lc| lc,
+        |lc| lc,
+        |lc| lc + index.get_variable() - (value, CS::one()),
+    );
+    Ok(MaybeHashed::from_values(vec![index]))
+}
+
 impl<E, H> Circuit<E> for RollupBench<E, H>
 where
     E: JubjubEngine,
     H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
 {
     fn synthesize<CS: ConstraintSystem<E>>(mut self, cs: &mut CS) -> CResult<()> {
+        // Without a challenge, nothing would be checked or inputized.
+        self.params.set_params.validate()?;
         let gen_value = self
             .params
             .jj_params
@@ -376,7 +464,7 @@ where
                     .set_params
                     .hasher
                     .allocate_hash(cs.namespace(|| format!("insert hash {}", i)), &elems)?;
-                Ok(hash::circuit::MaybeHashed::new(elems, hash))
+                Ok(MaybeHashed::new(elems, hash))
             })
             .collect::<CResult<Vec<_>>>()?;
         let removals = removed_accounts
@@ -389,7 +477,7 @@ where
                     .set_params
                     .hasher
                     .allocate_hash(cs.namespace(|| format!("remove hash {}", i)), &elems)?;
-                Ok(hash::circuit::MaybeHashed::new(elems, hash))
+                Ok(MaybeHashed::new(elems, hash))
             })
             .collect::<CResult<Vec<_>>>()?;
 
@@ -415,73 +503,332 @@ where
             self.params.set_params.n_bits_base / self.params.set_params.limb_width,
         )?;
 
-        let mut to_hash_to_challenge: Vec<AllocatedNum<E>> = Vec::new();
-        to_hash_to_challenge.extend(
-            expected_initial_digest
-                .as_limbs::<CS>()
-                .into_iter()
-                .enumerate()
-                .map(|(i, n)| {
-                    n.as_sapling_allocated_num(cs.namespace(|| format!("digest hash {}", i)))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        );
-        to_hash_to_challenge.extend(
-            expected_final_digest
-                .as_limbs::<CS>()
-                .into_iter()
-                .enumerate()
-                .map(|(i, n)| {
-                    n.as_sapling_allocated_num(cs.namespace(|| format!("digest hash {}", i)))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        );
-        to_hash_to_challenge.extend(insertions.iter().map(|i| i.hash.clone().unwrap()));
-        to_hash_to_challenge.extend(removals.iter().map(|i| i.hash.clone().unwrap()));
-        let challenge = hash::pocklington::hash_to_pocklington_prime(
-            cs.namespace(|| "challenge hash"),
-            &to_hash_to_challenge,
-            self.params.set_params.limb_width,
-            self.params.set_params.n_bits_challenge,
-            &self.params.set_params.hasher,
-        )?;
+        let mut items: Vec<_> = insertions.iter().chain(removals.iter()).cloned().collect();
+        let challenges = (0..self.params.set_params.n_challenges)
+            .map(|i| {
+                let mut cs = cs.namespace(|| format!("challenge {}", i));
+                let mut indexed;
+                let items = if i == 0 {
+                    &mut items
+                } else {
+                    indexed = items.clone();
+                    indexed.push(challenge_index(cs.namespace(|| "index"), i)?);
+                    &mut indexed
+                };
+                shared_challenge(
+                    cs.namespace(|| "hash"),
+                    &[&expected_initial_digest, &expected_final_digest],
+                    items,
+                    self.params.set_params.limb_width,
+                    self.params.set_params.n_bits_challenge,
+                    &self.params.set_params.hasher,
+                )
+            })
+            .collect::<CResult<Vec<_>>>()?;
 
         let raw_group = self.input.as_ref().map(|s| s.accounts.set.group().clone());
         let group = CircuitRsaQuotientGroup::alloc(
             cs.namespace(|| "group"),
             raw_group.as_ref(),
             (),
-            &CircuitRsaGroupParams {
-                limb_width: self.params.set_params.limb_width,
-                n_limbs: self.params.set_params.n_bits_base / self.params.set_params.limb_width,
-            },
+            &CircuitRsaGroupParams::new(
+                self.params.set_params.limb_width,
+                self.params.set_params.n_bits_base,
+            )?,
         )?;
         group.inputize(cs.namespace(|| "group input"))?;
-
-        let set: CircuitSet<E, H, CircuitRsaQuotientGroup<E>, NaiveExpSet<RsaQuotientGroup>> =
-            CircuitSet::alloc(
-                cs.namespace(|| "set init"),
-                self.input.as_ref().map(|is| &is.accounts.set),
-                (group, challenge),
-                &CircuitSetParams {
-                    hasher: self.params.set_params.hasher.clone(),
-                    n_bits: self.params.set_params.n_bits_elem,
-                    limb_width: self.params.set_params.limb_width,
-                },
-            )?;
-        set.inputize(cs.namespace(|| "initial_state input"))?;
-        set.inner.digest.equal(
-            cs.namespace(|| "initial digest matches"),
+        group.enforce_valid_generator(cs.namespace(|| "group generator"))?;
+        let expected_initial_digest = group.canonicalize(
+            cs.namespace(|| "canonical initial digest"),
             &expected_initial_digest,
         )?;
 
-        let new_set = set.swap_all(cs.namespace(|| "swap"), removals, insertions)?;
+        // Each challenge checks the whole swap. Only the sets of the first check are inputs:
+        // those of the others are equal to them.
+        for (i, challenge) in challenges.into_iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("check {}", i));
+            let set: CircuitSet<E, H, CircuitRsaQuotientGroup<E>, NaiveExpSet<RsaQuotientGroup>> =
+                CircuitSet::alloc(
+                    cs.namespace(|| "set init"),
+                    self.input.as_ref().map(|is| &is.accounts.set),
+                    (group.clone(), challenge),
+                    &CircuitSetParams {
+                        hasher: self.params.set_params.hasher.clone(),
+                        n_bits: self.params.set_params.n_bits_elem,
+                        n_trailing_ones: self.params.set_params.n_trailing_ones,
+                        limb_width: self.params.set_params.limb_width,
+                    },
+                )?;
+            if i == 0 {
+                set.inputize(cs.namespace(|| "initial_state input"))?;
+            }
+            set.inner.digest.equal(
+                cs.namespace(|| "initial digest matches"),
+                &expected_initial_digest,
+            )?;
 
-        new_set
-            .inner
-            .digest
-            .equal(cs.namespace(|| "check"), &expected_final_digest)?;
-        new_set.inputize(cs.namespace(|| "final_state input"))?;
+            // The final proof of exponentiation checks the resulting digest against the expected
+            // one, which is bound to the challenge.
+            let new_set = set.swap_all_to(
+                cs.namespace(|| "swap"),
+                removals.clone(),
+                insertions.clone(),
+                expected_final_digest.clone(),
+            )?;
+            if i == 0 {
+                new_set.inputize(cs.namespace(|| "final_state input"))?;
+            }
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use sapling_crypto::bellman::pairing::bls12_381::Bls12;
+    use sapling_crypto::bellman::pairing::bn256::Bn256;
+    use sapling_crypto::bellman::pairing::ff::{PrimeField, ScalarEngine};
+    use sapling_crypto::circuit::test::TestConstraintSystem;
+    use sapling_crypto::jubjub::JubjubBls12;
+
+    use group::RsaGroupParamsError;
+    use hash::hashes::Poseidon;
+    use hash::pocklington::helper::PocklingtonPlan;
+    use io::small_set::RSA_512;
+
+    /// Synthesizes the shared challenge for the given digests and batches, optionally checking it
+    /// against `expected`. Returns the challenge and whether the constraints are satisfied.
+    fn synth_challenge(
+        digests: &[&str],
+        batches: &[&[&str]],
+        expected: Option<&BigUint>,
+    ) -> (BigUint, bool) {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let digests = digests
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                BigNat::alloc_from_nat(
+                    cs.namespace(|| format!("digest {}", i)),
+                    || Ok(BigUint::from_str(d).unwrap()),
+                    32,
+                    4,
+                )
+            })
+            .collect::<CResult<Vec<_>>>()
+            .unwrap();
+        let mut items = Vec::new();
+        for (i, batch) in batches.iter().enumerate() {
+            for (j, item) in batch.iter().enumerate() {
+                let n = AllocatedNum::alloc(cs.namespace(|| format!("item {} {}", i, j)), || {
+                    Ok(<Bn256 as ScalarEngine>::Fr::from_str(item).unwrap())
+                })
+                .unwrap();
+                items.push(MaybeHashed::from_values(vec![n]));
+            }
+        }
+        let challenge = shared_challenge(
+            cs.namespace(|| "challenge"),
+            &digests.iter().collect::<Vec<_>>(),
+            &mut items,
+            32,
+            128,
+            &Poseidon::<Bn256>::default(),
+        )
+        .unwrap();
+        if let Some(expected) = expected {
+            let expected = BigNat::alloc_from_nat(
+                cs.namespace(|| "expected"),
+                || Ok(expected.clone()),
+                32,
+                (PocklingtonPlan::new(128).max_bits() - 1) / 32 + 1,
+            )
+            .unwrap();
+            challenge.equal(cs.namespace(|| "eq"), &expected).unwrap();
+        }
+        (challenge.value.unwrap(), cs.is_satisfied())
+    }
+
+    #[test]
+    fn shared_challenge_binds_every_batch() {
+        let digests = ["2", "3", "5"];
+        let (challenge, sat) = synth_challenge(&digests, &[&["1", "2"], &["3", "4"]], None);
+        assert!(sat);
+        let (same, sat) =
+            synth_challenge(&digests, &[&["1", "2"], &["3", "4"]], Some(&challenge));
+        assert_eq!(same, challenge);
+        assert!(sat);
+
+        let (first_altered, sat) =
+            synth_challenge(&digests, &[&["1", "9"], &["3", "4"]], Some(&challenge));
+        assert_ne!(first_altered, challenge);
+        assert!(!sat);
+
+        let (second_altered, sat) =
+            synth_challenge(&digests, &[&["1", "2"], &["3", "9"]], Some(&challenge));
+        assert_ne!(second_altered, challenge);
+        assert!(!sat);
+
+        let (digest_altered, sat) =
+            synth_challenge(&["2", "3", "7"], &[&["1", "2"], &["3", "4"]], Some(&challenge));
+        assert_ne!(digest_altered, challenge);
+        assert!(!sat);
+    }
+
+    /// A one-transaction rollup over RSA-512 with generator `g`.
+    fn small_rollup(limb_width: usize, g: usize) -> RollupBench<Bls12, Poseidon<Bls12>> {
+        RollupBench::from_counts_with_set_params(
+            2,
+            1,
+            JubjubBls12::new(),
+            RsaParams {
+                group: RsaQuotientGroup::new(BigUint::from(g), BigUint::from_str(RSA_512).unwrap())
+                    .unwrap(),
+                limb_width,
+                n_bits_base: 512,
+                n_bits_challenge: 128,
+                n_challenges: 1,
+                n_bits_elem: 128,
+                n_trailing_ones: 1,
+                hasher: Poseidon::default(),
+            },
+        )
+        .unwrap()
+    }
+
+    /// Synthesizes `small_rollup(limb_width, g)`, returning whether it is satisfied.
+    fn small_rollup_is_satisfied(limb_width: usize, g: usize) -> bool {
+        let circuit = small_rollup(limb_width, g);
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        println!(
+            "Constraints with {}-bit limbs and generator {}: {}",
+            limb_width,
+            g,
+            cs.num_constraints()
+        );
+        if !cs.is_satisfied() {
+            println!("UNSAT: {:#?}", cs.which_is_unsatisfied())
+        }
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn rollup_with_64_bit_limbs() {
+        assert!(small_rollup_is_satisfied(64, 2));
+    }
+
+    #[test]
+    fn rollup_params_are_validated() {
+        let params = small_rollup(32, 2).params.set_params;
+        assert_eq!(params.validate(), Ok(()));
+        let mut partial_limb = params.clone();
+        partial_limb.limb_width = 48;
+        assert_eq!(
+            partial_limb.validate(),
+            Err(ParamError::Limbs(RsaGroupParamsError::PartialLimb {
+                n_bits_base: 512,
+                limb_width: 48,
+            }))
+        );
+        let mut wide_elements = params.clone();
+        wide_elements.n_bits_elem = 1024;
+        assert_eq!(
+            wide_elements.validate(),
+            Err(ParamError::ElemTooWide {
+                n_bits_elem: 1024,
+                n_bits_base: 512,
+            })
+        );
+    }
+
+    #[test]
+    fn rollup_with_generator_3() {
+        assert!(small_rollup_is_satisfied(32, 3));
+    }
+
+    #[test]
+    fn rollup_with_elements_narrower_than_the_base() {
+        let circuit = small_rollup(32, 2);
+        assert_eq!(circuit.params.set_params.n_bits_elem, 128);
+        assert_eq!(circuit.params.set_params.n_bits_base, 512);
+        let mut wide = circuit.params.clone();
+        wide.set_params.n_bits_elem = 512;
+        let narrow_cost = RollupBench::estimate_constraints(&circuit.params).unwrap();
+        let wide_cost = RollupBench::estimate_constraints(&wide).unwrap();
+        assert!(narrow_cost < wide_cost);
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn rollup_with_two_challenges() {
+        let mut circuit = small_rollup(32, 2);
+        let cost = |n_challenges: usize| {
+            let mut params = circuit.params.clone();
+            params.set_params.n_challenges = n_challenges;
+            RollupBench::estimate_constraints(&params).unwrap()
+        };
+        // The transactions are checked once, and the swap once for each challenge.
+        let (one, two, three) = (cost(1), cost(2), cost(3));
+        assert!(one < two && two < 2 * one);
+        assert_eq!(three - two, two - one);
+
+        circuit.params.set_params.n_challenges = 2;
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn rollup_without_challenges_is_rejected() {
+        let mut circuit = small_rollup(32, 2);
+        circuit.params.set_params.n_challenges = 0;
+        assert_eq!(circuit.params.set_params.validate(), Err(ParamError::NoChallenges));
+        assert!(RollupBench::estimate_constraints(&circuit.params).is_err());
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        assert!(circuit.synthesize(&mut cs).is_err());
+    }
+
+    #[test]
+    fn constraint_estimate_matches_synthesis() {
+        let circuit = small_rollup(32, 2);
+        let estimate = RollupBench::estimate_constraints(&circuit.params).unwrap();
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert_eq!(estimate, cs.num_constraints());
+    }
+
+    #[test]
+    fn shared_challenge_value_matches_circuit() {
+        let value = |digests: &[&str], items: &[&str]| {
+            let digests: Vec<BigUint> =
+                digests.iter().map(|d| BigUint::from_str(d).unwrap()).collect();
+            let items: Vec<Vec<<Bn256 as ScalarEngine>::Fr>> = items
+                .iter()
+                .map(|i| vec![<Bn256 as ScalarEngine>::Fr::from_str(i).unwrap()])
+                .collect();
+            shared_challenge_value(
+                &digests.iter().collect::<Vec<_>>(),
+                &items,
+                32,
+                4,
+                128,
+                &Poseidon::<Bn256>::default(),
+            )
+            .unwrap()
+        };
+        let digests = ["2", "3", "5"];
+        let (challenge, _) = synth_challenge(&digests, &[&["1", "2"], &["3", "4"]], None);
+        assert_eq!(value(&digests, &["1", "2", "3", "4"]), challenge);
+        for altered in 0..4 {
+            let mut items = vec!["1", "2", "3", "4"];
+            items[altered] = "9";
+            assert_ne!(value(&digests, &items), challenge);
+        }
+    }
+}
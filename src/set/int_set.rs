@@ -1,9 +1,16 @@
-use num_bigint::BigUint;
+use num_bigint::{BigInt, BigUint, ToBigInt};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{ConstraintSystem, LinearCombination, SynthesisError};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::str::FromStr;
 
 use group::{CircuitSemiGroup, SemiGroup};
 use mp::bignat::BigNat;
@@ -27,6 +34,14 @@ pub trait IntSet: Sized + Clone + Eq + Debug {
     /// Gets the underlying RSA group
     fn group(&self) -> &Self::G;
 
+    /// The current elements, with repetition, in increasing order.
+    fn members(&self) -> Vec<BigUint>;
+
+    /// Whether `n` is in the set.
+    fn contains(&self, n: &BigUint) -> bool {
+        self.members().contains(n)
+    }
+
     /// Add all of the `ns` to the set. Returns whether all items were absent
     fn insert_all<I: IntoIterator<Item = BigUint>>(&mut self, ns: I) {
         for n in ns {
@@ -45,9 +60,29 @@ pub trait IntSet: Sized + Clone + Eq + Debug {
         }
         all_present
     }
+
+    /// The states before and after a batch which removes `removed` and inserts `inserted`, from
+    /// the elements themselves: the set of `untouched` and `removed`, and the digest of
+    /// `untouched` and `inserted`.
+    fn transition(
+        group: Self::G,
+        untouched: Vec<BigUint>,
+        removed: Vec<BigUint>,
+        inserted: Vec<BigUint>,
+    ) -> (Self, <Self::G as SemiGroup>::Elem) {
+        let mut initial_state = Self::new_with(group, untouched);
+        let mut final_state = initial_state.clone();
+        initial_state.insert_all(removed);
+        final_state.insert_all(inserted);
+        (initial_state, final_state.digest())
+    }
 }
 
-/// An `NaiveExpSet` which computes products from scratch each time.
+/// An `IntSet` which keeps its elements in a map and caches its digest.
+///
+/// Insertions raise the cached digest to the new element. Removals drop the cache, since taking
+/// roots is infeasible in groups of unknown order, and the next `digest` recomputes it from the
+/// generator.
 #[derive(Clone, PartialEq, Eq)]
 pub struct NaiveExpSet<G: SemiGroup> {
     group: G,
@@ -129,6 +164,244 @@ where
     fn group(&self) -> &G {
         &self.group
     }
+
+    fn members(&self) -> Vec<BigUint> {
+        self.elements().into_iter().cloned().collect()
+    }
+
+    fn contains(&self, n: &BigUint) -> bool {
+        self.elements.contains_key(n)
+    }
+}
+
+impl<G: SemiGroup> NaiveExpSet<G>
+where
+    G::Elem: Ord,
+{
+    /// Like `new_with`, but the digest is computed once, by `par_digest`, rather than one
+    /// exponentiation per item.
+    #[cfg(feature = "parallel")]
+    pub fn par_new_with<I: IntoIterator<Item = BigUint>>(group: G, items: I) -> Self {
+        let mut elements = BTreeMap::new();
+        for n in items {
+            *elements.entry(n).or_insert(0) += 1;
+        }
+        let mut this = Self {
+            group,
+            elements,
+            digest: None,
+        };
+        this.par_digest();
+        this
+    }
+
+    /// Like `digest`, but raises the generator to the product of the elements, which is
+    /// multiplied out by a parallel tree reduction, in a single exponentiation.
+    #[cfg(feature = "parallel")]
+    pub fn par_digest(&mut self) -> G::Elem {
+        if self.digest.is_none() {
+            let elements = self.elements();
+            self.digest = Some(if elements.is_empty() {
+                self.group.generator()
+            } else {
+                let product = elements
+                    .par_iter()
+                    .map(|e| (*e).clone())
+                    .reduce(BigUint::one, |a, b| a * b);
+                self.group.power(&self.group.generator(), &product)
+            });
+        }
+        self.digest.clone().unwrap()
+    }
+
+    /// Inserts `items` one at a time as the returned iterator is advanced, yielding the digest
+    /// after each insertion. Each digest is the last raised to the new item, so this costs one
+    /// exponentiation per item, once the current digest is known.
+    pub fn insert_iter<'a, I>(&'a mut self, items: I) -> impl Iterator<Item = G::Elem> + 'a
+    where
+        I: IntoIterator<Item = BigUint>,
+        I::IntoIter: 'a,
+    {
+        self.digest();
+        items.into_iter().map(move |n| {
+            self.insert(n);
+            self.digest()
+        })
+    }
+
+    /// The elements of the set, with repetition, in the order used by `membership_witnesses`.
+    pub fn elements(&self) -> Vec<&BigUint> {
+        let mut elements = Vec::new();
+        for (elem, ct) in &self.elements {
+            for _ in 0..*ct {
+                elements.push(elem);
+            }
+        }
+        elements
+    }
+
+    /// A witness that `n` is in the set: `g` to the product of the other elements.
+    /// Returns `None` if `n` is not in the set.
+    pub fn membership_witness(&self, n: &BigUint) -> Option<G::Elem> {
+        if !self.elements.contains_key(n) {
+            return None;
+        }
+        let mut skipped = false;
+        let mut witness = self.group.generator();
+        for elem in self.elements() {
+            if !skipped && elem == n {
+                skipped = true;
+            } else {
+                witness = self.group.power(&witness, elem);
+            }
+        }
+        Some(witness)
+    }
+
+    /// The membership witnesses of all elements, aligned with `elements`.
+    /// Uses the RootFactor algorithm, which takes O(n log n) exponentiations rather than the
+    /// O(n^2) of calling `membership_witness` for each element.
+    pub fn membership_witnesses(&self) -> Vec<G::Elem> {
+        fn root_factor<G: SemiGroup>(
+            group: &G,
+            base: G::Elem,
+            elements: &[&BigUint],
+        ) -> Vec<G::Elem> {
+            if elements.len() <= 1 {
+                return elements.iter().map(|_| base.clone()).collect();
+            }
+            let (left, right) = elements.split_at(elements.len() / 2);
+            let product = |half: &[&BigUint]| half.iter().fold(BigUint::one(), |acc, e| acc * *e);
+            let left_base = group.power(&base, &product(right));
+            let right_base = group.power(&base, &product(left));
+            let mut witnesses = root_factor(group, left_base, left);
+            witnesses.extend(root_factor(group, right_base, right));
+            witnesses
+        }
+        root_factor(&self.group, self.group.generator(), &self.elements())
+    }
+
+    /// A witness that `n` is not in the set: a pair `(a, b)` such that `a^n * g^b` is the digest
+    /// and `0 < b < n`. It is `(g^q, r)` where the product of the elements is `q * n + r`.
+    /// Returns `None` if `n` divides that product.
+    pub fn non_membership_witness(&self, n: &BigUint) -> Option<(G::Elem, BigUint)> {
+        let product = self
+            .elements()
+            .into_iter()
+            .fold(BigUint::one(), |acc, e| acc * e);
+        let (q, r) = product.div_rem(n);
+        if r.is_zero() {
+            return None;
+        }
+        Some((self.group.power(&self.group.generator(), &q), r))
+    }
+
+    /// The membership witness of `target` once `removed` are removed, given the current
+    /// witnesses of `target` and of each removed element. It is derived from them by repeated
+    /// `shamir_trick`s, so no witness is recomputed from the remaining elements. Returns `None`
+    /// if any of the elements is absent, if `target` shares a factor with a removed element, or
+    /// if the group cannot invert.
+    pub fn witness_after_removals(
+        &self,
+        target: &BigUint,
+        target_witness: &G::Elem,
+        removed: &[(BigUint, G::Elem)],
+    ) -> Option<G::Elem> {
+        if !self.elements.contains_key(target)
+            || removed.iter().any(|(r, _)| !self.elements.contains_key(r))
+        {
+            return None;
+        }
+        let mut target_witness = target_witness.clone();
+        let mut pending = removed.to_vec();
+        while let Some((r, r_witness)) = pending.pop() {
+            target_witness = shamir_trick(&self.group, target, &target_witness, &r, &r_witness)?;
+            for (s, s_witness) in &mut pending {
+                *s_witness = shamir_trick(&self.group, s, s_witness, &r, &r_witness)?;
+            }
+        }
+        Some(target_witness)
+    }
+}
+
+/// `b` raised to a possibly negative `e`.
+fn signed_power<G: SemiGroup>(group: &G, b: &G::Elem, e: &BigInt) -> Option<G::Elem> {
+    let base = if e.is_negative() {
+        group.inverse(b)?
+    } else {
+        b.clone()
+    };
+    Some(group.power(&base, &e.abs().to_biguint().unwrap()))
+}
+
+/// Shamir's trick. Given witnesses `wx` and `wy` that coprime `x` and `y` are in a set, computes
+/// the witness that `x` is in the set without `y`: `wx^b * wy^a`, where `a * x + b * y = 1`.
+/// Returns `None` if `x` and `y` share a factor, or if the group cannot invert.
+pub fn shamir_trick<G: SemiGroup>(
+    group: &G,
+    x: &BigUint,
+    wx: &G::Elem,
+    y: &BigUint,
+    wy: &G::Elem,
+) -> Option<G::Elem> {
+    let egcd = x.to_bigint().unwrap().extended_gcd(&y.to_bigint().unwrap());
+    if !egcd.gcd.is_one() {
+        return None;
+    }
+    Some(group.op(&signed_power(group, wx, &egcd.y)?, &signed_power(group, wy, &egcd.x)?))
+}
+
+/// The serialized form of a `NaiveExpSet`: its group, its elements (in decimal) with their
+/// multiplicities, and its digest (in decimal), if that is cached.
+#[derive(Serialize, Deserialize)]
+struct NaiveExpSetRepr<G> {
+    group: G,
+    elements: Vec<(String, usize)>,
+    digest: Option<String>,
+}
+
+impl<G: SemiGroup<Elem = BigUint> + Serialize> Serialize for NaiveExpSet<G> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        NaiveExpSetRepr {
+            group: &self.group,
+            elements: self
+                .elements
+                .iter()
+                .map(|(e, ct)| (e.to_string(), *ct))
+                .collect(),
+            digest: self.digest.as_ref().map(|d| d.to_string()),
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de, G: SemiGroup<Elem = BigUint> + Deserialize<'de>> Deserialize<'de> for NaiveExpSet<G> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let repr = NaiveExpSetRepr::<G>::deserialize(d)?;
+        let nat = |s: &str| {
+            BigUint::from_str(s).map_err(|_| D::Error::custom(format!("{} is not a natural", s)))
+        };
+        let mut elements = BTreeMap::new();
+        for (e, ct) in &repr.elements {
+            if *ct == 0 {
+                return Err(D::Error::custom(format!("{} has multiplicity 0", e)));
+            }
+            *elements.entry(nat(e)?).or_insert(0) += ct;
+        }
+        let digest = repr.digest.as_ref().map(|d| nat(d)).transpose()?;
+        let mut set = Self {
+            digest: None,
+            group: repr.group,
+            elements,
+        };
+        // Every later digest is derived from the cached one, so it is checked, not trusted.
+        if let Some(digest) = digest {
+            if set.digest() != digest {
+                return Err(D::Error::custom("the digest does not match the elements"));
+            }
+        }
+        Ok(set)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -163,12 +436,7 @@ where
     ) -> Result<Self, SynthesisError> {
         let mut value = value.cloned();
         let digest_val = value.as_mut().map(|v| v.digest());
-        let digest: CG::Elem = <CG::Elem as Gadget>::alloc(
-            cs.namespace(|| "digest"),
-            digest_val.as_ref(),
-            (),
-            &CG::elem_params(access.params()),
-        )?;
+        let digest: CG::Elem = access.alloc_elem(cs.namespace(|| "digest"), digest_val.as_ref())?;
         let group = access;
         Ok(Self {
             value,
@@ -200,29 +468,62 @@ where
     CG::Elem: Gadget<E = E, Value = <CG::Group as SemiGroup>::Elem, Access = ()>,
     S: IntSet<G = CG::Group>,
 {
+    /// A set of which only the digest is known, as it is to a verifier who does not hold the
+    /// members. It can check witnesses with `verify_member` and `verify_non_member`, but not
+    /// compute the digest after an insertion or removal.
+    pub fn from_digest<CS: ConstraintSystem<E>>(
+        mut cs: CS,
+        digest: Option<&<CG::Group as SemiGroup>::Elem>,
+        group: CG,
+    ) -> Result<Self, SynthesisError> {
+        let digest = group.alloc_elem(cs.namespace(|| "digest"), digest)?;
+        Ok(Self {
+            value: None,
+            group,
+            digest,
+        })
+    }
+
     pub fn remove<'a, CS: ConstraintSystem<E>>(
         self,
         mut cs: CS,
         challenge: &BigNat<E>,
         items: impl IntoIterator<Item = &'a Reduced<E>> + Clone,
     ) -> Result<Self, SynthesisError> {
-        let value = self.value.clone().and_then(|mut set| {
-            items
-                .clone()
-                .into_iter()
-                .map(|i| i.raw.value.as_ref())
-                .collect::<Option<Vec<&BigUint>>>()
-                .map(|is| {
-                    assert!(set.remove_all(is));
-                    set
-                })
-        });
+        let value = self.removal_value(items.clone());
         let new_set = Self::alloc(
             cs.namespace(|| "new"),
             value.as_ref(),
             self.group.clone(),
             &(),
         )?;
+        self.prove_removal(cs, challenge, items, new_set)
+    }
+
+    /// Removes `items`, using a proof of exponentiation to check that the resulting digest is
+    /// `digest`, rather than allocating the resulting digest.
+    pub fn remove_to<'a, CS: ConstraintSystem<E>>(
+        self,
+        cs: CS,
+        challenge: &BigNat<E>,
+        items: impl IntoIterator<Item = &'a Reduced<E>> + Clone,
+        digest: CG::Elem,
+    ) -> Result<Self, SynthesisError> {
+        let new_set = Self {
+            value: self.removal_value(items.clone()),
+            group: self.group.clone(),
+            digest,
+        };
+        self.prove_removal(cs, challenge, items, new_set)
+    }
+
+    fn prove_removal<'a, CS: ConstraintSystem<E>>(
+        self,
+        mut cs: CS,
+        challenge: &BigNat<E>,
+        items: impl IntoIterator<Item = &'a Reduced<E>> + Clone,
+        new_set: Self,
+    ) -> Result<Self, SynthesisError> {
         proof_of_exp(
             cs.namespace(|| "proof"),
             &new_set.group,
@@ -234,6 +535,64 @@ where
         Ok(new_set)
     }
 
+    fn removal_value<'a>(&self, items: impl IntoIterator<Item = &'a Reduced<E>>) -> Option<S> {
+        self.value.clone().and_then(|mut set| {
+            items
+                .into_iter()
+                .map(|i| i.raw.value.as_ref())
+                .collect::<Option<Vec<&BigUint>>>()
+                .map(|is| {
+                    assert!(set.remove_all(is.into_iter().filter(|i| !i.is_one())));
+                    set
+                })
+        })
+    }
+
+    /// Constrains `element` to be in the set, given a witness `w` such that `w^element` is the
+    /// digest, as computed by `NaiveExpSet::membership_witness`. The element must exceed one,
+    /// since every digest is its own witness for one.
+    pub fn verify_member<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        element: &BigNat<E>,
+        witness: CG::Elem,
+    ) -> Result<(), SynthesisError> {
+        BigNat::identity::<CS>(element.params.limb_width)
+            .enforce_lt(cs.namespace(|| "element > 1"), element)?;
+        let power = self.group.power(cs.namespace(|| "w^x"), &witness, element)?;
+        <CG::Elem as Gadget>::assert_equal(cs.namespace(|| "w^x == digest"), &power, &self.digest)
+    }
+
+    /// Constrains `element` not to be in the set, given a witness `(a, b)` such that
+    /// `a^element * g^b` is the digest and `0 < b < element`, as computed by
+    /// `NaiveExpSet::non_membership_witness`.
+    pub fn verify_non_member<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        element: &BigNat<E>,
+        witness: (CG::Elem, BigNat<E>),
+    ) -> Result<(), SynthesisError> {
+        let (a, b) = witness;
+        b.decompose(cs.namespace(|| "b rangecheck"))?;
+        b.sub(
+            cs.namespace(|| "b > 0"),
+            &BigNat::identity::<CS>(b.params.limb_width),
+        )?;
+        b.enforce_lt(cs.namespace(|| "b < element"), element)?;
+        let a_x = self.group.power(cs.namespace(|| "a^x"), &a, element)?;
+        let g_b = self
+            .group
+            .power(cs.namespace(|| "g^b"), &self.group.generator(), &b)?;
+        let product = self.group.op(cs.namespace(|| "a^x g^b"), &a_x, &g_b)?;
+        <CG::Elem as Gadget>::assert_equal(
+            cs.namespace(|| "a^x g^b == digest"),
+            &product,
+            &self.digest,
+        )
+    }
+
+    /// Inserts `items`. Items of one, which stand for empty slots in a batch, leave the set
+    /// unchanged, as they do when removed.
     pub fn insert<CS: ConstraintSystem<E>>(
         self,
         mut cs: CS,
@@ -246,7 +605,7 @@ where
                 .map(|i| i.raw.value.clone())
                 .collect::<Option<Vec<BigUint>>>()
                 .map(|is| {
-                    set.insert_all(is);
+                    set.insert_all(is.into_iter().filter(|i| !i.is_one()));
                     set
                 })
         });
@@ -273,7 +632,7 @@ mod tests {
     use super::*;
     use util::test_helpers::*;
 
-    use group::{CircuitRsaGroup, CircuitRsaGroupParams, RsaGroup};
+    use group::{CircuitRsaGroup, CircuitRsaGroupParams, RsaGroup, RsaQuotientGroup};
     use OptionExt;
 
     use std::str::FromStr;
@@ -352,6 +711,7 @@ mod tests {
                 &CircuitRsaGroupParams {
                     limb_width: self.params.limb_width,
                     n_limbs: self.params.n_limbs_b,
+                    window_bits: None,
                 },
             )?;
             let initial_set: CircuitIntSet<E, CircuitRsaGroup<E>, NaiveExpSet<RsaGroup>> =
@@ -503,4 +863,369 @@ mod tests {
                                                         true
                                                             ),
     }
+
+    pub struct RsaNonMembershipInputs<'a> {
+        pub items: &'a [&'a str],
+        pub element: &'a str,
+        /// A witness to use instead of the honest one, for negative tests.
+        pub forged_witness: Option<(&'a str, &'a str)>,
+    }
+
+    pub struct RsaNonMembership<'a> {
+        inputs: Option<RsaNonMembershipInputs<'a>>,
+    }
+
+    impl<'a, E: Engine> Circuit<E> for RsaNonMembership<'a> {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let inputs = self.inputs.grab()?;
+            let raw_group = RsaGroup {
+                g: BigUint::from(2usize),
+                m: BigUint::from(143usize),
+            };
+            let raw_set = NaiveExpSet::new_with(
+                raw_group.clone(),
+                inputs.items.iter().map(|i| BigUint::from_str(i).unwrap()),
+            );
+            let element_value = BigUint::from_str(inputs.element).unwrap();
+            let (a_value, b_value) = match inputs.forged_witness {
+                Some((a, b)) => (BigUint::from_str(a).unwrap(), BigUint::from_str(b).unwrap()),
+                None => raw_set
+                    .non_membership_witness(&element_value)
+                    .ok_or(SynthesisError::Unsatisfiable)?,
+            };
+            let group = CircuitRsaGroup::alloc(
+                cs.namespace(|| "group"),
+                Some(&raw_group),
+                (),
+                &CircuitRsaGroupParams {
+                    limb_width: 4,
+                    n_limbs: 2,
+                    window_bits: None,
+                },
+            )?;
+            let set: CircuitIntSet<E, CircuitRsaGroup<E>, NaiveExpSet<RsaGroup>> =
+                CircuitIntSet::alloc(cs.namespace(|| "set"), Some(&raw_set), group.clone(), &())?;
+            let element =
+                BigNat::alloc_from_nat(cs.namespace(|| "element"), || Ok(element_value), 4, 2)?;
+            let a = group.alloc_elem(cs.namespace(|| "a"), Some(&a_value))?;
+            let b = BigNat::alloc_from_nat(cs.namespace(|| "b"), || Ok(b_value), 4, 2)?;
+            set.verify_non_member(cs.namespace(|| "non member"), &element, (a, b))
+        }
+    }
+
+    pub struct RsaMembershipInputs<'a> {
+        pub items: &'a [&'a str],
+        pub element: &'a str,
+        /// The member whose witness is used, which differs from `element` in negative tests.
+        pub witness_of: &'a str,
+    }
+
+    /// Checks a membership witness against a set allocated from its digest alone.
+    pub struct RsaMembership<'a> {
+        inputs: Option<RsaMembershipInputs<'a>>,
+    }
+
+    impl<'a, E: Engine> Circuit<E> for RsaMembership<'a> {
+        fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let inputs = self.inputs.grab()?;
+            let raw_group = RsaGroup {
+                g: BigUint::from(2usize),
+                m: BigUint::from(143usize),
+            };
+            let mut raw_set = NaiveExpSet::new_with(
+                raw_group.clone(),
+                inputs.items.iter().map(|i| BigUint::from_str(i).unwrap()),
+            );
+            let witness_value = raw_set
+                .membership_witness(&BigUint::from_str(inputs.witness_of).unwrap())
+                .ok_or(SynthesisError::Unsatisfiable)?;
+            let group = CircuitRsaGroup::alloc(
+                cs.namespace(|| "group"),
+                Some(&raw_group),
+                (),
+                &CircuitRsaGroupParams {
+                    limb_width: 4,
+                    n_limbs: 2,
+                    window_bits: None,
+                },
+            )?;
+            let set: CircuitIntSet<E, CircuitRsaGroup<E>, NaiveExpSet<RsaGroup>> =
+                CircuitIntSet::from_digest(
+                    cs.namespace(|| "set"),
+                    Some(&raw_set.digest()),
+                    group.clone(),
+                )?;
+            assert!(set.value.is_none());
+            let element = BigNat::alloc_from_nat(
+                cs.namespace(|| "element"),
+                || Ok(BigUint::from_str(inputs.element).unwrap()),
+                4,
+                2,
+            )?;
+            let witness = group.alloc_elem(cs.namespace(|| "witness"), Some(&witness_value))?;
+            set.verify_member(cs.namespace(|| "member"), &element, witness)
+        }
+    }
+
+    circuit_tests! {
+        member_5_of_3_5_7: (
+            RsaMembership {
+                inputs: Some(RsaMembershipInputs {
+                    items: &["3", "5", "7"],
+                    element: "5",
+                    witness_of: "5",
+                }),
+            },
+            true
+        ),
+        member_11_of_3_5_7_with_witness_of_5: (
+            RsaMembership {
+                inputs: Some(RsaMembershipInputs {
+                    items: &["3", "5", "7"],
+                    element: "11",
+                    witness_of: "5",
+                }),
+            },
+            false
+        ),
+        non_member_7_of_3_5: (
+            RsaNonMembership {
+                inputs: Some(RsaNonMembershipInputs {
+                    items: &["3", "5"],
+                    element: "7",
+                    forged_witness: None,
+                }),
+            },
+            true
+        ),
+        non_member_7_of_empty: (
+            RsaNonMembership {
+                inputs: Some(RsaNonMembershipInputs {
+                    items: &[],
+                    element: "7",
+                    forged_witness: None,
+                }),
+            },
+            true
+        ),
+        // (2^4)^3 * 2^3 = 2^15 is the digest of {3, 5}, but b = 3 is not less than the element.
+        non_member_forged_for_member: (
+            RsaNonMembership {
+                inputs: Some(RsaNonMembershipInputs {
+                    items: &["3", "5"],
+                    element: "3",
+                    forged_witness: Some(("16", "3")),
+                }),
+            },
+            false
+        ),
+    }
+
+    #[test]
+    fn batch_membership_witnesses_16() {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let items: Vec<BigUint> = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 53]
+            .iter()
+            .map(|i| BigUint::from(*i as usize))
+            .collect();
+        let mut set = NaiveExpSet::new_with(group.clone(), items);
+        let elements = set.elements();
+        let witnesses = set.membership_witnesses();
+        assert_eq!(elements.len(), 16);
+        assert_eq!(witnesses.len(), 16);
+        for (elem, witness) in elements.iter().zip(&witnesses) {
+            assert_eq!(Some(witness), set.membership_witness(elem).as_ref());
+        }
+        let digest = set.digest();
+        for (elem, witness) in set.elements().into_iter().zip(witnesses) {
+            assert_eq!(group.power(&witness, elem), digest);
+        }
+    }
+
+    #[test]
+    fn insert_iter_yields_each_digest() {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let items: Vec<BigUint> = [3usize, 5, 7].iter().map(|i| BigUint::from(*i)).collect();
+        let mut set = NaiveExpSet::new_with(group.clone(), vec![BigUint::from(11usize)]);
+        let digests: Vec<BigUint> = set.insert_iter(items.clone()).collect();
+        assert_eq!(digests.len(), 3);
+        for (i, digest) in digests.iter().enumerate() {
+            let mut fresh = NaiveExpSet::new_with(
+                group.clone(),
+                Some(BigUint::from(11usize)).into_iter().chain(items[..=i].iter().cloned()),
+            );
+            assert_eq!(digest, &fresh.digest());
+        }
+        assert_eq!(set.digest(), digests[2]);
+        assert_eq!(set.members().len(), 4);
+    }
+
+    #[test]
+    fn witness_after_two_removals() {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let n = |i: usize| BigUint::from(i);
+        let set = NaiveExpSet::new_with(group.clone(), vec![n(3), n(5), n(7), n(11)]);
+        let w = |i: usize| set.membership_witness(&n(i)).unwrap();
+        let (w5, w7, w11) = (w(5), w(7), w(11));
+        let removed = [(n(5), w5.clone()), (n(11), w11)];
+        let witness = set.witness_after_removals(&n(7), &w7, &removed).unwrap();
+        let mut after = set.clone();
+        assert!(after.remove_all(vec![n(5), n(11)].iter()));
+        assert_eq!(group.power(&witness, &n(7)), after.digest());
+        assert_eq!(Some(witness), after.membership_witness(&n(7)));
+
+        let absent = [(n(5), w5.clone()), (n(13), w5)];
+        assert!(set.witness_after_removals(&n(7), &w7, &absent).is_none());
+        assert!(set.witness_after_removals(&n(13), &w7, &removed[..1]).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn parallel_digest_matches_serial() {
+        let group = RsaQuotientGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        // 100 odd 128-bit elements, some of them repeated.
+        let items: Vec<BigUint> = (0..100usize)
+            .map(|i| (BigUint::one() << 127) + 2 * (i % 90) + 1usize)
+            .collect();
+        let mut serial = NaiveExpSet::new_with(group.clone(), items.clone());
+        let mut parallel = NaiveExpSet::par_new_with(group.clone(), items);
+        assert_eq!(serial.digest(), parallel.digest());
+        assert_eq!(serial, parallel);
+
+        let n = BigUint::from(3usize);
+        serial.insert(n.clone());
+        assert!(serial.remove(&n));
+        assert_eq!(serial.clone().par_digest(), serial.digest());
+        let empty = NaiveExpSet::par_new_with(group.clone(), Vec::new());
+        assert_eq!(empty, NaiveExpSet::new(group));
+    }
+
+    #[test]
+    fn member_has_no_non_membership_witness() {
+        let set = NaiveExpSet::new_with(
+            RsaGroup {
+                g: BigUint::from(2usize),
+                m: BigUint::from(143usize),
+            },
+            vec![BigUint::from(3usize), BigUint::from(5usize)],
+        );
+        assert!(set.non_membership_witness(&BigUint::from(3usize)).is_none());
+        assert!(set.non_membership_witness(&BigUint::from(7usize)).is_some());
+    }
+
+    #[quickcheck]
+    fn qc_digest_is_order_independent(items: Vec<u16>, removed: u16) -> bool {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let items: Vec<BigUint> = items.into_iter().map(BigUint::from).collect();
+        let mut forward = NaiveExpSet::new_with(group.clone(), items.iter().cloned());
+        let mut backward = NaiveExpSet::new(group);
+        for item in items.iter().rev() {
+            // Take digests along the way, so that the cached digest is updated incrementally.
+            backward.digest();
+            backward.insert(item.clone());
+        }
+        let removed = BigUint::from(removed);
+        assert_eq!(forward.remove(&removed), backward.remove(&removed));
+        forward.members() == backward.members() && forward.digest() == backward.digest()
+    }
+
+    #[test]
+    fn cached_digest_matches_recomputation() {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let n = |i: usize| BigUint::from(i);
+        let mut set = NaiveExpSet::new_with(group.clone(), vec![n(3), n(5), n(7)]);
+        set.digest();
+        set.insert(n(11));
+        set.insert(n(5));
+        set.digest();
+        assert!(set.remove(&n(3)));
+        set.insert(n(13));
+        assert!(!set.remove(&n(17)));
+        set.digest();
+        set.insert(n(19));
+        assert!(set.remove(&n(5)));
+        assert!(set.remove_all(vec![n(7), n(19)].iter()));
+        set.insert_all(vec![n(23), n(29)]);
+        let fresh = NaiveExpSet::new_with(group.clone(), set.members()).digest();
+        assert_eq!(set.digest(), fresh);
+        let exponent = [5usize, 11, 13, 23, 29]
+            .iter()
+            .fold(BigUint::one(), |acc, i| acc * *i);
+        assert_eq!(fresh, group.power(&group.generator(), &exponent));
+    }
+
+    #[test]
+    fn members_after_inserts() {
+        let mut set = NaiveExpSet::new(RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from(143usize),
+        });
+        for i in &[7usize, 3, 5] {
+            set.insert(BigUint::from(*i));
+        }
+        let expected: Vec<BigUint> = [3usize, 5, 7].iter().map(|i| BigUint::from(*i)).collect();
+        assert_eq!(set.members(), expected);
+        set.insert(BigUint::from(5usize));
+        set.remove(&BigUint::from(3usize));
+        let expected: Vec<BigUint> = [5usize, 5, 7].iter().map(|i| BigUint::from(*i)).collect();
+        assert_eq!(set.members(), expected);
+    }
+
+    #[test]
+    fn serialized_set_round_trip() {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let items = [3usize, 5, 7, 7, 11].iter().map(|i| BigUint::from(*i));
+        let mut set = NaiveExpSet::new_with(group.clone(), items);
+        set.remove(&BigUint::from(5usize));
+        let json = serde_json::to_string(&set).unwrap();
+        let mut restored: NaiveExpSet<RsaGroup> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, set);
+        assert_eq!(restored.digest(), set.digest());
+
+        let json = serde_json::to_string(&set).unwrap();
+        let mut restored: NaiveExpSet<RsaGroup> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.group(), &group);
+        restored.insert(BigUint::from(13usize));
+        assert!(restored.remove(&BigUint::from(7usize)));
+        let expected = [3usize, 7, 11, 13].iter().map(|i| BigUint::from(*i));
+        assert_eq!(restored.digest(), NaiveExpSet::new_with(group, expected).digest());
+    }
+
+    #[test]
+    fn serialized_set_with_wrong_digest_is_rejected() {
+        let group = RsaGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        let items = [3usize, 5, 7].iter().map(|i| BigUint::from(*i));
+        let mut set = NaiveExpSet::new_with(group, items);
+        set.digest();
+        let mut json = serde_json::to_value(&set).unwrap();
+        assert!(serde_json::from_value::<NaiveExpSet<RsaGroup>>(json.clone()).is_ok());
+        json["digest"] = serde_json::Value::from("12");
+        assert!(serde_json::from_value::<NaiveExpSet<RsaGroup>>(json).is_err());
+    }
+
+    const RSA_512: &str = "11834783464130424096695514462778870280264989938857328737807205623069291535525952722847913694296392927890261736769191982212777933726583565708193466779811767";
 }
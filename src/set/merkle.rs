@@ -8,7 +8,7 @@ use sapling_crypto::circuit::num::AllocatedNum;
 use std::collections::BTreeMap;
 use std::rc::Rc;
 
-use super::{CircuitGenSet, GenSet};
+use super::{CircuitGenSet, GenSet, MissingItem};
 use hash::circuit::{CircuitHasher, MaybeHashed};
 use hash::Hasher;
 use util::convert::usize_to_f;
@@ -130,18 +130,16 @@ where
 {
     type Digest = H::F;
 
-    fn swap(&mut self, old: &[H::F], new: Vec<H::F>) {
+    fn swap(&mut self, old: &[H::F], new: Vec<H::F>) -> Result<(), MissingItem> {
         let o_r = self.hasher.hash(old).into_repr();
         let n = self.hasher.hash(&new);
         let n_r = n.into_repr();
-        let i = *self
-            .leaf_indices
-            .get(&o_r)
-            .expect("missing element in MerkleSet::swap");
+        let i = *self.leaf_indices.get(&o_r).ok_or(MissingItem)?;
         self.nodes.insert((self.depth, i), n);
         self.leaf_indices.remove(&o_r);
         self.leaf_indices.insert(n_r, i);
         self.update_hashes_from_leaf_index(i);
+        Ok(())
     }
 
     /// The digest of the current elements (`g` to the product of the elements).
@@ -322,7 +320,7 @@ where
                         .map(|n| n.get_value())
                         .collect::<Option<Vec<E::Fr>>>();
                     if let (Some(o), Some(n)) = (o, n) {
-                        v.swap(&o, n);
+                        v.swap(&o, n)?;
                     }
                 }
             }
@@ -337,6 +335,8 @@ where
 {
     /// The initial state of the set
     pub initial_state: MerkleSet<H>,
+    /// The root after the swaps, which the circuit takes as a public input
+    pub final_digest: H::F,
     /// The items to remove from the set
     pub to_remove: Vec<Vec<H::F>>,
     /// The items to insert into the set
@@ -390,8 +390,13 @@ where
         assert!((1 << depth) >= initial.len());
         assert_eq!(removed.len(), inserted.len());
         let initial_state = MerkleSet::new_with(hash, depth, initial.iter().map(Vec::as_slice));
+        let mut final_state = initial_state.clone();
+        final_state
+            .swap_all(removed.clone(), inserted.clone())
+            .expect("removed items must be in the set");
         Self {
             initial_state,
+            final_digest: final_state.digest(),
             to_remove: removed,
             to_insert: inserted,
         }
@@ -422,7 +427,7 @@ where
 {
     fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
         if self.params.verbose {
-            println!("Constructing Set");
+            info!("Constructing Set");
         }
         let set = MerkleCircuitSet::alloc(
             cs.namespace(|| "set init"),
@@ -432,7 +437,7 @@ where
         )?;
         set.inputize(cs.namespace(|| "initial_state input"))?;
         if self.params.verbose {
-            println!("Allocating Deletions...");
+            info!("Allocating Deletions...");
         }
         let removals = (0..self.params.n_swaps)
             .map(|i| {
@@ -447,7 +452,7 @@ where
             .collect::<Result<Vec<Vec<AllocatedNum<E>>>, SynthesisError>>()?;
 
         if self.params.verbose {
-            println!("Allocating Insertions...");
+            info!("Allocating Insertions...");
         }
         let insertions = (0..self.params.n_swaps)
             .map(|i| {
@@ -462,7 +467,7 @@ where
             .collect::<Result<Vec<Vec<AllocatedNum<E>>>, SynthesisError>>()?;
 
         if self.params.verbose {
-            println!("Swapping elements");
+            info!("Swapping elements");
         }
         let new_set = set.swap_all(
             cs.namespace(|| "swap"),
@@ -474,9 +479,20 @@ where
         )?;
 
         if self.params.verbose {
-            println!("NOT Verifying resulting digest");
+            info!("Verifying resulting digest");
         }
-        new_set.inputize(cs.namespace(|| "final_state input"))?;
+        // The declared root is the public input, and the computed root must equal it.
+        let expected_final_digest =
+            AllocatedNum::alloc(cs.namespace(|| "expected_final_digest"), || {
+                Ok(self.inputs.grab()?.final_digest)
+            })?;
+        expected_final_digest.inputize(cs.namespace(|| "final_state input"))?;
+        cs.enforce(
+            || "final digest matches",
+            |lc| lc,
+            |lc| lc,
+            |lc| lc + new_set.digest.get_variable() - expected_final_digest.get_variable(),
+        );
         Ok(())
     }
 }
@@ -484,8 +500,32 @@ where
 #[cfg(test)]
 mod test {
     use super::{MerkleSetBench, MerkleSetBenchInputs, MerkleSetBenchParams};
+    use sapling_crypto::bellman::pairing::ff::Field;
     use hash::hashes::Poseidon;
+    use set::GenSet;
     use util::test_helpers::*;
+
+    #[test]
+    fn final_root_is_the_declared_public_input() {
+        let inputs = MerkleSetBenchInputs::from_counts(2, 1, 5, Poseidon::default(), 4);
+        let mut public = vec![inputs.initial_state.clone().digest(), inputs.final_digest];
+        let circuit = MerkleSetBench {
+            inputs: Some(inputs),
+            params: MerkleSetBenchParams {
+                item_size: 5,
+                n_swaps: 1,
+                hash: Poseidon::default(),
+                verbose: false,
+                depth: 4,
+            },
+        };
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+        assert!(cs.verify(&public));
+        public[1].add_assign(&Field::one());
+        assert!(!cs.verify(&public));
+    }
     circuit_tests! {
         merkle_1_swap_3_depth: (MerkleSetBench {
             inputs: Some(MerkleSetBenchInputs::from_counts(
@@ -519,6 +559,42 @@ mod test {
                         depth: 10,
                     },
         }, true),
+        merkle_2_swap_10_depth_untouched: (MerkleSetBench {
+            inputs: Some(MerkleSetBenchInputs::from_counts(
+                            4,
+                            2,
+                            5,
+                            Poseidon::default(),
+                            10
+                    )),
+                    params: MerkleSetBenchParams {
+                        item_size: 5,
+                        n_swaps: 2,
+                        hash: Poseidon::default(),
+                        verbose: false,
+                        depth: 10,
+                    },
+        }, true),
+        merkle_1_swap_10_depth_wrong_final_digest: (MerkleSetBench {
+            inputs: Some({
+                let mut inputs = MerkleSetBenchInputs::from_counts(
+                    0,
+                    1,
+                    5,
+                    Poseidon::default(),
+                    10,
+                );
+                inputs.final_digest.add_assign(&Field::one());
+                inputs
+            }),
+            params: MerkleSetBenchParams {
+                item_size: 5,
+                n_swaps: 1,
+                hash: Poseidon::default(),
+                verbose: false,
+                depth: 10,
+            },
+        }, false),
         merkle_1_swap_25_depth: (MerkleSetBench {
             inputs: Some(MerkleSetBenchInputs::from_counts(
                             0,
@@ -1,34 +1,90 @@
 use sapling_crypto::bellman::pairing::ff::PrimeField;
 use sapling_crypto::bellman::pairing::Engine;
-use sapling_crypto::bellman::ConstraintSystem;
+use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
+
+use num_bigint::BigUint;
+
+use std::borrow::Borrow;
+use std::fmt::{self, Display, Formatter};
 
 use CResult;
 use hash::circuit::MaybeHashed;
+use mp::bignat::{nat_to_limbs, LimbError};
 
 pub mod int_set;
 pub mod merkle;
 pub mod rsa;
 
+/// An item to remove from a set is not in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingItem;
+
+impl Display for MissingItem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "the item to remove is not in the set")
+    }
+}
+
+impl std::error::Error for MissingItem {}
+
+impl From<MissingItem> for SynthesisError {
+    fn from(e: MissingItem) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
+/// An item to insert into a set is already in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateItem;
+
+impl Display for DuplicateItem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "the item to insert is already in the set")
+    }
+}
+
+impl std::error::Error for DuplicateItem {}
+
+impl From<DuplicateItem> for SynthesisError {
+    fn from(e: DuplicateItem) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
 pub trait GenSet<F>
 where
     F: PrimeField,
 {
     type Digest;
 
-    fn swap(&mut self, old: &[F], new: Vec<F>);
+    /// Replace `old` with `new`. Fails, leaving the set unchanged, if `old` is not in the set.
+    fn swap(&mut self, old: &[F], new: Vec<F>) -> Result<(), MissingItem>;
 
-    /// Remove all of the `ns` from the set.
-    fn swap_all<I, J>(&mut self, old: I, new: J)
+    /// Replace each of the `old` items with the corresponding `new` one, stopping at the first
+    /// `old` item which is not in the set.
+    fn swap_all<I, J>(&mut self, old: I, new: J) -> Result<(), MissingItem>
     where
         I: IntoIterator<Item = Vec<F>>,
         J: IntoIterator<Item = Vec<F>>,
     {
         for (i, j) in old.into_iter().zip(new.into_iter()) {
-            self.swap(i.as_slice(), j);
+            self.swap(i.as_slice(), j)?;
         }
+        Ok(())
     }
 
     fn digest(&mut self) -> Self::Digest;
+
+    /// The digest as `n_limbs` limbs of `limb_width` bits, least significant first: the layout in
+    /// which the circuit allocates it as a public input.
+    fn digest_limbs(&mut self, limb_width: usize, n_limbs: usize) -> Result<Vec<F>, LimbError>
+    where
+        Self::Digest: Borrow<BigUint>,
+    {
+        nat_to_limbs(self.digest().borrow(), limb_width, n_limbs)
+    }
 }
 
 pub trait CircuitGenSet : Sized {
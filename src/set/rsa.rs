@@ -1,18 +1,27 @@
 use num_bigint::BigUint;
-use sapling_crypto::bellman::pairing::ff::{PrimeField, ScalarEngine};
+use rand::Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, ScalarEngine};
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{Circuit, ConstraintSystem, LinearCombination, SynthesisError};
+use sapling_crypto::circuit::boolean::{AllocatedBit, Boolean};
 use sapling_crypto::circuit::num::AllocatedNum;
+use serde::{Deserialize, Serialize};
 
-use std::fmt::{self, Debug, Formatter};
+use std::fmt::{self, Debug, Display, Formatter};
 
-use mp::bignat::BigNat;
+use mp::bignat::{nat_to_limbs, BigNat};
+use util::convert::nat_to_f;
 use util::gadget::Gadget;
-use group::{CircuitRsaQuotientGroup, CircuitRsaGroupParams, CircuitSemiGroup, RsaQuotientGroup, SemiGroup};
-use hash::{pocklington, division_intractable as di, HashDomain};
+use group::{
+    CircuitRsaGroupParams, CircuitRsaQuotientGroup, CircuitSemiGroup, RsaGroupParamsError,
+    RsaQuotientGroup, SemiGroup,
+};
+use hash::{pocklington, division_intractable as di, HashDomain, HashDomainError};
 use hash::circuit::{MaybeHashed, CircuitHasher};
 use hash::Hasher;
-use set::{GenSet, CircuitGenSet};
+use set::{GenSet, CircuitGenSet, DuplicateItem, MissingItem};
 use set::int_set::{CircuitIntSet, IntSet, NaiveExpSet};
 use wesolowski::Reduced;
 use CResult;
@@ -48,13 +57,12 @@ impl<H: Hasher, Inner: IntSet> Set<H, Inner> {
         offset: BigUint,
         hasher: H,
         element_bits: usize,
+        n_trailing_ones: usize,
         limb_width: usize,
         items: impl IntoIterator<Item = &'b [<H as Hasher>::F]>,
     ) -> Self {
-        let hash_domain = HashDomain {
-            n_bits: element_bits,
-            n_trailing_ones: 1,
-        };
+        let hash_domain =
+            HashDomain::new(element_bits, n_trailing_ones).expect("invalid element domain");
         let inner = Inner::new_with(
             group,
             items.into_iter().map(|slice| {
@@ -81,29 +89,48 @@ impl<H: Hasher, Inner: IntSet> Set<H, Inner> {
         self.inner.group()
     }
 
-    /// Add `n` to the set.
-    pub fn insert(&mut self, n: Vec<H::F>) {
-        let x = di::helper::di_hash::<H>(
-            &n,
+    /// The element which represents the item `n` in the accumulator.
+    pub fn element(&self, n: &[H::F]) -> BigUint {
+        di::helper::di_hash::<H>(
+            n,
             &self.offset,
             &self.hash_domain,
             self.limb_width,
             &self.hasher,
-        );
+        )
+    }
+
+    /// Add `n` to the set.
+    pub fn insert(&mut self, n: Vec<H::F>) {
+        let x = self.element(&n);
         self.inner.insert(x)
     }
     /// Remove `n` from the set, returning whether `n` was present.
     pub fn remove(&mut self, n: &[H::F]) -> bool {
-        let x = di::helper::di_hash::<H>(
-            &n,
-            &self.offset,
-            &self.hash_domain,
-            self.limb_width,
-            &self.hasher,
-        );
+        let x = self.element(n);
         self.inner.remove(&x)
     }
 
+    /// Whether `n` is in the set.
+    pub fn contains(&self, n: &[H::F]) -> bool {
+        self.inner.contains(&self.element(n))
+    }
+
+    /// Add `n` to the set, unless it is already present. Unlike `insert`, this keeps the set free
+    /// of repetition.
+    pub fn insert_unique(&mut self, n: Vec<H::F>) -> Result<(), DuplicateItem> {
+        if self.contains(&n) {
+            return Err(DuplicateItem);
+        }
+        self.insert(n);
+        Ok(())
+    }
+
+    /// The hashed elements of the set, with repetition, in increasing order.
+    pub fn members(&self) -> Vec<BigUint> {
+        self.inner.members()
+    }
+
     pub fn remove_all<'b, I: IntoIterator<Item = &'b [H::F]>>(&mut self, ns: I) -> bool
     where
         <Inner::G as SemiGroup>::Elem: 'b,
@@ -122,6 +149,31 @@ impl<H: Hasher, Inner: IntSet> Set<H, Inner> {
     }
 }
 
+#[cfg(feature = "parallel")]
+impl<H, Inner> Set<H, Inner>
+where
+    H: Hasher + Sync,
+    H::F: Send + Sync,
+    Inner: IntSet,
+{
+    /// The elements which represent `items`, as `element` computes them, but hashed in parallel.
+    /// Hashing to a prime dominates building a set, and each item is hashed on its own.
+    pub fn par_elements(&self, items: &[Vec<H::F>]) -> Vec<BigUint> {
+        let (offset, domain, hasher) = (&self.offset, &self.hash_domain, &self.hasher);
+        let limb_width = self.limb_width;
+        items
+            .par_iter()
+            .map(|n| di::helper::di_hash::<H>(n, offset, domain, limb_width, hasher))
+            .collect()
+    }
+
+    /// Like `insert_all`, but all of the items are hashed, in parallel, before any is inserted.
+    pub fn par_insert_all(&mut self, ns: &[Vec<H::F>]) {
+        let elements = self.par_elements(ns);
+        self.inner.insert_all(elements);
+    }
+}
+
 impl<H, Inner> GenSet<H::F> for Set<H, Inner>
 where
     H: Hasher,
@@ -129,9 +181,12 @@ where
 {
     type Digest = <Inner::G as SemiGroup>::Elem;
 
-    fn swap(&mut self, old: &[H::F], new: Vec<H::F>) {
+    fn swap(&mut self, old: &[H::F], new: Vec<H::F>) -> Result<(), MissingItem> {
+        if !self.remove(old) {
+            return Err(MissingItem);
+        }
         self.insert(new);
-        self.remove(old);
+        Ok(())
     }
 
     /// The digest of the current elements (`g` to the product of the elements).
@@ -144,15 +199,14 @@ where
 pub struct CircuitSetParams<H> {
     pub hasher: H,
     pub n_bits: usize,
+    /// The number of low bits of each element which are fixed to one.
+    pub n_trailing_ones: usize,
     pub limb_width: usize,
 }
 
 impl<H> CircuitSetParams<H> {
-    fn hash_domain(&self) -> HashDomain {
-        HashDomain {
-            n_bits: self.n_bits,
-            n_trailing_ones: 1,
-        }
+    fn hash_domain(&self) -> Result<HashDomain, HashDomainError> {
+        HashDomain::new(self.n_bits, self.n_trailing_ones)
     }
 }
 
@@ -236,34 +290,59 @@ where
     Inner: IntSet<G = <CG as CircuitSemiGroup>::Group>,
 {
     pub fn remove<'b, CS: ConstraintSystem<E>>(
+        self,
+        cs: CS,
+        items: &mut Vec<MaybeHashed<E>>,
+    ) -> Result<Self, SynthesisError> {
+        let active = full_slots(items.len());
+        self.remove_with(cs, items, &active, None)
+    }
+
+    /// Removes `items`, checking that the resulting digest is `digest` within the proof of
+    /// exponentiation.
+    pub fn remove_to<'b, CS: ConstraintSystem<E>>(
+        self,
+        cs: CS,
+        items: &mut Vec<MaybeHashed<E>>,
+        digest: CG::Elem,
+    ) -> Result<Self, SynthesisError> {
+        let active = full_slots(items.len());
+        self.remove_with(cs, items, &active, Some(digest))
+    }
+
+    /// Like `remove_to`, but only the items in slots which `active` marks are removed. The
+    /// items in the other slots are hashed, but do not change the set.
+    pub fn remove_slots_to<'b, CS: ConstraintSystem<E>>(
+        self,
+        cs: CS,
+        items: &mut Vec<MaybeHashed<E>>,
+        active: &[Boolean],
+        digest: CG::Elem,
+    ) -> Result<Self, SynthesisError> {
+        self.remove_with(cs, items, active, Some(digest))
+    }
+
+    fn remove_with<'b, CS: ConstraintSystem<E>>(
         self,
         mut cs: CS,
         items: &mut Vec<MaybeHashed<E>>,
+        active: &[Boolean],
+        digest: Option<CG::Elem>,
     ) -> Result<Self, SynthesisError> {
-        let removals = items
-            .into_iter()
-            .enumerate()
-            .map(|(i, mut input)| -> Result<Reduced<E>, SynthesisError> {
-                di::modded_di_hash(
-                    cs.namespace(|| format!("hash {}", i)),
-                    &mut input,
-                    self.params.limb_width,
-                    &self.params.hash_domain(),
-                    &self.offset,
-                    &self.access.1,
-                    &self.params.hasher,
-                )
-            })
-            .collect::<Result<Vec<Reduced<E>>, SynthesisError>>()?;
-        let inner =
-            self.inner
-                .remove(cs.namespace(|| "int removals"), &self.access.1, &removals)?;
+        let removals = self.hash_slots(&mut cs, items, active)?;
+        let inner = match digest {
+            Some(digest) => self.inner.remove_to(
+                cs.namespace(|| "int removals"),
+                &self.access.1,
+                &removals,
+                digest,
+            )?,
+            None => self
+                .inner
+                .remove(cs.namespace(|| "int removals"), &self.access.1, &removals)?,
+        };
         let value = self.value.as_ref().and_then(|v| {
-            let is: Option<Vec<Vec<E::Fr>>> = items
-                .into_iter()
-                .map(|i| i.values.iter().map(|n| n.get_value()).collect::<Option<Vec<_>>>())
-                .collect::<Option<Vec<_>>>();
-            is.map(|is| {
+            active_values(items, active).map(|is| {
                 let mut v = v.clone();
                 assert!(v.remove_all(is.iter().map(Vec::as_slice)));
                 v
@@ -279,36 +358,30 @@ where
     }
 
     pub fn insert<'b, CS: ConstraintSystem<E>>(
+        self,
+        cs: CS,
+        items: &mut Vec<MaybeHashed<E>>,
+    ) -> Result<Self, SynthesisError> {
+        let active = full_slots(items.len());
+        self.insert_slots(cs, items, &active)
+    }
+
+    /// Like `insert`, but only the items in slots which `active` marks are inserted. The items in
+    /// the other slots are hashed, but do not change the set.
+    pub fn insert_slots<'b, CS: ConstraintSystem<E>>(
         self,
         mut cs: CS,
         items: &mut Vec<MaybeHashed<E>>,
+        active: &[Boolean],
     ) -> Result<Self, SynthesisError> {
-        let insertions = items
-            .into_iter()
-            .enumerate()
-            .map(|(i, mut slice)| -> Result<Reduced<E>, SynthesisError> {
-                di::modded_di_hash(
-                    cs.namespace(|| format!("hash {}", i)),
-                    &mut slice,
-                    self.params.limb_width,
-                    &self.params.hash_domain(),
-                    &self.offset,
-                    &self.access.1,
-                    &self.params.hasher,
-                )
-            })
-            .collect::<Result<Vec<Reduced<E>>, SynthesisError>>()?;
+        let insertions = self.hash_slots(&mut cs, items, active)?;
         let inner = self.inner.insert(
             cs.namespace(|| "int insertions"),
             &self.access.1,
             &insertions,
         )?;
         let value = self.value.as_ref().and_then(|v| {
-            let is: Option<Vec<Vec<E::Fr>>> = items
-                .into_iter()
-                .map(|i| i.values.iter().map(|n| n.get_value()).collect::<Option<Vec<_>>>())
-                .collect::<Option<Vec<_>>>();
-            is.map(|is| {
+            active_values(items, active).map(|is| {
                 let mut v = v.clone();
                 v.insert_all(is.into_iter());
                 v
@@ -322,6 +395,68 @@ where
             offset: self.offset.clone(),
         })
     }
+
+    /// Hashes `items` to elements, reduced modulo the challenge. The elements of slots which
+    /// `active` does not mark are replaced by one, which leaves the set unchanged.
+    fn hash_slots<CS: ConstraintSystem<E>>(
+        &self,
+        mut cs: CS,
+        items: &mut Vec<MaybeHashed<E>>,
+        active: &[Boolean],
+    ) -> Result<Vec<Reduced<E>>, SynthesisError> {
+        if active.len() != items.len() {
+            eprintln!("{} slots, but {} slot flags", items.len(), active.len());
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let one = BigNat::identity::<CS>(self.params.limb_width);
+        items
+            .iter_mut()
+            .zip(active)
+            .enumerate()
+            .map(|(i, (item, active))| -> Result<Reduced<E>, SynthesisError> {
+                let element = di::modded_di_hash(
+                    cs.namespace(|| format!("hash {}", i)),
+                    item,
+                    self.params.limb_width,
+                    &self.params.hash_domain()?,
+                    &self.offset,
+                    &self.access.1,
+                    &self.params.hasher,
+                )?;
+                match active {
+                    // A slot which is always full needs no selection.
+                    Boolean::Constant(true) => Ok(element),
+                    active => Ok(Reduced::new(
+                        BigNat::conditionally_select(
+                            cs.namespace(|| format!("select raw {}", i)),
+                            &element.raw,
+                            &one,
+                            active,
+                        )?,
+                        BigNat::conditionally_select(
+                            cs.namespace(|| format!("select reduced {}", i)),
+                            &element.reduced,
+                            &one,
+                            active,
+                        )?,
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Like `swap_all`, but the final digest is checked to be `digest` by the last proof of
+    /// exponentiation, instead of being allocated and compared afterwards.
+    pub fn swap_all_to<CS: ConstraintSystem<E>>(
+        self,
+        mut cs: CS,
+        mut removed_items: Vec<MaybeHashed<E>>,
+        mut inserted_items: Vec<MaybeHashed<E>>,
+        digest: CG::Elem,
+    ) -> CResult<Self> {
+        let with = self.insert(cs.namespace(|| "insert"), &mut inserted_items)?;
+        with.remove_to(cs.namespace(|| "remove"), &mut removed_items, digest)
+    }
 }
 
 impl<E, H, CG, Inner> CircuitGenSet for CircuitSet<E, H, CG, Inner>
@@ -345,6 +480,219 @@ where
     }
 }
 
+/// Flags for `n` slots which are always full.
+fn full_slots(n: usize) -> Vec<Boolean> {
+    vec![Boolean::constant(true); n]
+}
+
+/// The values of the items in the slots which `active` marks, if they and the flags are known.
+fn active_values<E: Engine>(
+    items: &[MaybeHashed<E>],
+    active: &[Boolean],
+) -> Option<Vec<Vec<E::Fr>>> {
+    let mut values = Vec::new();
+    for (item, active) in items.iter().zip(active) {
+        if active.get_value()? {
+            values.push(
+                item.values
+                    .iter()
+                    .map(|n| n.get_value())
+                    .collect::<Option<Vec<_>>>()?,
+            );
+        }
+    }
+    Some(values)
+}
+
+/// The least entropy, in bits, of a Fiat-Shamir challenge. With less, a prover could afford to
+/// grind through inputs until the challenge suits them.
+pub const MIN_CHALLENGE_BITS: usize = 128;
+
+/// Derives one Fiat-Shamir challenge for a chain of set transitions.
+///
+/// Every batch's digests and item hashes are absorbed before a single prime is squeezed, so each
+/// link of the chain is checked against the same challenge, which depends on all of them.
+/// Fails if `n_bits_challenge` is below `MIN_CHALLENGE_BITS`.
+pub fn shared_challenge<E, H, CS>(
+    mut cs: CS,
+    digests: &[&BigNat<E>],
+    items: &mut [MaybeHashed<E>],
+    limb_width: usize,
+    n_bits_challenge: usize,
+    hasher: &H,
+) -> CResult<BigNat<E>>
+where
+    E: Engine,
+    H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
+    CS: ConstraintSystem<E>,
+{
+    if n_bits_challenge < MIN_CHALLENGE_BITS {
+        return Err(ParamError::ChallengeTooNarrow { n_bits_challenge }.into());
+    }
+    let mut to_hash_to_challenge: Vec<AllocatedNum<E>> = Vec::new();
+    for (i, digest) in digests.iter().enumerate() {
+        for (j, limb) in digest.as_limbs::<CS>().into_iter().enumerate() {
+            to_hash_to_challenge.push(
+                limb.as_sapling_allocated_num(cs.namespace(|| format!("digest {} limb {}", i, j)))?,
+            );
+        }
+    }
+    for (i, item) in items.iter_mut().enumerate() {
+        to_hash_to_challenge.push(item.get_hash(|values| {
+            hasher.allocate_hash(cs.namespace(|| format!("item hash {}", i)), values)
+        })?);
+    }
+    pocklington::hash_to_pocklington_prime(
+        cs.namespace(|| "challenge hash"),
+        &to_hash_to_challenge,
+        limb_width,
+        n_bits_challenge,
+        hasher,
+    )
+}
+
+/// Computes the value of `shared_challenge` outside of the circuit, for digests of `n_limbs`
+/// limbs each and unhashed items. Returns `None` if a digest does not fit, the challenge is too
+/// narrow, or no prime is found.
+pub fn shared_challenge_value<H: Hasher>(
+    digests: &[&BigUint],
+    items: &[Vec<H::F>],
+    limb_width: usize,
+    n_limbs: usize,
+    n_bits_challenge: usize,
+    hasher: &H,
+) -> Option<BigUint> {
+    if n_bits_challenge < MIN_CHALLENGE_BITS {
+        return None;
+    }
+    let mut to_hash_to_challenge: Vec<H::F> = Vec::new();
+    for digest in digests {
+        to_hash_to_challenge.extend(nat_to_limbs::<H::F>(digest, limb_width, n_limbs).ok()?);
+    }
+    for item in items {
+        to_hash_to_challenge.push(hasher.hash(item));
+    }
+    pocklington::helper::hash_to_pocklington_prime(
+        &to_hash_to_challenge,
+        n_bits_challenge,
+        hasher,
+    )
+    .map(|cert| cert.number().clone())
+}
+
+/// The items of a batch of swaps, as decimal field elements. The set initially holds `initial`,
+/// of which `to_remove` are removed, and then `to_insert` are inserted.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetInputsJson {
+    pub initial: Vec<Vec<String>>,
+    pub to_remove: Vec<Vec<String>>,
+    pub to_insert: Vec<Vec<String>>,
+}
+
+/// How the field elements of items are written as strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemEncoding {
+    /// In decimal.
+    Decimal,
+    /// In `0x`-prefixed, big-endian hex, such as a 32-byte hash. The value must be below the field
+    /// modulus: it is not reduced.
+    Hex,
+}
+
+impl ItemEncoding {
+    pub fn parse<F: PrimeField>(&self, s: &str) -> Result<F, InputError> {
+        let f = match self {
+            ItemEncoding::Decimal => F::from_str(s),
+            ItemEncoding::Hex if s.starts_with("0x") => BigUint::parse_bytes(s[2..].as_bytes(), 16)
+                .and_then(|n| nat_to_f(&n)),
+            ItemEncoding::Hex => None,
+        };
+        f.ok_or_else(|| InputError::BadField(s.to_owned()))
+    }
+}
+
+/// Inputs passed to `SetBenchInputs::new` or `SetBenchInputs::from_json` which do not fit the
+/// circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputError {
+    /// The string is not a field element in the expected `ItemEncoding`.
+    BadField(String),
+    /// An item has `got` field elements, but the circuit takes items of `expected`.
+    WidthMismatch { expected: usize, got: usize },
+    /// The item appears twice among the removals, or twice among the insertions.
+    DuplicateElement(Vec<String>),
+    /// There are `got` removals, but the circuit makes `expected`.
+    RemovalCount { expected: usize, got: usize },
+    /// There are `got` insertions, but the circuit makes `expected`.
+    InsertionCount { expected: usize, got: usize },
+    /// The item to remove is not in the initial set.
+    MissingItem(Vec<String>),
+    /// An inserted item is already in the set, but the circuit requires unique insertions.
+    InsertedItemPresent,
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            InputError::BadField(s) => write!(f, "{} is not a field element", s),
+            InputError::WidthMismatch { expected, got } => {
+                write!(f, "an item has {} elements, not {}", got, expected)
+            }
+            InputError::DuplicateElement(item) => write!(
+                f,
+                "the item ({}) is removed or inserted twice in one batch",
+                item.join(", ")
+            ),
+            InputError::RemovalCount { expected, got } => {
+                write!(f, "{} removals, but the circuit has {}", got, expected)
+            }
+            InputError::InsertionCount { expected, got } => {
+                write!(f, "{} insertions, but the circuit has {}", got, expected)
+            }
+            InputError::MissingItem(item) => {
+                write!(f, "the item ({}) to remove is not in the set", item.join(", "))
+            }
+            InputError::InsertedItemPresent => write!(f, "an inserted item is already in the set"),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Parses `items`, each of which must have `width` field elements in `encoding`.
+fn parse_items<F: PrimeField>(
+    items: &[Vec<String>],
+    encoding: ItemEncoding,
+    width: usize,
+) -> Result<Vec<Vec<F>>, InputError> {
+    items
+        .iter()
+        .map(|item| {
+            if item.len() != width {
+                return Err(InputError::WidthMismatch {
+                    expected: width,
+                    got: item.len(),
+                });
+            }
+            item.iter().map(|e| encoding.parse(e)).collect()
+        })
+        .collect()
+}
+
+/// Checks that no two of `items` are equal, reporting the later of the first equal pair as it is
+/// written in `raw`.
+pub fn check_distinct<F: PrimeField>(
+    items: &[Vec<F>],
+    raw: &[Vec<String>],
+) -> Result<(), InputError> {
+    for (i, item) in items.iter().enumerate() {
+        if items[..i].contains(item) {
+            return Err(InputError::DuplicateElement(raw[i].clone()));
+        }
+    }
+    Ok(())
+}
+
 pub struct SetBenchInputs<H, Inner>
 where
     H: Hasher,
@@ -357,6 +705,8 @@ where
     pub to_remove: Vec<Vec<H::F>>,
     /// The items to insert into the set
     pub to_insert: Vec<Vec<H::F>>,
+    /// The challenge, if it is a public input. See `ChallengeSource`.
+    pub challenge: Option<BigUint>,
 }
 
 impl<H, Inner> SetBenchInputs<H, Inner>
@@ -365,17 +715,15 @@ where
     Inner: IntSet<G = RsaQuotientGroup>,
 {
     /// Creates an input to the set benchmark in which fixed numbers of items are present but
-    /// unmodified, a fixed number of items are removed, and a fixed number are added.
+    /// unmodified, a fixed number of items are removed, and a fixed number are added. The items
+    /// have `params.item_size` elements, and are hashed as `params` says.
     pub fn from_counts(
         n_untouched: usize,
         n_removed: usize,
         n_inserted: usize,
-        item_len: usize,
-        hasher: H,
-        n_bits_elem: usize,
-        limb_width: usize,
-        group: RsaQuotientGroup,
+        params: &SetBenchParams<H>,
     ) -> Self {
+        let item_len = params.item_size;
         let untouched_items: Vec<Vec<String>> = (0..n_untouched)
             .map(|i| {
                 (0..item_len)
@@ -398,44 +746,118 @@ where
             })
             .collect();
 
-        Self::new(
+        Self::new(untouched_items, removed_items, inserted_items, params)
+            .expect("generated items are well-formed")
+    }
+
+    /// Creates an input to the set benchmark from items of decimal field elements, hashed as
+    /// `params` says. Every item must have `params.item_size` elements, and no item may be
+    /// removed twice or inserted twice.
+    pub fn new(
+        untouched_items: Vec<Vec<String>>,
+        removed_items: Vec<Vec<String>>,
+        inserted_items: Vec<Vec<String>>,
+        params: &SetBenchParams<H>,
+    ) -> Result<Self, InputError> {
+        Self::new_encoded(
             untouched_items,
             removed_items,
             inserted_items,
-            hasher,
-            n_bits_elem,
-            limb_width,
-            group,
+            ItemEncoding::Decimal,
+            params,
         )
     }
-    pub fn new(
+
+    /// Like `new`, but with the field elements of the items in `encoding`.
+    pub fn new_encoded(
         untouched_items: Vec<Vec<String>>,
         removed_items: Vec<Vec<String>>,
         inserted_items: Vec<Vec<String>>,
-        hasher: H,
-        n_bits_elem: usize,
-        limb_width: usize,
-        group: RsaQuotientGroup,
+        encoding: ItemEncoding,
+        params: &SetBenchParams<H>,
+    ) -> Result<Self, InputError> {
+        let parse = |items: &[Vec<String>]| -> Result<Vec<Vec<H::F>>, InputError> {
+            parse_items(items, encoding, params.item_size)
+        };
+        let removed = parse(&removed_items)?;
+        let inserted = parse(&inserted_items)?;
+        check_distinct(&removed, &removed_items)?;
+        check_distinct(&inserted, &inserted_items)?;
+        Ok(Self::from_items(parse(&untouched_items)?, removed, inserted, params))
+    }
+
+    /// Creates a random input to the set benchmark, shaped by `params`. The initial set holds
+    /// `params.n_removes` items to remove and a few untouched ones, all random, and
+    /// `params.n_inserts` fresh random items are inserted.
+    pub fn random<R: Rng>(rng: &mut R, params: &SetBenchParams<H>) -> Self {
+        let n_untouched = rng.gen_range(0, MAX_RANDOM_UNTOUCHED + 1);
+        let mut members = random_items(rng, n_untouched + params.n_removes, params.item_size);
+        rng.shuffle(&mut members);
+        let removed = members.split_off(n_untouched);
+        let inserted = random_items(rng, params.n_inserts, params.item_size);
+        Self::from_items(members, removed, inserted, params)
+    }
+
+    /// Creates an input to the set benchmark from concrete items, checking that they fit
+    /// `params`: every item has `params.item_size` field elements, there are `params.n_removes`
+    /// removals and `params.n_inserts` insertions, and every removed item is initially present.
+    pub fn from_json(
+        json: &SetInputsJson,
+        params: &SetBenchParams<H>,
+    ) -> Result<Self, InputError> {
+        let parse = |items: &[Vec<String>]| -> Result<Vec<Vec<H::F>>, InputError> {
+            parse_items(items, ItemEncoding::Decimal, params.item_size)
+        };
+        let mut untouched = parse(&json.initial)?;
+        let removed = parse(&json.to_remove)?;
+        let inserted = parse(&json.to_insert)?;
+        check_distinct(&removed, &json.to_remove)?;
+        check_distinct(&inserted, &json.to_insert)?;
+        if removed.len() != params.n_removes {
+            return Err(InputError::RemovalCount {
+                expected: params.n_removes,
+                got: removed.len(),
+            });
+        }
+        if inserted.len() != params.n_inserts {
+            return Err(InputError::InsertionCount {
+                expected: params.n_inserts,
+                got: inserted.len(),
+            });
+        }
+        for (item, raw) in removed.iter().zip(&json.to_remove) {
+            match untouched.iter().position(|u| u == item) {
+                Some(j) => {
+                    untouched.swap_remove(j);
+                }
+                None => return Err(InputError::MissingItem(raw.clone())),
+            }
+        }
+        let inputs = Self::from_items(untouched, removed, inserted, params);
+        if params.unique_inserts {
+            inputs
+                .check_unique_inserts()
+                .map_err(|_| InputError::InsertedItemPresent)?;
+        }
+        Ok(inputs)
+    }
+
+    /// Creates an input to the set benchmark in which the set initially holds `untouched` and
+    /// `removed`, and finally holds `untouched` and `inserted`. The items are hashed as `params`
+    /// says.
+    pub fn from_items(
+        untouched: Vec<Vec<H::F>>,
+        removed: Vec<Vec<H::F>>,
+        inserted: Vec<Vec<H::F>>,
+        params: &SetBenchParams<H>,
     ) -> Self {
-        let untouched: Vec<Vec<H::F>> = untouched_items
-            .iter()
-            .map(|i| i.iter().map(|j| H::F::from_str(j).unwrap()).collect())
-            .collect();
-        let removed: Vec<Vec<H::F>> = removed_items
-            .iter()
-            .map(|i| i.iter().map(|j| H::F::from_str(j).unwrap()).collect())
-            .collect();
-        let inserted: Vec<Vec<H::F>> = inserted_items
-            .iter()
-            .map(|i| i.iter().map(|j| H::F::from_str(j).unwrap()).collect())
-            .collect();
-        let offset = di::offset(n_bits_elem);
         let mut initial_state = Set::new_with(
-            group,
-            offset,
-            hasher,
-            n_bits_elem,
-            limb_width,
+            params.group.clone(),
+            di::offset(params.n_bits_elem),
+            params.hasher.clone(),
+            params.n_bits_elem,
+            params.n_trailing_ones,
+            params.limb_width,
             untouched.iter().map(|v| v.as_slice()),
         );
         let mut final_state = initial_state.clone();
@@ -446,125 +868,576 @@ where
             final_digest: final_state.digest(),
             to_remove: removed,
             to_insert: inserted,
+            challenge: None,
+        }
+    }
+
+    /// Checks that each inserted item is absent from the set once the removals are made, and
+    /// from the other insertions.
+    pub fn check_unique_inserts(&self) -> Result<(), DuplicateItem> {
+        let mut set = self.initial_state.clone();
+        set.remove_all(self.to_remove.iter().map(Vec::as_slice));
+        for item in &self.to_insert {
+            set.insert_unique(item.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Computes the final digest which the circuit for `params` checks, from scratch: the items
+    /// are hashed to elements as the circuit hashes them, with the widths in `params` rather than
+    /// those the initial state was built with, and the generator of `params.group` is raised to
+    /// each member of the final multiset in turn.
+    ///
+    /// This should equal `final_digest`. If it does not, the inputs do not match the circuit.
+    /// Returns `MissingItem` if a removed item is not in the initial state.
+    pub fn expected_transition(&self, params: &SetBenchParams<H>) -> Result<BigUint, MissingItem> {
+        let hashing: Set<H, Inner> = Set::new_with(
+            params.group.clone(),
+            di::offset(params.n_bits_elem),
+            params.hasher.clone(),
+            params.n_bits_elem,
+            params.n_trailing_ones,
+            params.limb_width,
+            std::iter::empty(),
+        );
+        let mut members = self.initial_state.members();
+        for item in &self.to_remove {
+            let element = hashing.element(item);
+            let i = members
+                .iter()
+                .position(|m| m == &element)
+                .ok_or(MissingItem)?;
+            members.swap_remove(i);
+        }
+        members.extend(self.to_insert.iter().map(|item| hashing.element(item)));
+        Ok(members
+            .iter()
+            .fold(params.group.generator(), |digest, element| {
+                params.group.power(&digest, element)
+            }))
+    }
+}
+
+/// The most members which `SetBenchInputs::random` leaves untouched.
+const MAX_RANDOM_UNTOUCHED: usize = 3;
+
+#[cfg(feature = "parallel")]
+impl<H, Inner> SetBenchInputs<H, Inner>
+where
+    H: Hasher + Sync,
+    H::F: Send + Sync,
+    Inner: IntSet<G = RsaQuotientGroup>,
+{
+    /// Like `from_items`, but every item is hashed up front, in parallel, and the sets are then
+    /// built from the elements. The circuit still hashes the items as it allocates them.
+    pub fn par_from_items(
+        untouched: Vec<Vec<H::F>>,
+        removed: Vec<Vec<H::F>>,
+        inserted: Vec<Vec<H::F>>,
+        params: &SetBenchParams<H>,
+    ) -> Self {
+        let empty: Set<H, Inner> = Set::new_with(
+            params.group.clone(),
+            di::offset(params.n_bits_elem),
+            params.hasher.clone(),
+            params.n_bits_elem,
+            params.n_trailing_ones,
+            params.limb_width,
+            std::iter::empty(),
+        );
+        let (inner, final_digest) = Inner::transition(
+            params.group.clone(),
+            empty.par_elements(&untouched),
+            empty.par_elements(&removed),
+            empty.par_elements(&inserted),
+        );
+        SetBenchInputs {
+            initial_state: Set { inner, ..empty },
+            final_digest,
+            to_remove: removed,
+            to_insert: inserted,
+            challenge: None,
         }
     }
 }
 
+fn random_items<R: Rng, F: PrimeField>(
+    rng: &mut R,
+    n_items: usize,
+    item_size: usize,
+) -> Vec<Vec<F>> {
+    (0..n_items)
+        .map(|_| (0..item_size).map(|_| rng.gen()).collect())
+        .collect()
+}
+
+/// Where the prime challenge for the proofs of exponentiation comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChallengeSource {
+    /// Derived in the circuit, by hashing the digests and items to a prime.
+    FiatShamir,
+    /// Supplied by the verifier, e.g. from a randomness beacon, as a public input of
+    /// `n_bits_challenge` bits. The circuit does not check that it is prime: the verifier must.
+    PublicInput,
+}
+
 pub struct SetBenchParams<H> {
     pub group: RsaQuotientGroup,
     pub limb_width: usize,
     pub n_bits_base: usize,
     pub n_bits_elem: usize,
+    /// The number of low bits of each element which are fixed to one. Usually 1.
+    pub n_trailing_ones: usize,
     pub n_bits_challenge: usize,
+    pub challenge_source: ChallengeSource,
     pub item_size: usize,
     pub n_removes: usize,
     pub n_inserts: usize,
+    /// Whether a batch may fill fewer than `n_removes` removal slots and `n_inserts` insertion
+    /// slots. The items fill the first slots, and each slot has a flag, which the challenge
+    /// depends on, marking whether it is full. Otherwise every slot must be full, and needs no
+    /// flag.
+    pub optional_slots: bool,
+    /// The window size for exponentiation in the group. If `None`, it is chosen automatically.
+    pub window_bits: Option<usize>,
+    /// Whether the set has set semantics: inserted items must be absent once the removals are
+    /// made. Otherwise it is a multiset. This is only checked off-circuit, before synthesis.
+    pub unique_inserts: bool,
     pub hasher: H,
     pub verbose: bool,
 }
 
-pub struct SetBench<H, Inner>
-where
-    H: Hasher,
-    Inner: IntSet,
-{
-    pub inputs: Option<SetBenchInputs<H, Inner>>,
-    pub params: SetBenchParams<H>,
+/// An inconsistent combination of `SetBenchParams`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParamError {
+    /// The base does not fill whole limbs.
+    Limbs(RsaGroupParamsError),
+    /// The group modulus does not fit in the base.
+    ModulusTooWide { n_bits_modulus: usize, n_bits_base: usize },
+    /// Elements are wider than the base.
+    ElemTooWide { n_bits_elem: usize, n_bits_base: usize },
+    /// Elements cannot be hashed to, as the trailing ones are missing or fill them.
+    HashDomain(HashDomainError),
+    /// The challenge has less entropy than `MIN_CHALLENGE_BITS`.
+    ChallengeTooNarrow { n_bits_challenge: usize },
+    /// There are no challenges, so the swap would never be checked.
+    NoChallenges,
 }
 
-impl<E, H> Circuit<E> for SetBench<H, NaiveExpSet<RsaQuotientGroup>>
-where
-    E: Engine,
-    H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
-{
-    fn synthesize<CS: ConstraintSystem<E>>(mut self, cs: &mut CS) -> Result<(), SynthesisError> {
-        if self.params.verbose {
-            println!("Allocating Deletions...");
+impl Display for ParamError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ParamError::Limbs(e) => write!(f, "{}", e),
+            ParamError::ModulusTooWide {
+                n_bits_modulus,
+                n_bits_base,
+            } => write!(
+                f,
+                "the modulus has {} bits, but the base only {}",
+                n_bits_modulus, n_bits_base
+            ),
+            ParamError::ElemTooWide {
+                n_bits_elem,
+                n_bits_base,
+            } => write!(
+                f,
+                "elements have {} bits, but the base only {}",
+                n_bits_elem, n_bits_base
+            ),
+            ParamError::HashDomain(e) => write!(f, "{}", e),
+            ParamError::ChallengeTooNarrow { n_bits_challenge } => write!(
+                f,
+                "a {}-bit challenge is narrower than the minimum of {} bits",
+                n_bits_challenge, MIN_CHALLENGE_BITS
+            ),
+            ParamError::NoChallenges => write!(f, "there must be at least one challenge"),
         }
-        let removals = (0..self.params.n_removes)
-            .map(|i| {
-                let mut cs = cs.namespace(|| "init removals");
-                let values = (0..self.params.item_size)
-                    .map(|j| {
-                        AllocatedNum::alloc(cs.namespace(|| format!("alloc {} {}", i, j)), || {
-                            Ok(**self.inputs.grab()?.to_remove.get(i).grab()?.get(j).grab()?)
-                        })
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
-                let hash = self.params.hasher.allocate_hash(cs.namespace(|| format!("hash {}", i)), &values)?;
-                Ok(MaybeHashed::new(values, hash))
-            })
-            .collect::<Result<Vec<MaybeHashed<E>>, SynthesisError>>()?;
+    }
+}
 
-        if self.params.verbose {
-            println!("Allocating Insertions...");
-        }
-        let insertions = (0..self.params.n_inserts)
-            .map(|i| {
-                let mut cs = cs.namespace(|| "init insertions");
-                let values = (0..self.params.item_size)
-                    .map(|j| {
-                        AllocatedNum::alloc(cs.namespace(|| format!("alloc {} {}", i, j)), || {
-                            Ok(**self.inputs.grab()?.to_insert.get(i).grab()?.get(j).grab()?)
-                        })
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
-                let hash = self.params.hasher.allocate_hash(cs.namespace(|| format!("hash {}", i)), &values)?;
-                Ok(MaybeHashed::new(values, hash))
-            })
-            .collect::<Result<Vec<MaybeHashed<E>>, SynthesisError>>()?;
+impl std::error::Error for ParamError {}
 
-        let limb_width = self.params.limb_width;
-        let n_bits_base = self.params.n_bits_base;
-        let expected_initial_digest = BigNat::alloc_from_nat(
-            cs.namespace(|| "expected_initial_digest"),
-            || Ok(self.inputs.as_mut().ok_or(SynthesisError::AssignmentMissing)?.initial_state.digest()),
-            limb_width,
-            n_bits_base / limb_width,
-        )?;
-        let expected_final_digest = BigNat::alloc_from_nat(
+impl From<ParamError> for SynthesisError {
+    fn from(e: ParamError) -> Self {
+        eprintln!("{}", e);
+        SynthesisError::Unsatisfiable
+    }
+}
+
+impl From<RsaGroupParamsError> for ParamError {
+    fn from(e: RsaGroupParamsError) -> Self {
+        ParamError::Limbs(e)
+    }
+}
+
+impl From<HashDomainError> for ParamError {
+    fn from(e: HashDomainError) -> Self {
+        ParamError::HashDomain(e)
+    }
+}
+
+/// Builds `SetBenchParams`, defaulting everything but the group, hasher, and item size.
+///
+/// By default there are 32-bit limbs, a base as wide as the modulus, 128-bit elements with one
+/// trailing one, a 128-bit challenge, and a single removal and insertion, under multiset
+/// semantics.
+pub struct SetBenchParamsBuilder<H> {
+    params: SetBenchParams<H>,
+}
+
+impl<H> SetBenchParamsBuilder<H> {
+    pub fn new(group: RsaQuotientGroup, hasher: H, item_size: usize) -> Self {
+        let n_bits_base = group.m.bits();
+        Self {
+            params: SetBenchParams {
+                group,
+                limb_width: 32,
+                n_bits_base,
+                n_bits_elem: 128,
+                n_trailing_ones: 1,
+                n_bits_challenge: 128,
+                challenge_source: ChallengeSource::FiatShamir,
+                item_size,
+                n_removes: 1,
+                n_inserts: 1,
+                window_bits: None,
+                unique_inserts: false,
+                optional_slots: false,
+                hasher,
+                verbose: false,
+            },
+        }
+    }
+
+    pub fn with_limb_width(mut self, limb_width: usize) -> Self {
+        self.params.limb_width = limb_width;
+        self
+    }
+
+    pub fn with_n_bits_base(mut self, n_bits_base: usize) -> Self {
+        self.params.n_bits_base = n_bits_base;
+        self
+    }
+
+    pub fn with_n_bits_elem(mut self, n_bits_elem: usize) -> Self {
+        self.params.n_bits_elem = n_bits_elem;
+        self
+    }
+
+    pub fn with_n_trailing_ones(mut self, n_trailing_ones: usize) -> Self {
+        self.params.n_trailing_ones = n_trailing_ones;
+        self
+    }
+
+    pub fn with_n_bits_challenge(mut self, n_bits_challenge: usize) -> Self {
+        self.params.n_bits_challenge = n_bits_challenge;
+        self
+    }
+
+    pub fn with_challenge_source(mut self, challenge_source: ChallengeSource) -> Self {
+        self.params.challenge_source = challenge_source;
+        self
+    }
+
+    pub fn with_swaps(mut self, n_removes: usize, n_inserts: usize) -> Self {
+        self.params.n_removes = n_removes;
+        self.params.n_inserts = n_inserts;
+        self
+    }
+
+    pub fn with_window_bits(mut self, window_bits: usize) -> Self {
+        self.params.window_bits = Some(window_bits);
+        self
+    }
+
+    pub fn with_optional_slots(mut self, optional_slots: bool) -> Self {
+        self.params.optional_slots = optional_slots;
+        self
+    }
+
+    pub fn with_unique_inserts(mut self, unique_inserts: bool) -> Self {
+        self.params.unique_inserts = unique_inserts;
+        self
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.params.verbose = verbose;
+        self
+    }
+
+    /// Checks that the widths are consistent: the base fills whole limbs and holds both the
+    /// modulus and the elements, which form a valid `HashDomain`, and the challenge is at least
+    /// `MIN_CHALLENGE_BITS` wide.
+    pub fn build(self) -> Result<SetBenchParams<H>, ParamError> {
+        let p = self.params;
+        CircuitRsaGroupParams::new(p.limb_width, p.n_bits_base)?;
+        let n_bits_modulus = p.group.m.bits();
+        if n_bits_modulus > p.n_bits_base {
+            return Err(ParamError::ModulusTooWide {
+                n_bits_modulus,
+                n_bits_base: p.n_bits_base,
+            });
+        }
+        if p.n_bits_elem > p.n_bits_base {
+            return Err(ParamError::ElemTooWide {
+                n_bits_elem: p.n_bits_elem,
+                n_bits_base: p.n_bits_base,
+            });
+        }
+        HashDomain::new(p.n_bits_elem, p.n_trailing_ones)?;
+        if p.n_bits_challenge < MIN_CHALLENGE_BITS {
+            return Err(ParamError::ChallengeTooNarrow {
+                n_bits_challenge: p.n_bits_challenge,
+            });
+        }
+        Ok(p)
+    }
+}
+
+impl<H> SetBenchParams<H> {
+    /// The limbs of the group's generator and modulus, and of the digests, which are all
+    /// `n_bits_base` wide.
+    pub fn group_params(&self) -> Result<CircuitRsaGroupParams, RsaGroupParamsError> {
+        Ok(CircuitRsaGroupParams {
+            window_bits: self.window_bits,
+            ..CircuitRsaGroupParams::new(self.limb_width, self.n_bits_base)?
+        })
+    }
+
+    /// The number of limbs of a challenge supplied as a public input.
+    pub fn n_challenge_limbs(&self) -> usize {
+        (self.n_bits_challenge + self.limb_width - 1) / self.limb_width
+    }
+}
+
+/// The `j`th value of the item in slot `i`. Empty slots hold zeros, if slots are optional.
+fn slot_value<F: PrimeField>(
+    items: &[Vec<F>],
+    i: usize,
+    j: usize,
+    optional_slots: bool,
+) -> Result<F, SynthesisError> {
+    match items.get(i) {
+        Some(item) => Ok(*item.get(j).grab()?),
+        None if optional_slots => Ok(F::zero()),
+        None => Err(SynthesisError::AssignmentMissing),
+    }
+}
+
+/// Flags for `n_slots` slots, of which the first `n_items` are full. If slots are not optional,
+/// they are all full, and the flags are constants.
+fn alloc_slot_flags<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    n_slots: usize,
+    n_items: Option<usize>,
+    optional_slots: bool,
+) -> CResult<Vec<Boolean>> {
+    if !optional_slots {
+        return Ok(full_slots(n_slots));
+    }
+    (0..n_slots)
+        .map(|i| {
+            Ok(Boolean::from(AllocatedBit::alloc(
+                cs.namespace(|| format!("slot {}", i)),
+                n_items.map(|n| i < n),
+            )?))
+        })
+        .collect()
+}
+
+/// Packs slot flags into as few field elements as possible, so that the challenge can depend on
+/// them.
+fn pack_slot_flags<E: Engine, CS: ConstraintSystem<E>>(
+    mut cs: CS,
+    flags: &[Boolean],
+) -> CResult<Vec<MaybeHashed<E>>> {
+    flags
+        .chunks(E::Fr::CAPACITY as usize)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let value = chunk.iter().rev().try_fold(E::Fr::zero(), |mut acc, flag| {
+                acc.double();
+                if flag.get_value()? {
+                    acc.add_assign(&E::Fr::one());
+                }
+                Some(acc)
+            });
+            let packed = AllocatedNum::alloc(cs.namespace(|| format!("packed {}", i)), || {
+                Ok(*value.grab()?)
+            })?;
+            let mut sum = LinearCombination::zero();
+            let mut coeff = E::Fr::one();
+            for flag in chunk {
+                sum = sum + &flag.lc(CS::one(), coeff);
+                coeff.double();
+            }
+            cs.enforce(
+                || format!("pack {}", i),
+                |lc| lc,
+                |lc| lc,
+                |lc| lc + packed.get_variable() - &sum,
+            );
+            Ok(MaybeHashed::from_values(vec![packed]))
+        })
+        .collect()
+}
+
+pub struct SetBench<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet,
+{
+    pub inputs: Option<SetBenchInputs<H, Inner>>,
+    pub params: SetBenchParams<H>,
+}
+
+impl<H, Inner> SetBench<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet<G = RsaQuotientGroup>,
+{
+    /// The public inputs of the circuit, in the order it allocates them: the limbs of the group's
+    /// generator and modulus, and then those of the initial and final digests.
+    /// Each has `n_bits_base / limb_width` limbs, and this is `None` if that does not divide
+    /// evenly. If the challenge is a public input, its `n_challenge_limbs` limbs come last.
+    pub fn public_inputs(&mut self) -> Option<Vec<H::F>> {
+        let group_params = self.params.group_params().ok()?;
+        let (limb_width, n_limbs) = (group_params.limb_width, group_params.n_limbs);
+        let inputs = self.inputs.as_mut()?;
+        let group = inputs.initial_state.group().clone();
+        let mut public = Vec::new();
+        for n in &[group.g, group.m] {
+            public.extend(nat_to_limbs::<H::F>(n, limb_width, n_limbs).ok()?);
+        }
+        public.extend(inputs.initial_state.digest_limbs(limb_width, n_limbs).ok()?);
+        public.extend(nat_to_limbs::<H::F>(&inputs.final_digest, limb_width, n_limbs).ok()?);
+        if self.params.challenge_source == ChallengeSource::PublicInput {
+            let challenge = inputs.challenge.as_ref()?;
+            let n_limbs = self.params.n_challenge_limbs();
+            public.extend(nat_to_limbs::<H::F>(challenge, limb_width, n_limbs).ok()?);
+        }
+        Some(public)
+    }
+}
+
+impl<E, H> Circuit<E> for SetBench<H, NaiveExpSet<RsaQuotientGroup>>
+where
+    E: Engine,
+    H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(mut self, cs: &mut CS) -> Result<(), SynthesisError> {
+        // A challenge supplied as a public input must be as wide as a Fiat-Shamir one.
+        let n_bits_challenge = self.params.n_bits_challenge;
+        if n_bits_challenge < MIN_CHALLENGE_BITS {
+            return Err(ParamError::ChallengeTooNarrow { n_bits_challenge }.into());
+        }
+        if self.params.unique_inserts {
+            if let Some(inputs) = self.inputs.as_ref() {
+                inputs.check_unique_inserts()?;
+            }
+        }
+        let optional_slots = self.params.optional_slots;
+        if self.params.verbose {
+            info!("Allocating Deletions...");
+        }
+        let mut removals = (0..self.params.n_removes)
+            .map(|i| {
+                let mut cs = cs.namespace(|| "init removals");
+                let values = (0..self.params.item_size)
+                    .map(|j| {
+                        AllocatedNum::alloc(cs.namespace(|| format!("alloc {} {}", i, j)), || {
+                            slot_value(&self.inputs.grab()?.to_remove, i, j, optional_slots)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let hash = self.params.hasher.allocate_hash(cs.namespace(|| format!("hash {}", i)), &values)?;
+                Ok(MaybeHashed::new(values, hash))
+            })
+            .collect::<Result<Vec<MaybeHashed<E>>, SynthesisError>>()?;
+
+        if self.params.verbose {
+            info!("Allocating Insertions...");
+        }
+        let mut insertions = (0..self.params.n_inserts)
+            .map(|i| {
+                let mut cs = cs.namespace(|| "init insertions");
+                let values = (0..self.params.item_size)
+                    .map(|j| {
+                        AllocatedNum::alloc(cs.namespace(|| format!("alloc {} {}", i, j)), || {
+                            slot_value(&self.inputs.grab()?.to_insert, i, j, optional_slots)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let hash = self.params.hasher.allocate_hash(cs.namespace(|| format!("hash {}", i)), &values)?;
+                Ok(MaybeHashed::new(values, hash))
+            })
+            .collect::<Result<Vec<MaybeHashed<E>>, SynthesisError>>()?;
+
+        let removal_slots = alloc_slot_flags(
+            cs.namespace(|| "removal slots"),
+            self.params.n_removes,
+            self.inputs.as_ref().map(|i| i.to_remove.len()),
+            optional_slots,
+        )?;
+        let insertion_slots = alloc_slot_flags(
+            cs.namespace(|| "insertion slots"),
+            self.params.n_inserts,
+            self.inputs.as_ref().map(|i| i.to_insert.len()),
+            optional_slots,
+        )?;
+
+        let group_params = self.params.group_params()?;
+        let expected_initial_digest = BigNat::alloc_from_nat(
+            cs.namespace(|| "expected_initial_digest"),
+            || Ok(self.inputs.as_mut().ok_or(SynthesisError::AssignmentMissing)?.initial_state.digest()),
+            group_params.limb_width,
+            group_params.n_limbs,
+        )?;
+        let expected_final_digest = BigNat::alloc_from_nat(
             cs.namespace(|| "expected_final_digest"),
             || Ok(self.inputs.as_ref().grab()?.final_digest.clone()),
-            self.params.limb_width,
-            self.params.n_bits_base / self.params.limb_width,
+            group_params.limb_width,
+            group_params.n_limbs,
         )?;
 
         if self.params.verbose {
-            println!("Hashing everything");
+            info!("Hashing everything");
         }
-        let mut to_hash_to_challenge: Vec<AllocatedNum<E>> = Vec::new();
-        to_hash_to_challenge.extend(
-            expected_initial_digest
-                .as_limbs::<CS>()
-                .into_iter()
-                .enumerate()
-                .map(|(i, n)| {
-                    n.as_sapling_allocated_num(cs.namespace(|| format!("digest hash {}", i)))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        );
-        to_hash_to_challenge.extend(
-            expected_final_digest
-                .as_limbs::<CS>()
-                .into_iter()
-                .enumerate()
-                .map(|(i, n)| {
-                    n.as_sapling_allocated_num(cs.namespace(|| format!("digest hash {}", i)))
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        );
-        to_hash_to_challenge.extend(insertions.iter().map(|i| i.hash.clone().unwrap()));
-        to_hash_to_challenge.extend(removals.iter().map(|i| i.hash.clone().unwrap()));
-        let challenge = pocklington::hash_to_pocklington_prime(
-            cs.namespace(|| "chash"),
-            &to_hash_to_challenge,
-            self.params.limb_width,
-            self.params.n_bits_challenge,
-            &self.params.hasher,
-        )?;
+        let challenge = match self.params.challenge_source {
+            ChallengeSource::FiatShamir => {
+                let mut items: Vec<_> =
+                    insertions.iter().chain(removals.iter()).cloned().collect();
+                if optional_slots {
+                    let flags: Vec<_> =
+                        insertion_slots.iter().chain(&removal_slots).cloned().collect();
+                    items.extend(pack_slot_flags(cs.namespace(|| "pack slots"), &flags)?);
+                }
+                shared_challenge(
+                    cs.namespace(|| "challenge"),
+                    &[&expected_initial_digest, &expected_final_digest],
+                    &mut items,
+                    self.params.limb_width,
+                    self.params.n_bits_challenge,
+                    &self.params.hasher,
+                )?
+            }
+            ChallengeSource::PublicInput => {
+                let challenge = BigNat::alloc_from_nat(
+                    cs.namespace(|| "challenge"),
+                    || Ok(self.inputs.as_ref().grab()?.challenge.grab()?.clone()),
+                    self.params.limb_width,
+                    self.params.n_challenge_limbs(),
+                )?;
+                challenge.assert_bit_length(
+                    cs.namespace(|| "challenge width"),
+                    self.params.n_bits_challenge,
+                )?;
+                challenge
+            }
+        };
 
         if self.params.verbose {
-            println!("Constructing Group");
+            info!("Constructing Group");
         }
         let raw_group = self
             .inputs
@@ -574,23 +1447,26 @@ where
             cs.namespace(|| "group"),
             raw_group.as_ref(),
             (),
-            &CircuitRsaGroupParams {
-                limb_width: self.params.limb_width,
-                n_limbs: self.params.n_bits_base / self.params.limb_width,
-            },
+            &group_params,
         )?;
         group.inputize(cs.namespace(|| "group input"))?;
+        group.enforce_valid_generator(cs.namespace(|| "group generator"))?;
+        let expected_initial_digest = group.canonicalize(
+            cs.namespace(|| "canonical initial digest"),
+            &expected_initial_digest,
+        )?;
 
         if self.params.verbose {
-            println!("Constructing Set");
+            info!("Constructing Set");
         }
         let set: CircuitSet<E, H, CircuitRsaQuotientGroup<E>, NaiveExpSet<RsaQuotientGroup>> = CircuitSet::alloc(
             cs.namespace(|| "set init"),
             self.inputs.as_ref().map(|is| &is.initial_state),
-            (group, challenge),
+            (group, challenge.clone()),
             &CircuitSetParams {
                 hasher: self.params.hasher.clone(),
                 n_bits: self.params.n_bits_elem,
+                n_trailing_ones: self.params.n_trailing_ones,
                 limb_width: self.params.limb_width,
             },
         )?;
@@ -598,22 +1474,286 @@ where
         set.inner.digest.equal(cs.namespace(|| "initial digest matches"), &expected_initial_digest)?;
 
         if self.params.verbose {
-            println!("Swapping elements");
+            info!("Swapping elements");
+        }
+        let new_set = {
+            let mut cs = cs.namespace(|| "swap");
+            let with = set.insert_slots(
+                cs.namespace(|| "insert"),
+                &mut insertions,
+                &insertion_slots,
+            )?;
+            with.remove_slots_to(
+                cs.namespace(|| "remove"),
+                &mut removals,
+                &removal_slots,
+                expected_final_digest,
+            )?
+        };
+
+        new_set.inputize(cs.namespace(|| "final_state input"))?;
+        if self.params.challenge_source == ChallengeSource::PublicInput {
+            challenge.inputize(cs.namespace(|| "challenge input"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Several independent set benchmarks, each with its own group and set, in one circuit. The
+/// shards should share a hasher, so that its parameters are built once.
+pub struct MultiSetBench<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet,
+{
+    pub shards: Vec<SetBench<H, Inner>>,
+}
+
+impl<H, Inner> MultiSetBench<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet<G = RsaQuotientGroup>,
+{
+    /// The public inputs of the circuit: those of each shard, in order.
+    pub fn public_inputs(&mut self) -> Option<Vec<H::F>> {
+        let mut public = Vec::new();
+        for shard in &mut self.shards {
+            public.extend(shard.public_inputs()?);
+        }
+        Some(public)
+    }
+}
+
+impl<E, H> Circuit<E> for MultiSetBench<H, NaiveExpSet<RsaQuotientGroup>>
+where
+    E: Engine,
+    H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        for (i, shard) in self.shards.into_iter().enumerate() {
+            shard.synthesize(&mut cs.namespace(|| format!("shard {}", i)))?;
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of a `Snapshot`: those of a `SetBench`, but with a number of items in place of
+/// the numbers of removals and insertions.
+pub struct SnapshotParams<H> {
+    pub group: RsaQuotientGroup,
+    pub limb_width: usize,
+    pub n_bits_base: usize,
+    pub n_bits_elem: usize,
+    /// The number of low bits of each element which are fixed to one. Usually 1.
+    pub n_trailing_ones: usize,
+    pub n_bits_challenge: usize,
+    pub item_size: usize,
+    pub n_items: usize,
+    /// The window size for exponentiation in the group. If `None`, it is chosen automatically.
+    pub window_bits: Option<usize>,
+    pub hasher: H,
+    pub verbose: bool,
+}
+
+impl<H> SnapshotParams<H> {
+    /// The limbs of the group's generator and modulus, and of the digest, which are all
+    /// `n_bits_base` wide.
+    pub fn group_params(&self) -> Result<CircuitRsaGroupParams, RsaGroupParamsError> {
+        Ok(CircuitRsaGroupParams {
+            window_bits: self.window_bits,
+            ..CircuitRsaGroupParams::new(self.limb_width, self.n_bits_base)?
+        })
+    }
+}
+
+impl<H> SetBenchParams<H> {
+    /// The parameters of a snapshot of `n_items` items, in the same group and with the same
+    /// widths as these, which `SetBenchParamsBuilder::build` has checked.
+    pub fn snapshot_params(self, n_items: usize) -> SnapshotParams<H> {
+        SnapshotParams {
+            group: self.group,
+            limb_width: self.limb_width,
+            n_bits_base: self.n_bits_base,
+            n_bits_elem: self.n_bits_elem,
+            n_trailing_ones: self.n_trailing_ones,
+            n_bits_challenge: self.n_bits_challenge,
+            item_size: self.item_size,
+            n_items,
+            window_bits: self.window_bits,
+            hasher: self.hasher,
+            verbose: self.verbose,
+        }
+    }
+}
+
+pub struct SnapshotInputs<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet,
+{
+    /// The empty set, which the items are inserted into.
+    pub empty_state: Set<H, Inner>,
+    /// The digest of the set holding exactly `items`.
+    pub digest: BigUint,
+    pub items: Vec<Vec<H::F>>,
+}
+
+impl<H, Inner> SnapshotInputs<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet<G = RsaQuotientGroup>,
+{
+    pub fn from_items(
+        items: Vec<Vec<H::F>>,
+        hasher: H,
+        n_bits_elem: usize,
+        n_trailing_ones: usize,
+        limb_width: usize,
+        group: RsaQuotientGroup,
+    ) -> Self {
+        let empty_state: Set<H, Inner> = Set::new_with(
+            group,
+            di::offset(n_bits_elem),
+            hasher,
+            n_bits_elem,
+            n_trailing_ones,
+            limb_width,
+            std::iter::empty(),
+        );
+        let mut full_state = empty_state.clone();
+        full_state.insert_all(items.clone());
+        SnapshotInputs {
+            digest: full_state.digest(),
+            empty_state,
+            items,
+        }
+    }
+}
+
+/// A proof that a digest is that of a set holding some items, without any removals or
+/// insertions: the items are inserted into the empty set, whose digest is the generator, and the
+/// result is checked to be the digest.
+pub struct Snapshot<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet,
+{
+    pub inputs: Option<SnapshotInputs<H, Inner>>,
+    pub params: SnapshotParams<H>,
+}
+
+impl<H, Inner> Snapshot<H, Inner>
+where
+    H: Hasher,
+    Inner: IntSet<G = RsaQuotientGroup>,
+{
+    /// The public inputs of the circuit, in the order it allocates them: the limbs of the group's
+    /// generator and modulus, and then those of the digest. `None` if the group parameters are
+    /// invalid.
+    pub fn public_inputs(&self) -> Option<Vec<H::F>> {
+        let group_params = self.params.group_params().ok()?;
+        let (limb_width, n_limbs) = (group_params.limb_width, group_params.n_limbs);
+        let inputs = self.inputs.as_ref()?;
+        let group = inputs.empty_state.group();
+        let mut public = Vec::new();
+        for n in &[&group.g, &group.m, &inputs.digest] {
+            public.extend(nat_to_limbs::<H::F>(n, limb_width, n_limbs).ok()?);
+        }
+        Some(public)
+    }
+}
+
+impl<E, H> Circuit<E> for Snapshot<H, NaiveExpSet<RsaQuotientGroup>>
+where
+    E: Engine,
+    H: Hasher<F = E::Fr> + CircuitHasher<E = E>,
+{
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        if self.params.verbose {
+            info!("Allocating Items...");
+        }
+        let mut items = (0..self.params.n_items)
+            .map(|i| {
+                let mut cs = cs.namespace(|| "init items");
+                let values = (0..self.params.item_size)
+                    .map(|j| {
+                        AllocatedNum::alloc(cs.namespace(|| format!("alloc {} {}", i, j)), || {
+                            Ok(**self.inputs.grab()?.items.get(i).grab()?.get(j).grab()?)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let hash = self
+                    .params
+                    .hasher
+                    .allocate_hash(cs.namespace(|| format!("hash {}", i)), &values)?;
+                Ok(MaybeHashed::new(values, hash))
+            })
+            .collect::<Result<Vec<MaybeHashed<E>>, SynthesisError>>()?;
+
+        let group_params = self.params.group_params()?;
+        let expected_digest = BigNat::alloc_from_nat(
+            cs.namespace(|| "expected_digest"),
+            || Ok(self.inputs.as_ref().grab()?.digest.clone()),
+            group_params.limb_width,
+            group_params.n_limbs,
+        )?;
+
+        if self.params.verbose {
+            info!("Hashing everything");
         }
-        let new_set = set.swap_all(
-            cs.namespace(|| "swap"),
-            removals,
-            insertions,
+        let challenge = shared_challenge(
+            cs.namespace(|| "challenge"),
+            &[&expected_digest],
+            &mut items,
+            self.params.limb_width,
+            self.params.n_bits_challenge,
+            &self.params.hasher,
         )?;
 
         if self.params.verbose {
-            println!("Verifying resulting digest");
+            info!("Constructing Group");
         }
-        new_set
+        let raw_group = self
+            .inputs
+            .as_ref()
+            .map(|s| s.empty_state.group().clone());
+        let group = CircuitRsaQuotientGroup::alloc(
+            cs.namespace(|| "group"),
+            raw_group.as_ref(),
+            (),
+            &group_params,
+        )?;
+        group.inputize(cs.namespace(|| "group input"))?;
+        group.enforce_valid_generator(cs.namespace(|| "group generator"))?;
+
+        if self.params.verbose {
+            info!("Constructing Set");
+        }
+        let set: CircuitSet<E, H, CircuitRsaQuotientGroup<E>, NaiveExpSet<RsaQuotientGroup>> =
+            CircuitSet::alloc(
+                cs.namespace(|| "set init"),
+                self.inputs.as_ref().map(|is| &is.empty_state),
+                (group.clone(), challenge),
+                &CircuitSetParams {
+                    hasher: self.params.hasher.clone(),
+                    n_bits: self.params.n_bits_elem,
+                    n_trailing_ones: self.params.n_trailing_ones,
+                    limb_width: self.params.limb_width,
+                },
+            )?;
+        set.inner
+            .digest
+            .equal(cs.namespace(|| "empty digest is generator"), &group.g)?;
+
+        if self.params.verbose {
+            info!("Folding items");
+        }
+        let full_set = set.insert(cs.namespace(|| "insert"), &mut items)?;
+        full_set
             .inner
             .digest
-            .equal(cs.namespace(|| "check"), &expected_final_digest)?;
-        new_set.inputize(cs.namespace(|| "final_state input"))?;
+            .equal(cs.namespace(|| "digest matches"), &expected_digest)?;
+        full_set.inputize(cs.namespace(|| "digest input"))?;
         Ok(())
     }
 }
@@ -625,49 +1765,1115 @@ mod test {
     const RSA_2048: &str = "25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525880784406918290641249515082189298559149176184502808489120072844992687392807287776735971418347270261896375014971824691165077613379859095700097330459748808428401797429100642458691817195118746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739934236584823824281198163815010674810451660377306056201619676256133844143603833904414952634432190114657544454178424020924616515723350778707749817125772467962926386356373289912154831438167899885040445364023527381951378636564391212010397122822120720357";
     // From my machine (openssl)
     const RSA_512: &str = "11834783464130424096695514462778870280264989938857328737807205623069291535525952722847913694296392927890261736769191982212777933726583565708193466779811767";
+    // A product of two random 256-bit primes
+    const RSA_512_2: &str = "10919293706927485485517987462452860921585934892767790535275002742212506458970867338869880253422014293058215436914394183835765828532501403748064923086278683";
 
     use super::*;
 
+    use sapling_crypto::bellman::pairing::ff::Field;
+
     use std::str::FromStr;
 
+    use hash::element_of;
     use hash::hashes::Poseidon;
+    use mp::bignat::limbs_to_nat_checked;
 
     use util::test_helpers::*;
 
-    circuit_tests! {
-        small_rsa_1_swap: (SetBench {
+    use rand::{SeedableRng, XorShiftRng};
+
+    fn small_params() -> SetBenchParams<Poseidon<Bn256>> {
+        let group = RsaQuotientGroup {
+            g: BigUint::from(2usize),
+            m: BigUint::from_str(RSA_512).unwrap(),
+        };
+        SetBenchParamsBuilder::new(group, Poseidon::default(), 5)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn params_builder_checks_widths() {
+        type Builder = SetBenchParamsBuilder<Poseidon<Bn256>>;
+        let build = |f: fn(Builder) -> Builder| {
+            let group = RsaQuotientGroup {
+                g: BigUint::from(2usize),
+                m: BigUint::from_str(RSA_512).unwrap(),
+            };
+            f(SetBenchParamsBuilder::new(group, Poseidon::default(), 5))
+                .build()
+                .map(|p| (p.n_bits_base, p.limb_width))
+        };
+        assert_eq!(build(|b| b), Ok((512, 32)));
+        assert_eq!(
+            build(|b| b.with_limb_width(48)),
+            Err(ParamError::Limbs(RsaGroupParamsError::PartialLimb {
+                n_bits_base: 512,
+                limb_width: 48,
+            }))
+        );
+        assert_eq!(
+            build(|b| b.with_n_bits_base(256)),
+            Err(ParamError::ModulusTooWide {
+                n_bits_modulus: 512,
+                n_bits_base: 256,
+            })
+        );
+        assert_eq!(
+            build(|b| b.with_n_bits_elem(1024)),
+            Err(ParamError::ElemTooWide {
+                n_bits_elem: 1024,
+                n_bits_base: 512,
+            })
+        );
+        assert_eq!(
+            build(|b| b.with_n_trailing_ones(128)),
+            Err(ParamError::HashDomain(HashDomainError::NoHashBits {
+                n_bits: 128,
+                n_trailing_ones: 128,
+            }))
+        );
+        // The top bit is fixed too, so this leaves no bits for the hash either.
+        assert_eq!(
+            build(|b| b.with_n_trailing_ones(127)),
+            Err(ParamError::HashDomain(HashDomainError::NoHashBits {
+                n_bits: 128,
+                n_trailing_ones: 127,
+            }))
+        );
+        assert_eq!(
+            build(|b| b.with_n_trailing_ones(0)),
+            Err(ParamError::HashDomain(HashDomainError::NoTrailingOnes))
+        );
+        assert_eq!(
+            build(|b| b.with_n_bits_challenge(64)),
+            Err(ParamError::ChallengeTooNarrow { n_bits_challenge: 64 })
+        );
+    }
+
+    #[test]
+    fn narrow_challenge_fails_loudly() {
+        for &source in &[ChallengeSource::FiatShamir, ChallengeSource::PublicInput] {
+            for &n_bits_challenge in &[0, 64] {
+                let mut bench = asymmetric_bench(1, 1);
+                bench.params.challenge_source = source;
+                bench.params.n_bits_challenge = n_bits_challenge;
+                bench.inputs.as_mut().unwrap().challenge = Some(BigUint::from(3usize));
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                assert!(bench.synthesize(&mut cs).is_err());
+            }
+        }
+        let mut params = small_params();
+        params.n_bits_challenge = 0;
+        assert_eq!(params.n_challenge_limbs(), 0);
+        params.n_bits_challenge = 128;
+        assert_eq!(params.n_challenge_limbs(), 4);
+    }
+
+    #[test]
+    fn rsa_512_public_inputs_have_16_limbs() {
+        let params = small_params();
+        assert_eq!(params.group_params().unwrap().n_limbs, 16);
+        let public = asymmetric_bench(1, 1).public_inputs().unwrap();
+        assert_eq!(public.len(), 4 * 16);
+        let nat = |i: usize| limbs_to_nat_checked(&public[16 * i..16 * (i + 1)], 32).unwrap();
+        assert_eq!(nat(0), params.group.g);
+        assert_eq!(nat(1), params.group.m);
+
+        let mut partial_limbs = asymmetric_bench(1, 1);
+        partial_limbs.params.limb_width = 48;
+        assert_eq!(partial_limbs.public_inputs(), None);
+    }
+
+    #[test]
+    fn challenge_may_be_a_public_input() {
+        // 2^127 - 1, a Mersenne prime
+        let challenge = (BigUint::from(1usize) << 127) - 1usize;
+        for &source in &[ChallengeSource::FiatShamir, ChallengeSource::PublicInput] {
+            let mut bench = asymmetric_bench(1, 1);
+            bench.params.challenge_source = source;
+            bench.inputs.as_mut().unwrap().challenge = Some(challenge.clone());
+            let mut public = bench.public_inputs().unwrap();
+            let is_input = source == ChallengeSource::PublicInput;
+            assert_eq!(public.len(), 4 * 16 + if is_input { 4 } else { 0 });
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            bench.synthesize(&mut cs).expect("synthesis failed");
+            assert!(cs.is_satisfied());
+            assert!(cs.verify(&public));
+            if is_input {
+                public.last_mut().unwrap().add_assign(&<Bn256 as ScalarEngine>::Fr::one());
+                assert!(!cs.verify(&public));
+            }
+        }
+    }
+
+    #[test]
+    fn digest_limbs_match_public_inputs() {
+        let params = small_params();
+        let n_limbs = params.n_bits_base / params.limb_width;
+        let mut bench = asymmetric_bench(1, 1);
+        let public = bench.public_inputs().unwrap();
+        let set = &mut bench.inputs.as_mut().unwrap().initial_state;
+        let limbs = set.digest_limbs(params.limb_width, n_limbs).unwrap();
+        assert_eq!(limbs, nat_to_limbs(&set.digest(), params.limb_width, n_limbs).unwrap());
+        assert_eq!(&limbs[..], &public[2 * n_limbs..3 * n_limbs]);
+        assert!(set.digest_limbs(params.limb_width, n_limbs / 2).is_err());
+    }
+
+    #[test]
+    fn random_inputs_are_satisfied() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for i in 0..100 {
+            let params = small_params();
+            let inputs = SetBenchInputs::random(&mut rng, &params);
+            let circuit = SetBench {
+                inputs: Some(inputs),
+                params,
+            };
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            circuit.synthesize(&mut cs).expect("synthesis failed");
+            if !cs.is_satisfied() {
+                panic!("random input {} is unsatisfied: {:?}", i, cs.which_is_unsatisfied());
+            }
+        }
+    }
+
+    #[test]
+    fn reference_transition_matches_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        for _ in 0..10 {
+            let params = small_params();
+            let n_limbs = params.n_bits_base / params.limb_width;
+            let inputs: SmallInputs = SetBenchInputs::random(&mut rng, &params);
+            let expected = inputs.expected_transition(&params).unwrap();
+            assert_eq!(expected, inputs.final_digest);
+            let mut circuit = SetBench {
+                inputs: Some(inputs),
+                params,
+            };
+            let mut public = circuit.public_inputs().unwrap();
+            let limbs = nat_to_limbs(&expected, circuit.params.limb_width, n_limbs).unwrap();
+            public[3 * n_limbs..4 * n_limbs].clone_from_slice(&limbs);
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            circuit.synthesize(&mut cs).expect("synthesis failed");
+            assert!(cs.is_satisfied());
+            assert!(cs.verify(&public));
+        }
+
+        // Inputs built with other widths than the circuit's diverge from it.
+        let params = small_params();
+        let mut other_params = small_params();
+        other_params.n_trailing_ones += 1;
+        let inputs: SmallInputs = SetBenchInputs::from_items(
+            Vec::new(),
+            Vec::new(),
+            vec![vec![<Bn256 as ScalarEngine>::Fr::one(); params.item_size]],
+            &other_params,
+        );
+        assert_ne!(inputs.expected_transition(&params).unwrap(), inputs.final_digest);
+    }
+
+    #[test]
+    fn two_trailing_ones() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut params = small_params();
+        params.n_trailing_ones = 2;
+        let inputs = SetBenchInputs::random(&mut rng, &params);
+        assert_eq!(inputs.initial_state.hash_domain.n_trailing_ones, 2);
+        let circuit = SetBench {
+            inputs: Some(inputs),
+            params,
+        };
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+
+        // A witness padded with one trailing one does not satisfy a circuit which expects two.
+        let inputs = SetBenchInputs::random(&mut rng, &small_params());
+        let mut params = small_params();
+        params.n_trailing_ones = 2;
+        let circuit = SetBench {
+            inputs: Some(inputs),
+            params,
+        };
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(!cs.is_satisfied());
+    }
+
+    /// A benchmark which removes `n_removes` items from a set of two other items, and inserts
+    /// `n_inserts` items.
+    fn asymmetric_bench(
+        n_removes: usize,
+        n_inserts: usize,
+    ) -> SetBench<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>> {
+        let mut params = small_params();
+        params.n_removes = n_removes;
+        params.n_inserts = n_inserts;
+        SetBench {
+            inputs: Some(SetBenchInputs::from_counts(2, n_removes, n_inserts, &params)),
+            params,
+        }
+    }
+
+    /// A snapshot of `items`, whose digest is checked to be the generator raised to each of their
+    /// elements in turn.
+    fn snapshot_of(items: &[&[&str]]) -> Snapshot<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>> {
+        let params = small_params().snapshot_params(items.len());
+        let items: Vec<Vec<<Bn256 as ScalarEngine>::Fr>> = items
+            .iter()
+            .map(|i| i.iter().map(|s| PrimeField::from_str(s).unwrap()).collect())
+            .collect();
+        let inputs: SnapshotInputs<_, NaiveExpSet<RsaQuotientGroup>> = SnapshotInputs::from_items(
+            items.clone(),
+            Poseidon::default(),
+            params.n_bits_elem,
+            params.n_trailing_ones,
+            params.limb_width,
+            params.group.clone(),
+        );
+        let folded = items.iter().fold(params.group.generator(), |digest, item| {
+            params.group.power(&digest, &inputs.empty_state.element(item))
+        });
+        assert_eq!(inputs.digest, folded);
+        Snapshot {
+            inputs: Some(inputs),
+            params,
+        }
+    }
+
+    #[test]
+    fn elements_give_the_same_transition_as_items() {
+        type Inputs = SetBenchInputs<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>>;
+        let params = small_params();
+        let items = |ids: &[usize]| -> Vec<Vec<<Bn256 as ScalarEngine>::Fr>> {
+            ids.iter()
+                .map(|i| {
+                    (0..params.item_size)
+                        .map(|j| PrimeField::from_str(&format!("{}", 10 * i + j)).unwrap())
+                        .collect()
+                })
+                .collect()
+        };
+        let (untouched, removed, inserted) = (items(&[0, 1]), items(&[2]), items(&[3, 4]));
+        let mut inputs = Inputs::from_items(
+            untouched.clone(),
+            removed.clone(),
+            inserted.clone(),
+            &params,
+        );
+        let elements = |items: &[Vec<_>]| -> Vec<BigUint> {
+            items.iter().map(|i| inputs.initial_state.element(i)).collect()
+        };
+        let (mut initial_state, final_digest) = NaiveExpSet::transition(
+            params.group.clone(),
+            elements(&untouched),
+            elements(&removed),
+            elements(&inserted),
+        );
+        assert_eq!(initial_state.members(), inputs.initial_state.members());
+        assert_eq!(initial_state.digest(), inputs.initial_state.digest());
+        assert_eq!(final_digest, inputs.final_digest);
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn parallel_hashing_gives_the_same_inputs() {
+        use std::time::Instant;
+        type Inputs = SetBenchInputs<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>>;
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = small_params();
+        let untouched = random_items(&mut rng, 10, params.item_size);
+        let removed = random_items(&mut rng, 30, params.item_size);
+        let inserted = random_items(&mut rng, 30, params.item_size);
+
+        let start = Instant::now();
+        let mut serial =
+            Inputs::from_items(untouched.clone(), removed.clone(), inserted.clone(), &params);
+        let serial_time = start.elapsed();
+        let start = Instant::now();
+        let mut parallel = Inputs::par_from_items(untouched, removed, inserted, &params);
+        let parallel_time = start.elapsed();
+        println!("30 swaps: {:?} serial, {:?} parallel", serial_time, parallel_time);
+
+        assert_eq!(parallel.initial_state.members(), serial.initial_state.members());
+        assert_eq!(parallel.initial_state.digest(), serial.initial_state.digest());
+        assert_eq!(parallel.final_digest, serial.final_digest);
+        assert_eq!(parallel.to_remove, serial.to_remove);
+
+        let items = random_items(&mut rng, 4, params.item_size);
+        parallel.initial_state.par_insert_all(&items);
+        serial.initial_state.insert_all(items);
+        assert_eq!(parallel.initial_state.digest(), serial.initial_state.digest());
+    }
+
+    #[test]
+    fn element_of_matches_set_elements() {
+        let params = small_params();
+        let bench = asymmetric_bench(2, 1);
+        let inputs = bench.inputs.as_ref().unwrap();
+        let domain = HashDomain::new(params.n_bits_elem, params.n_trailing_ones).unwrap();
+        for item in inputs.to_remove.iter().chain(&inputs.to_insert) {
+            let element = element_of(&item[..], &domain, params.limb_width, &params.hasher);
+            assert_eq!(element, inputs.initial_state.element(item));
+            assert!(element.bits() <= params.n_bits_elem);
+        }
+    }
+
+    #[test]
+    fn set_bench_is_silent_unless_verbose() {
+        let synthesize = |verbose: bool| {
+            let mut bench = asymmetric_bench(1, 1);
+            bench.params.verbose = verbose;
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let (result, logs) = capture_logs(|| bench.synthesize(&mut cs));
+            result.expect("synthesis failed");
+            assert!(cs.is_satisfied());
+            logs
+        };
+        assert_eq!(synthesize(false), Vec::<String>::new());
+        assert_eq!(
+            synthesize(true),
+            vec![
+                "Allocating Deletions...",
+                "Allocating Insertions...",
+                "Hashing everything",
+                "Constructing Group",
+                "Constructing Set",
+                "Swapping elements",
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_progress_is_logged_only_when_verbose() {
+        let synthesize = |verbose: bool| {
+            let mut snapshot = snapshot_of(&[&["0", "1", "2", "3", "4"]]);
+            snapshot.params.verbose = verbose;
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let (result, logs) = capture_logs(|| snapshot.synthesize(&mut cs));
+            result.expect("synthesis failed");
+            assert!(cs.is_satisfied());
+            logs
+        };
+        assert_eq!(synthesize(false), Vec::<String>::new());
+        assert_eq!(
+            synthesize(true),
+            vec![
+                "Allocating Items...",
+                "Hashing everything",
+                "Constructing Group",
+                "Constructing Set",
+                "Folding items",
+            ]
+        );
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn group_operations_are_linear_in_removals() {
+        use util::profiling::reset_op_counts;
+        let counts: Vec<_> = (1..4)
+            .map(|n_removes| {
+                let circuit = asymmetric_bench(n_removes, 0);
+                let mut cs = TestConstraintSystem::<Bn256>::new();
+                reset_op_counts();
+                circuit.synthesize(&mut cs).expect("synthesis failed");
+                assert!(cs.is_satisfied());
+                reset_op_counts()
+            })
+            .collect();
+        // Each removal adds a multiplication to the proof of exponentiation, but the
+        // exponentiations are of reduced values, so their number need not grow.
+        for pair in counts.windows(2) {
+            assert!(pair[1].modpows >= pair[0].modpows);
+            assert!(pair[1].mult_mods > pair[0].mult_mods);
+        }
+        assert_eq!(
+            counts[2].modpows - counts[1].modpows,
+            counts[1].modpows - counts[0].modpows
+        );
+        assert_eq!(
+            counts[2].mult_mods - counts[1].mult_mods,
+            counts[1].mult_mods - counts[0].mult_mods
+        );
+    }
+
+    #[test]
+    fn empty_removal_slots_leave_the_digest() {
+        let params = small_params();
+        let n_limbs = params.n_bits_base / params.limb_width;
+        let inputs = || -> SmallInputs {
+            SetBenchInputs::new(
+                items(&[&["0", "1", "2", "3", "9"]]),
+                items(&[
+                    &["0", "1", "2", "3", "4"],
+                    &["0", "1", "2", "3", "5"],
+                    &["0", "1", "2", "3", "6"],
+                ]),
+                Vec::new(),
+                &params,
+            )
+            .unwrap()
+        };
+        let bench = |n_removes: usize, optional_slots: bool| {
+            let mut params = small_params();
+            params.n_removes = n_removes;
+            params.n_inserts = 0;
+            params.optional_slots = optional_slots;
+            SetBench {
+                inputs: Some(inputs()),
+                params,
+            }
+        };
+        let expected = inputs().final_digest;
+        // Three full slots, and three full slots with two empty ones.
+        for &(n_removes, optional_slots) in &[(3, false), (5, true)] {
+            let mut circuit = bench(n_removes, optional_slots);
+            let public = circuit.public_inputs().unwrap();
+            let digest = &public[3 * n_limbs..4 * n_limbs];
+            assert_eq!(limbs_to_nat_checked(digest, params.limb_width), Ok(expected.clone()));
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            circuit.synthesize(&mut cs).expect("synthesis failed");
+            assert!(cs.is_satisfied());
+            assert!(cs.verify(&public));
+        }
+
+        // Without optional slots, every slot needs an item.
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        assert!(bench(5, false).synthesize(&mut cs).is_err());
+    }
+
+    /// Constraints for a swap-free batch of `n_inserts` insertions into a 2-item set.
+    fn insertion_constraints(n_inserts: usize) -> usize {
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        asymmetric_bench(0, n_inserts)
+            .synthesize(&mut cs)
+            .expect("synthesis failed");
+        assert!(cs.is_satisfied());
+        let n = cs.num_constraints();
+        record_constraint_count(&format!("small_rsa_{}_inserts", n_inserts), n);
+        n
+    }
+
+    // Insertions share one proof of exponentiation, so an extra element costs its hash and its
+    // share of the product of elements, rather than a whole exponentiation of the digest.
+    #[test]
+    fn insertion_cost_per_element() {
+        let counts: Vec<usize> = (0..3).map(insertion_constraints).collect();
+        println!(
+            "Constraints per inserted element: {}, {}",
+            counts[1] - counts[0],
+            counts[2] - counts[1]
+        );
+        assert!(counts[1] > counts[0]);
+        assert!(counts[2] - counts[1] < counts[1]);
+    }
+
+    #[test]
+    fn empty_batch_keeps_digest() {
+        let mut inputs = asymmetric_bench(0, 0).inputs.unwrap();
+        assert_eq!(inputs.initial_state.digest(), inputs.final_digest);
+    }
+
+    #[test]
+    fn two_shards_in_one_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let hasher = Poseidon::default();
+        let mut shards = Vec::new();
+        for m in &[RSA_512, RSA_512_2] {
+            let mut params = small_params();
+            params.group.m = BigUint::from_str(m).unwrap();
+            params.hasher = hasher.clone();
+            shards.push(SetBench {
+                inputs: Some(SetBenchInputs::random(&mut rng, &params)),
+                params,
+            });
+        }
+        let mut circuit = MultiSetBench { shards };
+        let public = circuit.public_inputs().unwrap();
+        assert_eq!(public.len(), 2 * 4 * 512 / 32);
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+        assert!(cs.verify(&public));
+    }
+
+    #[test]
+    fn bls12_inputs_are_satisfied() {
+        use sapling_crypto::bellman::pairing::bls12_381::Bls12;
+        use sapling_crypto::group_hash::Keccak256Hasher;
+        use sapling_crypto::poseidon::bls12::Bls12PoseidonParams;
+
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let small = small_params();
+        let params = SetBenchParams {
+            group: small.group,
+            limb_width: small.limb_width,
+            n_bits_elem: small.n_bits_elem,
+            n_trailing_ones: small.n_trailing_ones,
+            n_bits_challenge: small.n_bits_challenge,
+            challenge_source: small.challenge_source,
+            n_bits_base: small.n_bits_base,
+            item_size: small.item_size,
+            n_inserts: small.n_inserts,
+            n_removes: small.n_removes,
+            window_bits: small.window_bits,
+            unique_inserts: small.unique_inserts,
+            optional_slots: small.optional_slots,
+            hasher: Poseidon::<Bls12>::from_params(Bls12PoseidonParams::new::<Keccak256Hasher>()),
+            verbose: false,
+        };
+        let circuit = SetBench::<_, NaiveExpSet<RsaQuotientGroup>> {
+            inputs: Some(SetBenchInputs::random(&mut rng, &params)),
+            params,
+        };
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn swap_requires_the_removed_item() {
+        let params = small_params();
+        let item = |last: &str| -> Vec<<Bn256 as ScalarEngine>::Fr> {
+            ["0", "1", "2", "3", last]
+                .iter()
+                .map(|s| PrimeField::from_str(s).unwrap())
+                .collect()
+        };
+        let set = |items: &[Vec<_>]| -> Set<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>> {
+            Set::new_with(
+                params.group.clone(),
+                di::offset(params.n_bits_elem),
+                params.hasher.clone(),
+                params.n_bits_elem,
+                params.n_trailing_ones,
+                params.limb_width,
+                items.iter().map(Vec::as_slice),
+            )
+        };
+        let mut s = set(&[item("4"), item("5")]);
+        let before = s.digest();
+        assert_eq!(s.swap(&item("6"), item("7")), Err(MissingItem));
+        assert_eq!(s.digest(), before);
+        assert_eq!(s.swap(&item("4"), item("7")), Ok(()));
+        assert_eq!(s.digest(), set(&[item("5"), item("7")]).digest());
+    }
+
+    fn alloc_item<CS: ConstraintSystem<Bn256>>(
+        mut cs: CS,
+        values: &[<Bn256 as ScalarEngine>::Fr],
+    ) -> MaybeHashed<Bn256> {
+        MaybeHashed::from_values(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    AllocatedNum::alloc(cs.namespace(|| format!("elem {}", i)), || Ok(*v)).unwrap()
+                })
+                .collect(),
+        )
+    }
+
+    /// Synthesizes two chained swaps in a set: `5` for `6`, then `6` for `7`. With `shared`, one
+    /// challenge covers both steps; otherwise each step derives its own, and the intermediate set
+    /// is reallocated under it. Returns the constraint count and whether the constraints are
+    /// satisfied.
+    fn chained_swaps(shared: bool) -> (usize, bool) {
+        type SmallCircuitSet = CircuitSet<
+            Bn256,
+            Poseidon<Bn256>,
+            CircuitRsaQuotientGroup<Bn256>,
+            NaiveExpSet<RsaQuotientGroup>,
+        >;
+        let params = small_params();
+        let item = |last: &str| -> Vec<<Bn256 as ScalarEngine>::Fr> {
+            ["0", "1", "2", "3", last]
+                .iter()
+                .map(|s| PrimeField::from_str(s).unwrap())
+                .collect()
+        };
+        // The (removed, inserted) items of each step.
+        let steps = [(item("5"), item("6")), (item("6"), item("7"))];
+        let mut state: Set<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>> = Set::new_with(
+            params.group.clone(),
+            di::offset(params.n_bits_elem),
+            params.hasher.clone(),
+            params.n_bits_elem,
+            params.n_trailing_ones,
+            params.limb_width,
+            [item("4"), item("5")].iter().map(Vec::as_slice),
+        );
+        let mut states = vec![state.clone()];
+        for (removed, inserted) in &steps {
+            state.swap(removed, inserted.clone()).unwrap();
+            states.push(state.clone());
+        }
+
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        let digests = states
+            .iter_mut()
+            .enumerate()
+            .map(|(i, s)| {
+                BigNat::alloc_from_nat(
+                    cs.namespace(|| format!("digest {}", i)),
+                    || Ok(s.digest()),
+                    params.limb_width,
+                    params.n_bits_base / params.limb_width,
+                )
+            })
+            .collect::<CResult<Vec<_>>>()
+            .unwrap();
+        // The insertion, then the removal, of each step.
+        let mut items = Vec::new();
+        for (i, (removed, inserted)) in steps.iter().enumerate() {
+            items.push(alloc_item(cs.namespace(|| format!("insert {}", i)), inserted));
+            items.push(alloc_item(cs.namespace(|| format!("remove {}", i)), removed));
+        }
+
+        let challenges = if shared {
+            let challenge = shared_challenge(
+                cs.namespace(|| "challenge"),
+                &digests.iter().collect::<Vec<_>>(),
+                &mut items,
+                params.limb_width,
+                params.n_bits_challenge,
+                &params.hasher,
+            )
+            .unwrap();
+            vec![challenge.clone(), challenge]
+        } else {
+            (0..steps.len())
+                .map(|i| {
+                    shared_challenge(
+                        cs.namespace(|| format!("challenge {}", i)),
+                        &[&digests[i], &digests[i + 1]],
+                        &mut items[2 * i..2 * i + 2],
+                        params.limb_width,
+                        params.n_bits_challenge,
+                        &params.hasher,
+                    )
+                })
+                .collect::<CResult<Vec<_>>>()
+                .unwrap()
+        };
+
+        let group = CircuitRsaQuotientGroup::alloc(
+            cs.namespace(|| "group"),
+            Some(&params.group),
+            (),
+            &CircuitRsaGroupParams::new(params.limb_width, params.n_bits_base).unwrap(),
+        )
+        .unwrap();
+        let set_params = CircuitSetParams {
+            hasher: params.hasher.clone(),
+            n_bits: params.n_bits_elem,
+            n_trailing_ones: params.n_trailing_ones,
+            limb_width: params.limb_width,
+        };
+        let mut set: Option<SmallCircuitSet> = None;
+        for (i, challenge) in challenges.into_iter().enumerate() {
+            let mut cs = cs.namespace(|| format!("step {}", i));
+            let current = match set.take() {
+                // Under a shared challenge, the set carries over from the previous step.
+                Some(s) if shared => s,
+                _ => {
+                    let s = SmallCircuitSet::alloc(
+                        cs.namespace(|| "set"),
+                        Some(&states[i]),
+                        (group.clone(), challenge),
+                        &set_params,
+                    )
+                    .unwrap();
+                    s.inner
+                        .digest
+                        .equal(cs.namespace(|| "digest matches"), &digests[i])
+                        .unwrap();
+                    s
+                }
+            };
+            let next = current
+                .swap_all_to(
+                    cs.namespace(|| "swap"),
+                    vec![items[2 * i + 1].clone()],
+                    vec![items[2 * i].clone()],
+                    digests[i + 1].clone(),
+                )
+                .unwrap();
+            set = Some(next);
+        }
+        (cs.num_constraints(), cs.is_satisfied())
+    }
+
+    #[test]
+    fn chained_swaps_share_a_challenge() {
+        let (shared, sat) = chained_swaps(true);
+        assert!(sat);
+        let (separate, sat) = chained_swaps(false);
+        assert!(sat);
+        println!("Constraints for two swaps: {} shared, {} separate", shared, separate);
+        assert!(shared < separate);
+    }
+
+    type SmallInputs = SetBenchInputs<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>>;
+
+    fn items(items: &[&[&str]]) -> Vec<Vec<String>> {
+        items
+            .iter()
+            .map(|i| i.iter().map(|s| s.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn json_inputs_match_items() {
+        let params = small_params();
+        let json = SetInputsJson {
+            initial: items(&[&["0", "1", "2", "3", "4"], &["0", "1", "2", "3", "6"]]),
+            to_remove: items(&[&["0", "1", "2", "3", "4"]]),
+            to_insert: items(&[&["0", "1", "2", "3", "5"]]),
+        };
+        let from_json = SmallInputs::from_json(&json, &params).unwrap();
+        let from_strings = SmallInputs::new(
+            items(&[&["0", "1", "2", "3", "6"]]),
+            json.to_remove.clone(),
+            json.to_insert.clone(),
+            &params,
+        )
+        .unwrap();
+        assert_eq!(from_json.final_digest, from_strings.final_digest);
+        assert_eq!(from_json.to_remove, from_strings.to_remove);
+
+        let circuit = SetBench {
+            inputs: Some(from_json),
+            params,
+        };
+        let mut cs = TestConstraintSystem::<Bn256>::new();
+        circuit.synthesize(&mut cs).expect("synthesis failed");
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn json_inputs_are_validated() {
+        let params = small_params();
+        let valid = SetInputsJson {
+            initial: items(&[&["0", "1", "2", "3", "4"]]),
+            to_remove: items(&[&["0", "1", "2", "3", "4"]]),
+            to_insert: items(&[&["0", "1", "2", "3", "5"]]),
+        };
+        let check = |json: &SetInputsJson| SmallInputs::from_json(json, &params).map(|_| ());
+        assert_eq!(check(&valid), Ok(()));
+
+        let mut narrow = valid.clone();
+        narrow.to_insert = items(&[&["0", "1", "2", "3"]]);
+        assert_eq!(
+            check(&narrow),
+            Err(InputError::WidthMismatch { expected: 5, got: 4 })
+        );
+
+        let mut unbalanced = valid.clone();
+        unbalanced.to_remove.clear();
+        assert_eq!(
+            check(&unbalanced),
+            Err(InputError::RemovalCount {
+                expected: 1,
+                got: 0
+            })
+        );
+
+        let mut absent = valid.clone();
+        absent.to_remove = items(&[&["0", "1", "2", "3", "6"]]);
+        assert_eq!(
+            check(&absent),
+            Err(InputError::MissingItem(absent.to_remove[0].clone()))
+        );
+
+        let mut not_field = valid.clone();
+        not_field.initial[0][0] = "x".to_string();
+        assert_eq!(check(&not_field), Err(InputError::BadField("x".to_string())));
+    }
+
+    #[test]
+    fn duplicate_items_in_a_batch_are_rejected() {
+        let params = small_params();
+        let inputs = |removed: &[&[&str]], inserted: &[&[&str]]| {
+            SmallInputs::new(
+                items(&[&["0", "1", "2", "3", "6"]]),
+                items(removed),
+                items(inserted),
+                &params,
+            )
+            .map(|_| ())
+        };
+        let (a, b): (&[&str], &[&str]) = (&["0", "1", "2", "3", "4"], &["0", "1", "2", "3", "5"]);
+        assert_eq!(inputs(&[a, b], &[]), Ok(()));
+        assert_eq!(
+            inputs(&[a, b, a], &[]),
+            Err(InputError::DuplicateElement(items(&[a]).remove(0)))
+        );
+        assert_eq!(
+            inputs(&[], &[b, b]),
+            Err(InputError::DuplicateElement(items(&[b]).remove(0)))
+        );
+        // An item may be removed and reinserted, which leaves the set as it was.
+        assert_eq!(inputs(&[a], &[a]), Ok(()));
+
+        let mut params = small_params();
+        params.n_removes = 2;
+        params.n_inserts = 2;
+        let from_json = |removed: &[&[&str]], inserted: &[&[&str]]| {
+            let json = SetInputsJson {
+                initial: items(&[a, b]),
+                to_remove: items(removed),
+                to_insert: items(inserted),
+            };
+            SmallInputs::from_json(&json, &params).map(|_| ())
+        };
+        assert_eq!(from_json(&[a, b], &[a, b]), Ok(()));
+        assert_eq!(
+            from_json(&[a, a], &[a, b]),
+            Err(InputError::DuplicateElement(items(&[a]).remove(0)))
+        );
+        assert_eq!(
+            from_json(&[a, b], &[b, b]),
+            Err(InputError::DuplicateElement(items(&[b]).remove(0)))
+        );
+    }
+
+    #[test]
+    fn hex_items_match_decimal_items() {
+        let params = small_params();
+        // A 32-byte hash, below the modulus.
+        let hash = "0x1c0ffee2b8b7c8a4c1d6f4e1c9bd5bd2e7c3a99e9a60a3a5f1d7f0e4ad8c3b61";
+        let decimal = BigUint::parse_bytes(&hash.as_bytes()[2..], 16)
+            .unwrap()
+            .to_string();
+        let inputs = |item: &[&str], encoding: ItemEncoding| -> SmallInputs {
+            SetBenchInputs::new_encoded(
+                Vec::new(),
+                Vec::new(),
+                items(&[item]),
+                encoding,
+                &params,
+            )
+            .unwrap()
+        };
+        let from_hex = inputs(&["0x0", "0x1", "0x2", "0x3", hash], ItemEncoding::Hex);
+        let from_decimal = inputs(&["0", "1", "2", "3", decimal.as_str()], ItemEncoding::Decimal);
+        assert_eq!(from_hex.to_insert, from_decimal.to_insert);
+        assert_eq!(from_hex.final_digest, from_decimal.final_digest);
+
+        let parse = |s: &str| ItemEncoding::Hex.parse::<<Bn256 as ScalarEngine>::Fr>(s);
+        assert_eq!(parse("0x05"), Ok(PrimeField::from_str("5").unwrap()));
+        let too_big = format!("0x{}", "f".repeat(64));
+        for bad in &["5", "0x", "0xzz", too_big.as_str()] {
+            assert_eq!(parse(bad), Err(InputError::BadField(bad.to_string())));
+        }
+    }
+
+    #[test]
+    fn malformed_items_are_rejected() {
+        let params = small_params();
+        let new = |initial: Vec<Vec<String>>| {
+            SmallInputs::new(
+                initial,
+                items(&[&["0", "1", "2", "3", "4"]]),
+                items(&[&["0", "1", "2", "3", "5"]]),
+                &params,
+            )
+            .map(|_| ())
+        };
+        assert_eq!(new(items(&[&["0", "1", "2", "3", "6"]])), Ok(()));
+        assert_eq!(
+            new(items(&[&["0", "1", "2", "3", "x"]])),
+            Err(InputError::BadField("x".to_string()))
+        );
+        assert_eq!(
+            new(items(&[&["0", "1", "2", "3", "6"], &["0", "1", "2"]])),
+            Err(InputError::WidthMismatch { expected: 5, got: 3 })
+        );
+    }
+
+    #[test]
+    fn duplicate_inserts_are_rejected_when_unique() {
+        let params = small_params();
+        let new = |inserted: &[&str]| {
+            SmallInputs::new(
+                items(&[&["0", "1", "2", "3", "4"], &["0", "1", "2", "3", "6"]]),
+                items(&[&["0", "1", "2", "3", "4"]]),
+                items(&[&["0", "1", "2", "3", inserted[0]]]),
+                &params,
+            )
+            .unwrap()
+        };
+        let satisfied = |inputs: SmallInputs, unique_inserts: bool| {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            let circuit = SetBench {
+                inputs: Some(inputs),
+                params: SetBenchParams { unique_inserts, ..small_params() },
+            };
+            circuit.synthesize(&mut cs).map(|()| cs.is_satisfied())
+        };
+
+        let SetBenchInputs { mut initial_state, to_insert, .. } = new(&["6"]);
+        let set = &mut initial_state;
+        assert_eq!(set.insert_unique(to_insert[0].clone()), Err(DuplicateItem));
+        set.insert(to_insert[0].clone());
+        assert_eq!(set.members().len(), 3);
+
+        // A removed item may come straight back.
+        assert_eq!(new(&["4"]).check_unique_inserts(), Ok(()));
+        assert_eq!(satisfied(new(&["4"]), true).unwrap(), true);
+        // An item that stays in the set may not, unless repetition is allowed.
+        assert_eq!(new(&["6"]).check_unique_inserts(), Err(DuplicateItem));
+        assert!(satisfied(new(&["6"]), true).is_err());
+        assert_eq!(satisfied(new(&["6"]), false).unwrap(), true);
+    }
+
+    fn small_rsa_1_swap_bench() -> SetBench<Poseidon<Bn256>, NaiveExpSet<RsaQuotientGroup>> {
+        let params = SetBenchParams {
+            group: RsaQuotientGroup {
+                g: BigUint::from(2usize),
+                m: BigUint::from_str(RSA_512).unwrap(),
+            },
+            limb_width: 32,
+            n_bits_elem: 128,
+            n_trailing_ones: 1,
+            n_bits_challenge: 128,
+            challenge_source: ChallengeSource::FiatShamir,
+            n_bits_base: 512,
+            item_size: 5,
+            n_inserts: 1,
+            n_removes: 1,
+            window_bits: None,
+            unique_inserts: false,
+            optional_slots: false,
+            hasher: Poseidon::default(),
+            verbose: true,
+        };
+        SetBench {
             inputs: Some(SetBenchInputs::new(
-                            [].to_vec(),
-                            [
-                            ["0", "1", "2", "3", "4"].iter().map(|s| s.to_string()).collect(),
-                            ].to_vec(),
-                            [
-                            ["0", "1", "2", "3", "5"].iter().map(|s| s.to_string()).collect(),
-                            ].to_vec(),
-                            Poseidon::default(),
-                            128,
-                            32,
-                            RsaQuotientGroup {
-                                g: BigUint::from(2usize),
-                                m: BigUint::from_str(RSA_512).unwrap(),
-                            },
-                    )),
-                    params: SetBenchParams {
-                        group: RsaQuotientGroup {
-                            g: BigUint::from(2usize),
-                            m: BigUint::from_str(RSA_512).unwrap(),
-                        },
-                        limb_width: 32,
-                        n_bits_elem: 128,
-                        n_bits_challenge: 128,
-                        n_bits_base: 512,
-                        item_size: 5,
-                        n_inserts: 1,
-                        n_removes: 1,
-                        hasher: Poseidon::default(),
-                        verbose: true,
-                    },
+                [].to_vec(),
+                [
+                ["0", "1", "2", "3", "4"].iter().map(|s| s.to_string()).collect(),
+                ].to_vec(),
+                [
+                ["0", "1", "2", "3", "5"].iter().map(|s| s.to_string()).collect(),
+                ].to_vec(),
+                &params,
+            ).unwrap()),
+            params,
+        }
+    }
+
+    #[test]
+    fn small_rsa_1_swap_derives_its_carries() {
+        use mp::bignat::with_allocated_carries;
+        use util::bench::ConstraintCounter;
+
+        let satisfied = || {
+            let mut cs = TestConstraintSystem::<Bn256>::new();
+            small_rsa_1_swap_bench().synthesize(&mut cs).unwrap();
+            cs.is_satisfied()
+        };
+        let count = || {
+            let mut cs = ConstraintCounter::new();
+            let mut circuit = small_rsa_1_swap_bench();
+            circuit.inputs = None;
+            Circuit::<Bn256>::synthesize(circuit, &mut cs).unwrap();
+            (cs.num_variables(), cs.num_constraints())
+        };
+        assert!(satisfied());
+        assert!(with_allocated_carries(satisfied));
+        let (derived_vars, derived_constraints) = count();
+        let (allocated_vars, allocated_constraints) = with_allocated_carries(count);
+        println!(
+            "small_rsa_1_swap: {} -> {} variables, {} -> {} constraints",
+            allocated_vars, derived_vars, allocated_constraints, derived_constraints
+        );
+        // Each allocated carry costs a variable and the constraint tying it to its derivation.
+        assert!(derived_vars < allocated_vars);
+        assert_eq!(
+            allocated_vars - derived_vars,
+            allocated_constraints - derived_constraints
+        );
+    }
+
+    circuit_tests! {
+        small_rsa_1_swap: (small_rsa_1_swap_bench(), true),
+        small_rsa_1_swap_wrong_final_digest: ({
+            let params = SetBenchParams {
+                group: RsaQuotientGroup {
+                    g: BigUint::from(2usize),
+                    m: BigUint::from_str(RSA_512).unwrap(),
+                },
+                limb_width: 32,
+                n_bits_elem: 128,
+                n_trailing_ones: 1,
+                n_bits_challenge: 128,
+                challenge_source: ChallengeSource::FiatShamir,
+                n_bits_base: 512,
+                item_size: 5,
+                n_inserts: 1,
+                n_removes: 1,
+                window_bits: None,
+                unique_inserts: false,
+                optional_slots: false,
+                hasher: Poseidon::default(),
+                verbose: false,
+            };
+            let mut inputs = SetBenchInputs::new(
+                [].to_vec(),
+                [
+                ["0", "1", "2", "3", "4"].iter().map(|s| s.to_string()).collect(),
+                ].to_vec(),
+                [
+                ["0", "1", "2", "3", "5"].iter().map(|s| s.to_string()).collect(),
+                ].to_vec(),
+                &params,
+            ).unwrap();
+            inputs.final_digest += 1usize;
+            SetBench {
+                inputs: Some(inputs),
+                params,
+            }
+        }, false),
+        small_rsa_reinsert_removed_item: (SetBench {
+            inputs: Some({
+                let params = small_params();
+                let mut inputs = SetBenchInputs::new(
+                    items(&[&["0", "1", "2", "3", "6"]]),
+                    items(&[&["0", "1", "2", "3", "4"]]),
+                    items(&[&["0", "1", "2", "3", "4"]]),
+                    &params,
+                ).unwrap();
+                assert_eq!(inputs.initial_state.digest(), inputs.final_digest);
+                inputs
+            }),
+            params: small_params(),
         }, true),
+        small_rsa_3_inserts_1_remove: (asymmetric_bench(1, 3), true),
+        small_rsa_1_insert_3_removes: (asymmetric_bench(3, 1), true),
+        small_rsa_empty_batch: (asymmetric_bench(0, 0), true),
+        small_rsa_empty_batch_wrong_final_digest: ({
+            let mut bench = asymmetric_bench(0, 0);
+            bench.inputs.as_mut().unwrap().final_digest += 1usize;
+            bench
+        }, false),
+        small_rsa_snapshot_3_items: (snapshot_of(&[
+            &["0", "1", "2", "3", "4"],
+            &["1", "1", "2", "3", "4"],
+            &["2", "1", "2", "3", "4"],
+        ]), true),
+        small_rsa_snapshot_wrong_digest: ({
+            let mut snapshot = snapshot_of(&[
+                &["0", "1", "2", "3", "4"],
+                &["1", "1", "2", "3", "4"],
+                &["2", "1", "2", "3", "4"],
+            ]);
+            snapshot.inputs.as_mut().unwrap().digest += 1usize;
+            snapshot
+        }, false),
         //small_rsa_5_swaps: (SetBench {
         //    inputs: Some(SetBenchInputs::new(
         //        [].to_vec(),
@@ -9,14 +9,22 @@ use std::time::{Instant, Duration};
 
 pub struct ConstraintCounter {
     n_constraints: usize,
+    n_variables: usize,
 }
 
 impl ConstraintCounter {
     pub fn num_constraints(&self) -> usize {
         self.n_constraints
     }
+    /// The number of allocated variables, inputs included.
+    pub fn num_variables(&self) -> usize {
+        self.n_variables
+    }
     pub fn new() -> Self {
-        Self { n_constraints: 0 }
+        Self {
+            n_constraints: 0,
+            n_variables: 0,
+        }
     }
 }
 
@@ -28,6 +36,7 @@ impl<E: Engine> ConstraintSystem<E> for ConstraintCounter {
         A: FnOnce() -> AR,
         AR: Into<String>,
     {
+        self.n_variables += 1;
         Ok(Variable::new_unchecked(Index::Aux(0)))
     }
     fn alloc_input<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
@@ -36,6 +45,7 @@ impl<E: Engine> ConstraintSystem<E> for ConstraintCounter {
         A: FnOnce() -> AR,
         AR: Into<String>,
     {
+        self.n_variables += 1;
         Ok(Variable::new_unchecked(Index::Input(0)))
     }
 
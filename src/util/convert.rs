@@ -26,3 +26,21 @@ pub fn f_to_usize<F: PrimeField>(n: F) -> usize {
     let s = format!("{}", n);
     usize::from_str_radix(&(s.as_str()[6..(s.len()-1)]), 16).unwrap()
 }
+
+/// Encodes a natural number as a decimal string, for use with `#[serde(with = "nat_decimal")]`.
+pub mod nat_decimal {
+    use num_bigint::BigUint;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(n: &BigUint, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(n)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BigUint, D::Error> {
+        let s = String::deserialize(d)?;
+        BigUint::from_str(&s).map_err(|_| D::Error::custom(format!("{} is not a natural", s)))
+    }
+}
@@ -0,0 +1,43 @@
+use sapling_crypto::bellman::groth16::VerifyingKey;
+use sapling_crypto::bellman::pairing::Engine;
+use sha2::{Digest, Sha256};
+
+/// Returns a stable identifier for a verifying key: the hex-encoded SHA256 of its canonical
+/// (bellman) serialization.
+pub fn fingerprint<E: Engine>(vk: &VerifyingKey<E>) -> String {
+    let mut bytes = Vec::new();
+    vk.write(&mut bytes).expect("writing to a Vec cannot fail");
+    let mut hasher = Sha256::new();
+    hasher.input(&bytes);
+    hasher
+        .result()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::thread_rng;
+    use sapling_crypto::bellman::groth16::generate_random_parameters;
+
+    use util::test_helpers::*;
+
+    #[test]
+    fn fingerprint_stable() {
+        let rng = &mut thread_rng();
+        let params = generate_random_parameters::<Bn256, _, _>(Square, rng).unwrap();
+        assert_eq!(fingerprint(&params.vk), fingerprint(&params.vk));
+        assert_eq!(fingerprint(&params.vk).len(), 64);
+    }
+
+    #[test]
+    fn fingerprint_distinguishes_keys() {
+        let rng = &mut thread_rng();
+        let a = generate_random_parameters::<Bn256, _, _>(Square, rng).unwrap();
+        let b = generate_random_parameters::<Bn256, _, _>(Square, rng).unwrap();
+        assert_ne!(fingerprint(&a.vk), fingerprint(&b.vk));
+    }
+}
@@ -2,8 +2,10 @@ pub mod bench;
 pub mod bit;
 pub mod convert;
 pub mod gadget;
+pub mod key;
 pub mod lazy;
 pub mod num;
+pub mod profiling;
 
 #[cfg(test)]
 #[macro_use]
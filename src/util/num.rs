@@ -3,6 +3,7 @@ use num_traits::One;
 use sapling_crypto::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use sapling_crypto::bellman::pairing::Engine;
 use sapling_crypto::bellman::{ConstraintSystem, LinearCombination, SynthesisError};
+use sapling_crypto::circuit::boolean::{AllocatedBit, Boolean};
 use sapling_crypto::circuit::num::AllocatedNum;
 
 use std::convert::From;
@@ -95,6 +96,28 @@ impl<E: Engine> Num<E> {
         Ok(Bitvector { values, bits })
     }
 
+    /// A bit which is set iff `self` is zero. Besides the bit, this costs two constraints:
+    /// `self * inv = 1 - bit` and `self * bit = 0`.
+    pub fn is_zero<CS: ConstraintSystem<E>>(&self, mut cs: CS) -> Result<Boolean, SynthesisError> {
+        let bit = AllocatedBit::alloc(cs.namespace(|| "bit"), self.value.map(|v| v.is_zero()))?;
+        let inv = Num::alloc(cs.namespace(|| "inv"), || {
+            Ok(self.value.grab()?.inverse().unwrap_or_else(E::Fr::zero))
+        })?;
+        cs.enforce(
+            || "self * inv = 1 - bit",
+            |lc| lc + &self.num,
+            |lc| lc + &inv.num,
+            |lc| lc + CS::one() - bit.get_variable(),
+        );
+        cs.enforce(
+            || "self * bit = 0",
+            |lc| lc + &self.num,
+            |lc| lc + bit.get_variable(),
+            |lc| lc,
+        );
+        Ok(Boolean::from(bit))
+    }
+
     pub fn as_sapling_allocated_num<CS: ConstraintSystem<E>>(
         &self,
         mut cs: CS,
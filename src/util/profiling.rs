@@ -0,0 +1,59 @@
+//! Counts of the expensive operations in the group, both in and out of circuits, for tuning batch
+//! sizes. Operations are counted per thread, and only with the `profiling` feature: otherwise the
+//! counters do nothing.
+
+#[cfg(feature = "profiling")]
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+
+/// Numbers of modular exponentiations and modular multiplications.
+///
+/// In circuits, the multiplications within an exponentiation are counted too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub modpows: usize,
+    pub mult_mods: usize,
+}
+
+impl Display for OpCounts {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} modpows, {} mult_mods", self.modpows, self.mult_mods)
+    }
+}
+
+#[cfg(feature = "profiling")]
+thread_local! {
+    static COUNTS: Cell<OpCounts> = Cell::new(OpCounts::default());
+}
+
+#[inline]
+pub fn count_modpow() {
+    #[cfg(feature = "profiling")]
+    COUNTS.with(|c| {
+        let mut counts = c.get();
+        counts.modpows += 1;
+        c.set(counts);
+    });
+}
+
+#[inline]
+pub fn count_mult_mod() {
+    #[cfg(feature = "profiling")]
+    COUNTS.with(|c| {
+        let mut counts = c.get();
+        counts.mult_mods += 1;
+        c.set(counts);
+    });
+}
+
+/// The operations counted on this thread since the last reset.
+#[cfg(feature = "profiling")]
+pub fn op_counts() -> OpCounts {
+    COUNTS.with(Cell::get)
+}
+
+/// Resets the counts on this thread, returning them.
+#[cfg(feature = "profiling")]
+pub fn reset_op_counts() -> OpCounts {
+    COUNTS.with(|c| c.replace(OpCounts::default()))
+}
@@ -2,6 +2,90 @@ pub use sapling_crypto::bellman::pairing::bn256::Bn256;
 pub use sapling_crypto::bellman::pairing::ff::PrimeField;
 pub use sapling_crypto::bellman::Circuit;
 pub use sapling_crypto::circuit::test::TestConstraintSystem;
+
+use log::{self, LevelFilter, Log, Metadata, Record};
+use sapling_crypto::bellman::pairing::ff::Field;
+use sapling_crypto::bellman::pairing::Engine;
+use sapling_crypto::bellman::{ConstraintSystem, SynthesisError};
+
+use std::cell::RefCell;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// If set, `circuit_tests!` appends a `name,num_constraints` line to the file at this path for each
+/// test it runs.
+pub const CONSTRAINT_COUNTS_VAR: &str = "BELLMAN_BIGNAT_CONSTRAINT_COUNTS";
+
+/// Appends `name,num_constraints` to the file at `path`, creating it if necessary.
+pub fn append_constraint_count(path: &Path, name: &str, num_constraints: usize) {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("could not open the constraint count file");
+    // One `write_all` per line, so that concurrent tests do not interleave their entries.
+    file.write_all(format!("{},{}\n", name, num_constraints).as_bytes())
+        .expect("could not write the constraint count file");
+}
+
+/// Records a constraint count in the file named by `CONSTRAINT_COUNTS_VAR`, if it is set.
+pub fn record_constraint_count(name: &str, num_constraints: usize) {
+    if let Some(path) = env::var_os(CONSTRAINT_COUNTS_VAR) {
+        append_constraint_count(Path::new(&path), name, num_constraints);
+    }
+}
+
+thread_local! {
+    static CAPTURED_LOGS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
+/// Records messages logged inside `capture_logs`. Each thread has its own record, so that tests
+/// running in parallel do not see each other's messages.
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        CAPTURED_LOGS.with(|logs| {
+            if let Some(ref mut logs) = *logs.borrow_mut() {
+                logs.push(format!("{}", record.args()));
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE_LOGGER: CaptureLogger = CaptureLogger;
+
+/// Runs `f`, returning its result and the messages it logged.
+pub fn capture_logs<T, F: FnOnce() -> T>(f: F) -> (T, Vec<String>) {
+    // A process has one logger, so this fails after the first call, leaving the same logger.
+    let _ = log::set_logger(&CAPTURE_LOGGER);
+    log::set_max_level(LevelFilter::Trace);
+    CAPTURED_LOGS.with(|logs| *logs.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let logs = CAPTURED_LOGS.with(|logs| logs.borrow_mut().take().unwrap());
+    (result, logs)
+}
+
+/// A tiny circuit, with one public input, for tests of keys and proofs: it checks that `1 * 1 = 1`.
+pub struct Square;
+
+impl<E: Engine> Circuit<E> for Square {
+    fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let a = cs.alloc(|| "a", || Ok(E::Fr::one()))?;
+        let b = cs.alloc_input(|| "b", || Ok(E::Fr::one()))?;
+        cs.enforce(|| "a * a = b", |lc| lc + a, |lc| lc + a, |lc| lc + b);
+        Ok(())
+    }
+}
+
 macro_rules! circuit_tests {
     ($($name:ident: $value:expr,)*) => {
         $(
@@ -10,8 +94,20 @@ macro_rules! circuit_tests {
                 let (circuit, is_sat) = $value;
                 let mut cs = TestConstraintSystem::<Bn256>::new();
 
+                #[cfg(feature = "profiling")]
+                $crate::util::profiling::reset_op_counts();
                 circuit.synthesize(&mut cs).expect("synthesis failed");
                 println!(concat!("Constraints in {}: {}"), stringify!($name), cs.num_constraints());
+                #[cfg(feature = "profiling")]
+                println!(
+                    concat!("Group operations in {}: {}"),
+                    stringify!($name),
+                    $crate::util::profiling::op_counts(),
+                );
+                record_constraint_count(
+                    concat!(module_path!(), "::", stringify!($name)),
+                    cs.num_constraints(),
+                );
                 if is_sat && !cs.is_satisfied() {
                     println!("UNSAT: {:#?}", cs.which_is_unsatisfied())
                 }
@@ -25,3 +121,24 @@ macro_rules! circuit_tests {
         )*
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn constraint_counts_are_appended() {
+        let path = env::temp_dir().join(format!("bbn-constraint-counts-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        append_constraint_count(&path, "mp::bignat::test::mult_mod_1_by_0", 32);
+        append_constraint_count(&path, "set::rsa::test::small_rsa_1_swap", 12345);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            contents,
+            "mp::bignat::test::mult_mod_1_by_0,32\nset::rsa::test::small_rsa_1_swap,12345\n"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! `wasm-bindgen` entry points for proving and verifying set swaps in a browser or Node, and for
+//! computing the accumulator elements and digests which drive them.
+//!
+//! Only the small configuration of `io::small_set` is supported for now; digests may use another
+//! group. Keys and proofs use the JSON formats of `io::groth16_json`.
+
+use sapling_crypto::bellman::groth16::Parameters;
+use sapling_crypto::bellman::pairing::bn256::{Bn256, Fr};
+use wasm_bindgen::prelude::*;
+
+use group::RsaQuotientGroup;
+use io::groth16_json::{
+    self, from_proof, from_public_inputs, from_public_inputs_hex, ProofJson, VerifyingKeyJson,
+};
+use io::small_set::{self, err, parse_item, parse_swap, small_circuit};
+
+pub fn prove_json(params_bytes: &[u8], inputs_json: &str) -> Result<String, String> {
+    let params = Parameters::<Bn256>::read(params_bytes, true).map_err(err)?;
+    let swap = parse_swap(inputs_json)?;
+    let proof = small_set::prove(&params, &swap)?;
+    serde_json::to_string(&from_proof(&proof)).map_err(err)
+}
+
+fn swap_public_inputs(inputs_json: &str) -> Result<Vec<Fr>, String> {
+    let swap = parse_swap(inputs_json)?;
+    small_circuit(swap.removed.len(), Some(&swap))
+        .public_inputs()
+        .ok_or_else(|| "the digests do not fit in the public inputs".to_string())
+}
+
+pub fn public_inputs_json(inputs_json: &str) -> Result<String, String> {
+    serde_json::to_string(&from_public_inputs(&swap_public_inputs(inputs_json)?)).map_err(err)
+}
+
+pub fn public_inputs_hex_json(inputs_json: &str) -> Result<String, String> {
+    serde_json::to_string(&from_public_inputs_hex(&swap_public_inputs(inputs_json)?)).map_err(err)
+}
+
+pub fn verify_json(vk_json: &str, proof_json: &str, public_json: &str) -> Result<bool, String> {
+    let vk_json: VerifyingKeyJson = serde_json::from_str(vk_json).map_err(err)?;
+    let proof_json: ProofJson = serde_json::from_str(proof_json).map_err(err)?;
+    let public_json: Vec<String> = serde_json::from_str(public_json).map_err(err)?;
+    if public_json.len() + 1 != vk_json.ic.len() {
+        return Ok(false);
+    }
+    groth16_json::verify_json(&vk_json, &proof_json, &public_json).map_err(err)
+}
+
+pub fn element_json(item_json: &str) -> Result<String, String> {
+    let item: Vec<String> = serde_json::from_str(item_json).map_err(err)?;
+    let set = small_set::empty_set(small_set::small_group());
+    Ok(set.element(&parse_item(&item)?).to_string())
+}
+
+pub fn digest_json(group_json: &str, items_json: &str) -> Result<String, String> {
+    let group: RsaQuotientGroup = serde_json::from_str(group_json).map_err(err)?;
+    let group = RsaQuotientGroup::new(group.g, group.m).map_err(err)?;
+    let items: Vec<Vec<String>> = serde_json::from_str(items_json).map_err(err)?;
+    let mut set = small_set::empty_set(group);
+    for item in &items {
+        set.insert(parse_item(item)?);
+    }
+    Ok(set.digest().to_string())
+}
+
+/// Proves the swap in `inputs_json` (a `SwapJson`) with the bellman-serialized `params_bytes`.
+/// Returns the proof as JSON.
+#[wasm_bindgen]
+pub fn prove(params_bytes: &[u8], inputs_json: &str) -> Result<String, JsValue> {
+    prove_json(params_bytes, inputs_json).map_err(|e| JsValue::from_str(&e))
+}
+
+/// The public inputs of the swap in `inputs_json`, as a JSON array of decimal strings.
+#[wasm_bindgen]
+pub fn public_inputs(inputs_json: &str) -> Result<String, JsValue> {
+    public_inputs_json(inputs_json).map_err(|e| JsValue::from_str(&e))
+}
+
+/// The public inputs of the swap in `inputs_json`, as a JSON array of 32-byte big-endian hex
+/// strings, in the same order as `public_inputs`.
+#[wasm_bindgen]
+pub fn public_inputs_hex(inputs_json: &str) -> Result<String, JsValue> {
+    public_inputs_hex_json(inputs_json).map_err(|e| JsValue::from_str(&e))
+}
+
+/// The accumulator element of the item in `item_json` (a JSON array of decimal field elements),
+/// as a decimal string.
+#[wasm_bindgen]
+pub fn element(item_json: &str) -> Result<String, JsValue> {
+    element_json(item_json).map_err(|e| JsValue::from_str(&e))
+}
+
+/// The digest, as a decimal string, of a set holding the items in `items_json` in the group
+/// `group_json` (an object with decimal strings `g` and `m`).
+#[wasm_bindgen]
+pub fn digest(group_json: &str, items_json: &str) -> Result<String, JsValue> {
+    digest_json(group_json, items_json).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Checks a JSON proof against a JSON verifying key and a JSON array of public inputs.
+#[wasm_bindgen]
+pub fn verify(vk_json: &str, proof_json: &str, public_json: &str) -> Result<bool, JsValue> {
+    verify_json(vk_json, proof_json, public_json).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::thread_rng;
+    use sapling_crypto::bellman::groth16::generate_random_parameters;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use io::groth16_json::from_parameters;
+    use io::small_set::SwapJson;
+
+    fn item(last: &str) -> Vec<String> {
+        ["0", "1", "2", "3", last].iter().map(|s| s.to_string()).collect()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn small_swap_proves_and_verifies() {
+        let params =
+            generate_random_parameters(small_circuit(1, None), &mut thread_rng()).unwrap();
+        let mut params_bytes = Vec::new();
+        params.write(&mut params_bytes).unwrap();
+        let vk_json = serde_json::to_string(&from_parameters(&params).1).unwrap();
+
+        let swap = serde_json::to_string(&SwapJson {
+            untouched: vec![],
+            removed: vec![item("4")],
+            inserted: vec![item("5")],
+        })
+        .unwrap();
+        let proof_json = prove_json(&params_bytes, &swap).unwrap();
+        let public_json = public_inputs_json(&swap).unwrap();
+        assert!(verify_json(&vk_json, &proof_json, &public_json).unwrap());
+
+        let mut public: Vec<String> = serde_json::from_str(&public_json).unwrap();
+        public[0] = "3".to_string();
+        let tampered = serde_json::to_string(&public).unwrap();
+        assert!(!verify_json(&vk_json, &proof_json, &tampered).unwrap());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn elements_and_digests_match_circuit() {
+        let swap = SwapJson {
+            untouched: vec![],
+            removed: vec![item("4")],
+            inserted: vec![item("5")],
+        };
+        let mut inputs = small_circuit(1, Some(&swap)).inputs.unwrap();
+        let group_json = serde_json::to_string(&small_set::small_group()).unwrap();
+        let items_json = |items: &[Vec<String>]| serde_json::to_string(items).unwrap();
+
+        let element = element_json(&serde_json::to_string(&item("4")).unwrap()).unwrap();
+        assert_eq!(element, inputs.initial_state.members()[0].to_string());
+        assert_eq!(
+            digest_json(&group_json, &items_json(&swap.removed)).unwrap(),
+            inputs.initial_state.digest().to_string()
+        );
+        assert_eq!(
+            digest_json(&group_json, &items_json(&swap.inserted)).unwrap(),
+            inputs.final_digest.to_string()
+        );
+        assert!(digest_json(&group_json, &items_json(&[vec!["1".to_string()]])).is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn malformed_swap() {
+        let swap = serde_json::to_string(&SwapJson {
+            untouched: vec![],
+            removed: vec![item("4")],
+            inserted: vec![],
+        })
+        .unwrap();
+        assert!(public_inputs_json(&swap).is_err());
+        assert!(public_inputs_json("not json").is_err());
+
+        let twice = serde_json::to_string(&SwapJson {
+            untouched: vec![],
+            removed: vec![item("4"), item("4")],
+            inserted: vec![item("5"), item("6")],
+        })
+        .unwrap();
+        assert!(public_inputs_json(&twice).is_err());
+    }
+}
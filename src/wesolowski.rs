@@ -81,7 +81,7 @@ pub fn proof_of_exp<'a, E: Engine, G: CircuitSemiGroup<E = E>, CS: ConstraintSys
     result: &G::Elem,
 ) -> Result<(), SynthesisError>
 where
-    G::Elem: Gadget<Value = <G::Group as SemiGroup>::Elem> + Debug,
+    G::Elem: Gadget<Value = <G::Group as SemiGroup>::Elem, Access = ()> + Debug,
 {
     let pf: Vec<&'a Reduced<E>> = power_factors.into_iter().collect();
     let q_value: Option<<G::Group as SemiGroup>::Elem> = {
@@ -97,12 +97,10 @@ where
         })
     };
     let r = {
-        let mut acc = BigNat::alloc_from_nat(
-            cs.namespace(|| "r"),
-            || Ok(BigUint::one()),
-            challenge.params.limb_width,
-            challenge.limbs.len(),
-        )?;
+        // The fold starts from the constant 1, so that with no factors `r` is 1 and the proof
+        // checks that `result` is `base`.
+        let mut acc = BigNat::identity::<CS>(challenge.params.limb_width)
+            .with_n_limbs::<CS>(challenge.limbs.len());
         for (i, f) in pf.into_iter().enumerate() {
             acc = acc
                 .mult_mod(
@@ -114,12 +112,7 @@ where
         }
         acc
     };
-    let q = <G::Elem as Gadget>::alloc(
-        cs.namespace(|| "Q"),
-        q_value.as_ref(),
-        base.access().clone(),
-        <G::Elem as Gadget>::params(base),
-    )?;
+    let q = group.alloc_elem(cs.namespace(|| "Q"), q_value.as_ref())?;
     let ql = group.power(cs.namespace(|| "Q^l"), &q, &challenge)?;
     let br = group.power(cs.namespace(|| "b^r"), &base, &r)?;
     let left = group.op(cs.namespace(|| "Q^l b^r"), &ql, &br)?;
@@ -272,6 +265,40 @@ mod tests {
                                                   },
                                                   true
                                                       ),
+        proof_2_to_nothing: (
+            PoE {
+                params: PoEParams {
+                    limb_width: 4,
+                    n_limbs_b: 2,
+                    n_limbs_e: 1,
+                },
+                inputs: Some(PoEInputs {
+                    b: "2",
+                    m: "255",
+                    exps: &[],
+                    l: "15",
+                    res: Some("2"),
+                }),
+            },
+            true
+        ),
+        proof_2_to_nothing_wrong: (
+            PoE {
+                params: PoEParams {
+                    limb_width: 4,
+                    n_limbs_b: 2,
+                    n_limbs_e: 1,
+                },
+                inputs: Some(PoEInputs {
+                    b: "2",
+                    m: "255",
+                    exps: &[],
+                    l: "15",
+                    res: Some("4"),
+                }),
+            },
+            false
+        ),
     }
 
     impl<'a, E: Engine> Circuit<E> for PoE<'a> {
@@ -328,6 +355,7 @@ mod tests {
                 &CircuitRsaGroupParams {
                     n_limbs: self.params.n_limbs_b,
                     limb_width: self.params.limb_width,
+                    window_bits: None,
                 },
             )?;
             let l = BigNat::alloc_from_nat(